@@ -18,7 +18,7 @@ use std::thread;
 use std::time::Duration;
 use tauri::Emitter;
 use tauri::Manager;
-#[cfg(desktop)]
+#[cfg(target_os = "macos")]
 use tauri_plugin_autostart::MacosLauncher;
 #[cfg(desktop)]
 use tauri_plugin_autostart::ManagerExt as AutostartManagerExt;
@@ -31,7 +31,27 @@ struct ManagedProcess {
     started_at: u64,
 }
 
-static MANAGED_CHILD: Lazy<Mutex<Option<ManagedProcess>>> = Lazy::new(|| Mutex::new(None));
+/// 按 workspace_id 索引，支持多个工作区的后端同时运行
+static MANAGED_CHILDREN: Lazy<Mutex<std::collections::HashMap<String, ManagedProcess>>> =
+    Lazy::new(|| Mutex::new(std::collections::HashMap::new()));
+
+/// 看门狗跟踪的工作区（由 Tauri 成功启动过的后端）
+struct WatchdogTarget {
+    venv_dir: String,
+    /// 用户主动停止（openakita_service_stop / 托盘退出）或判定为崩溃循环后置 true，看门狗不再拉起
+    user_stopped: bool,
+    restart_count: u32,
+    /// 最近 WATCHDOG_LOOP_WINDOW_SECS 内自动重启的时间戳，用于崩溃循环检测与退避
+    restart_times: Vec<u64>,
+    /// 首次发现本次退出的时间（退避计时起点）
+    crashed_at: Option<u64>,
+    /// 心跳连续健康的起始时间
+    healthy_since: Option<u64>,
+    last_exit_code: Option<i32>,
+}
+
+static WATCHDOG_TARGETS: Lazy<Mutex<std::collections::HashMap<String, WatchdogTarget>>> =
+    Lazy::new(|| Mutex::new(std::collections::HashMap::new()));
 
 /// Rust 自动启动后端时置 true，启动完成（成功/失败）后置 false。
 /// 前端可查询该标记以显示"正在自动启动服务"并禁用启动/重启按钮。
@@ -69,6 +89,12 @@ struct WorkspaceSummary {
     name: String,
     path: String,
     is_current: bool,
+    /// 创建时间（unix epoch 秒），旧工作区由迁移 v2 按目录 mtime 回填
+    #[serde(default)]
+    created_at: Option<u64>,
+    /// 最近一次成功启动后端的时间（unix epoch 秒）
+    #[serde(default)]
+    last_started_at: Option<u64>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
@@ -80,25 +106,116 @@ struct AppStateFile {
     current_workspace_id: Option<String>,
     #[serde(default)]
     workspaces: Vec<WorkspaceMeta>,
+    /// 应用级开关（config v3 起从顶层移入 settings 对象）
+    #[serde(default)]
+    settings: AppSettings,
+    // ── v2 及更早版本的顶层字段：只读不写，读取后由 fold_legacy_settings 并入 settings ──
+    #[serde(default, rename = "autoStartBackend", skip_serializing)]
+    legacy_auto_start_backend: Option<bool>,
+    #[serde(default, rename = "lastInstalledVersion", skip_serializing)]
+    legacy_last_installed_version: Option<String>,
+    #[serde(default, rename = "installMode", skip_serializing)]
+    legacy_install_mode: Option<String>,
+    #[serde(default, rename = "autoUpdate", skip_serializing)]
+    legacy_auto_update: Option<bool>,
+    /// 工作区 .env 未配置 API_PORT 时使用的默认端口（未设置时为 DEFAULT_API_PORT）
+    #[serde(default)]
+    default_api_port: Option<u16>,
+    /// 用户偏好的 pip 镜像（install_module / pip_install 未显式传入时使用）
+    #[serde(default)]
+    pip_mirror: Option<String>,
+    /// 后端异常退出时由看门狗自动重启
+    #[serde(default)]
+    auto_restart_backend: Option<bool>,
+    /// 出站 HTTP 代理（如 http://127.0.0.1:7890），用于下载 Python / 模块 / PyPI 查询等
+    #[serde(default)]
+    http_proxy: Option<String>,
+    /// Playwright 浏览器下载地址（PLAYWRIGHT_DOWNLOAD_HOST），未设置时用 PLAYWRIGHT_DOWNLOAD_MIRROR
+    #[serde(default)]
+    playwright_download_mirror: Option<String>,
+    /// Windows：把后端放进 Job Object，Setup Center 进程结束（含任务管理器强杀）时后端随之退出
+    #[serde(default)]
+    kill_backend_with_app: Option<bool>,
+    /// 停止后端时调用 /api/shutdown 后等待进程自行退出的秒数（默认 5，上限 60），超时后强制结束
+    #[serde(default)]
+    graceful_stop_timeout_secs: Option<u64>,
+    /// 心跳超过该秒数未更新即报告为过期（默认 DEFAULT_HEARTBEAT_STALE_SECS）
+    #[serde(default)]
+    heartbeat_stale_secs: Option<u64>,
+    /// 心跳超过该秒数未更新视为卡死并清理进程（默认 DEFAULT_HEARTBEAT_KILL_SECS，须大于 heartbeat_stale_secs）
+    #[serde(default)]
+    heartbeat_kill_secs: Option<u64>,
+    /// 开机自启时追加在 --background 之后的参数（如 ["--workspace", "<id>"]）
+    #[serde(default)]
+    autostart_args: Option<Vec<String>>,
+    /// 后端常驻内存上限（MB），连续超限时由内存守护重启后端；None / 0 表示关闭
+    #[serde(default)]
+    max_backend_memory_mb: Option<u64>,
+    /// 自定义的启动失败识别规则，优先于内置规则匹配（见 classify_startup_failure）
+    #[serde(default)]
+    startup_failure_patterns: Option<Vec<StartupFailurePattern>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+struct AppSettings {
     #[serde(default)]
     auto_start_backend: Option<bool>,
     #[serde(default)]
-    last_installed_version: Option<String>,
+    auto_update: Option<bool>,
     #[serde(default)]
     install_mode: Option<String>,
     #[serde(default)]
-    auto_update: Option<bool>,
+    last_installed_version: Option<String>,
+}
+
+impl AppStateFile {
+    /// 兼容未迁移（或被旧版本写回）的 state.json：settings 中缺失的值取旧的顶层字段
+    fn fold_legacy_settings(&mut self) {
+        let st = &mut self.settings;
+        if st.auto_start_backend.is_none() {
+            st.auto_start_backend = self.legacy_auto_start_backend.take();
+        }
+        if st.auto_update.is_none() {
+            st.auto_update = self.legacy_auto_update.take();
+        }
+        if st.install_mode.is_none() {
+            st.install_mode = self.legacy_install_mode.take();
+        }
+        if st.last_installed_version.is_none() {
+            st.last_installed_version = self.legacy_last_installed_version.take();
+        }
+    }
 }
 
 fn default_config_version() -> u32 {
     migrations::CURRENT_CONFIG_VERSION
 }
 
+const DEFAULT_API_PORT: u16 = 18900;
+
+fn default_api_port_from_state(state: &AppStateFile) -> u16 {
+    state.default_api_port.filter(|p| *p != 0).unwrap_or(DEFAULT_API_PORT)
+}
+
+/// 状态轮询时频繁用到，缓存在内存中，由 set_default_api_port / reload_cached_settings 刷新
+static DEFAULT_API_PORT_SETTING: Lazy<Mutex<u16>> =
+    Lazy::new(|| Mutex::new(default_api_port_from_state(&read_state_file())));
+
+/// 工作区未配置 API_PORT 时的回退端口（可通过 set_default_api_port 全局修改）
+fn default_api_port() -> u16 {
+    *DEFAULT_API_PORT_SETTING.lock().unwrap()
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 struct WorkspaceMeta {
     id: String,
     name: String,
+    #[serde(default)]
+    created_at: Option<u64>,
+    #[serde(default)]
+    last_started_at: Option<u64>,
 }
 
 fn openakita_root_dir() -> PathBuf {
@@ -144,9 +261,54 @@ fn start_onboarding_log(date_label: String) -> Result<String, String> {
     Ok(path.to_string_lossy().to_string())
 }
 
-/// 追加一行到安装配置日志（每行建议带时间戳，由前端拼接）。
+/// 日志行时间戳格式（ISO-8601 UTC，毫秒精度），前端可据此解析/排序
+const ONBOARDING_LOG_TIMESTAMP_FORMAT: &str = "YYYY-MM-DDTHH:MM:SS.mmmZ";
+
+/// 当前时间的 ISO-8601 UTC 字符串，如 2025-01-31T08:05:09.123Z
+fn iso8601_utc_now() -> String {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = now.as_secs();
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+    // civil_from_days（Howard Hinnant 算法），把 1970-01-01 起的天数换算为年月日
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60,
+        now.subsec_millis()
+    )
+}
+
+/// 追加日志行；`with_timestamp` 为 true 时由 Rust 侧统一加 `[ISO-8601 UTC]` 前缀
+fn format_onboarding_line(line: &str, with_timestamp: bool) -> String {
+    if with_timestamp {
+        format!("[{}] {}", iso8601_utc_now(), line)
+    } else {
+        line.to_string()
+    }
+}
+
+/// 安装配置日志使用的时间戳格式
+#[tauri::command]
+fn onboarding_log_timestamp_format() -> String {
+    ONBOARDING_LOG_TIMESTAMP_FORMAT.to_string()
+}
+
+/// 追加一行到安装配置日志。`with_timestamp`（默认 false，兼容前端自行拼接时间戳的旧调用）
+/// 为 true 时由后端加 ISO-8601 UTC 时间戳前缀。
 #[tauri::command]
-fn append_onboarding_log(log_path: String, line: String) -> Result<(), String> {
+fn append_onboarding_log(log_path: String, line: String, with_timestamp: Option<bool>) -> Result<(), String> {
     let path = PathBuf::from(&log_path);
     if !path.exists() {
         return Ok(());
@@ -155,14 +317,93 @@ fn append_onboarding_log(log_path: String, line: String) -> Result<(), String> {
         .append(true)
         .open(&path)
         .map_err(|e| format!("append onboarding log failed: {e}"))?;
+    let line = format_onboarding_line(&line, with_timestamp.unwrap_or(false));
     writeln!(f, "{}", line).map_err(|e| format!("write line failed: {e}"))?;
     f.flush().map_err(|e| format!("flush failed: {e}"))?;
     Ok(())
 }
 
+/// 读取安装配置日志末尾 `tail_bytes`（默认 40KB，上限 400KB），仅允许读取 setup_logs_dir 下的文件。
+#[tauri::command]
+fn read_onboarding_log(log_path: String, tail_bytes: Option<u64>) -> Result<ServiceLogChunk, String> {
+    let path = PathBuf::from(&log_path)
+        .canonicalize()
+        .map_err(|e| format!("onboarding log not found: {e}"))?;
+    let logs_dir = setup_logs_dir()
+        .canonicalize()
+        .map_err(|e| format!("logs dir not found: {e}"))?;
+    if !path.starts_with(&logs_dir) {
+        return Err(format!("not an onboarding log: {log_path}"));
+    }
+    let tail = tail_bytes.unwrap_or(40_000).min(400_000);
+    let mut f = fs::File::open(&path).map_err(|e| format!("open log failed: {e}"))?;
+    let len = f.metadata().map_err(|e| format!("stat log failed: {e}"))?.len();
+    let start = len.saturating_sub(tail);
+    f.seek(SeekFrom::Start(start))
+        .map_err(|e| format!("seek log failed: {e}"))?;
+    let mut buf = Vec::new();
+    f.read_to_end(&mut buf).map_err(|e| format!("read log failed: {e}"))?;
+
+    Ok(ServiceLogChunk {
+        path: path.to_string_lossy().to_string(),
+        content: String::from_utf8_lossy(&buf).to_string(),
+        truncated: start > 0,
+        next_offset: start + buf.len() as u64,
+        file_len: len,
+        reset: false,
+        matches: vec![],
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct LogFileInfo {
+    filename: String,
+    path: String,
+    size_bytes: u64,
+    /// 最后修改时间（Unix epoch 秒）
+    modified_at: u64,
+}
+
+/// 列出历史安装配置日志（onboarding-*.log），最新的在前。
+#[tauri::command]
+fn list_onboarding_logs() -> Vec<LogFileInfo> {
+    let mut out = vec![];
+    if let Ok(entries) = fs::read_dir(setup_logs_dir()) {
+        for entry in entries.flatten() {
+            let filename = entry.file_name().to_string_lossy().to_string();
+            if !filename.starts_with("onboarding-") || !filename.ends_with(".log") {
+                continue;
+            }
+            let Ok(meta) = entry.metadata() else { continue };
+            if !meta.is_file() {
+                continue;
+            }
+            let modified_at = meta
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            out.push(LogFileInfo {
+                filename,
+                path: entry.path().to_string_lossy().to_string(),
+                size_bytes: meta.len(),
+                modified_at,
+            });
+        }
+    }
+    out.sort_by_key(|f| std::cmp::Reverse(f.modified_at));
+    out
+}
+
 /// 批量追加多行到安装配置日志（用于写入配置快照等）。
 #[tauri::command]
-fn append_onboarding_log_lines(log_path: String, lines: Vec<String>) -> Result<(), String> {
+fn append_onboarding_log_lines(
+    log_path: String,
+    lines: Vec<String>,
+    with_timestamp: Option<bool>,
+) -> Result<(), String> {
     let path = PathBuf::from(&log_path);
     if !path.exists() || lines.is_empty() {
         return Ok(());
@@ -171,7 +412,9 @@ fn append_onboarding_log_lines(log_path: String, lines: Vec<String>) -> Result<(
         .append(true)
         .open(&path)
         .map_err(|e| format!("append onboarding log failed: {e}"))?;
+    let with_timestamp = with_timestamp.unwrap_or(false);
     for line in lines {
+        let line = format_onboarding_line(&line, with_timestamp);
         writeln!(f, "{}", line).map_err(|e| format!("write line failed: {e}"))?;
     }
     f.flush().map_err(|e| format!("flush failed: {e}"))?;
@@ -359,6 +602,23 @@ struct ModuleInfo {
     bundled: bool,
     size_mb: u32,
     category: String,
+    /// .installed 标记中记录的实际安装版本（包名 → 版本），旧格式标记或未安装时为空
+    installed_versions: std::collections::BTreeMap<String, String>,
+}
+
+/// 模块目录下的 `.installed` 标记内容
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+struct ModuleMarker {
+    installed_at: u64,
+    #[serde(default)]
+    packages: std::collections::BTreeMap<String, String>,
+}
+
+/// 读取 `.installed` 标记。早期版本写入的是 `installed_at=<secs>` 纯文本，解析失败时返回 None。
+fn read_module_marker(module_id: &str) -> Option<ModuleMarker> {
+    let content = fs::read_to_string(modules_dir().join(module_id).join(".installed")).ok()?;
+    serde_json::from_str(&content).ok()
 }
 
 fn module_definitions() -> Vec<(&'static str, &'static str, &'static str, &'static [&'static str], u32, &'static str)> {
@@ -374,6 +634,70 @@ fn module_definitions() -> Vec<(&'static str, &'static str, &'static str, &'stat
     ]
 }
 
+/// 模块包的版本约束：(module_id, package, 默认安装版本, 允许的版本范围)。
+/// 在线安装默认装固定版本，保证不同时间安装的机器依赖一致；
+/// install_module 的 versions 参数只能在允许范围内选择，update_module 也不会越过范围上限。
+const MODULE_VERSION_PINS: &[(&str, &str, &str, &str)] = &[
+    ("vector-memory", "sentence-transformers", "3.3.1", ">=2.2.0,<4"),
+    ("vector-memory", "chromadb", "0.5.23", ">=0.4.0,<0.6"),
+    ("vector-memory", "regex", "2024.11.6", ">=2023.6.3"),
+    ("whisper", "openai-whisper", "20240930", ">=20231117"),
+    ("whisper", "static-ffmpeg", "2.8", ">=2.7,<3"),
+    ("orchestration", "pyzmq", "26.2.0", ">=25.0.0,<27"),
+];
+
+fn module_version_pin(module_id: &str, package: &str) -> Option<(&'static str, &'static str)> {
+    let wanted = normalize_dist_name(package);
+    MODULE_VERSION_PINS
+        .iter()
+        .find(|(m, p, _, _)| *m == module_id && normalize_dist_name(p) == wanted)
+        .map(|(_, _, pin, range)| (*pin, *range))
+}
+
+/// 生成传给 pip 的包参数。
+/// - `requested` 中指定的版本必须落在 MODULE_VERSION_PINS 的允许范围内，否则报错；
+/// - `upgrade` 时未指定版本的包使用允许范围（`pip -U` 升到范围内最新）；
+/// - `use_default_pins` 时未指定版本的包固定到默认版本，否则沿用 module_definitions 中的原始写法。
+fn module_pip_specs(
+    module_id: &str,
+    packages: &[&str],
+    requested: Option<&std::collections::HashMap<String, String>>,
+    use_default_pins: bool,
+    upgrade: bool,
+) -> Result<Vec<String>, String> {
+    if let Some(req) = requested {
+        if let Some(unknown) = req.keys().find(|k| {
+            !packages.iter().any(|p| normalize_dist_name(package_spec_name(p)) == normalize_dist_name(k))
+        }) {
+            return Err(format!("模块 {} 不包含包 {}", module_id, unknown));
+        }
+    }
+
+    let mut specs = Vec::with_capacity(packages.len());
+    for spec in packages {
+        let name = package_spec_name(spec);
+        let pin = module_version_pin(module_id, name);
+        let version = requested.and_then(|req| {
+            req.iter()
+                .find(|(k, _)| normalize_dist_name(k) == normalize_dist_name(name))
+                .map(|(_, v)| v.trim())
+        });
+        specs.push(match (version, pin) {
+            (Some(v), Some((_, range))) => {
+                if !pep440_satisfies(v, range) {
+                    return Err(format!("{} {} 不在允许的版本范围 {} 内", name, v, range));
+                }
+                format!("{}=={}", name, v)
+            }
+            (Some(_), None) => return Err(format!("包 {} 不支持指定版本", name)),
+            (None, Some((_, range))) if upgrade => format!("{}{}", name, range),
+            (None, Some((default, _))) if use_default_pins => format!("{}=={}", name, default),
+            (None, _) => spec.to_string(),
+        });
+    }
+    Ok(specs)
+}
+
 fn is_module_installed(module_id: &str) -> bool {
     let sp = modules_dir().join(module_id).join("site-packages");
     if sp.exists() && sp.read_dir().map(|mut d| d.next().is_some()).unwrap_or(false) {
@@ -411,33 +735,386 @@ fn detect_modules() -> Vec<ModuleInfo> {
             bundled: is_module_bundled(id),
             size_mb: *size,
             category: cat.to_string(),
+            installed_versions: read_module_marker(id).map(|m| m.packages).unwrap_or_default(),
         })
         .collect()
 }
 
+/// 安装包内为模块预打包的离线 wheels 目录（存在即可离线安装）
+fn bundled_module_wheels_dir(module_id: &str) -> PathBuf {
+    bundled_backend_dir()
+        .parent()
+        .map(|p| p.join("modules").join(module_id).join("wheels"))
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct InstallPlan {
+    offline_available: bool,
+    wheels_dir: Option<String>,
+    packages: Vec<String>,
+    post_install_steps: Vec<String>,
+}
+
+/// 预先告知前端模块能否离线安装，便于在无网络环境下提前提示
+#[tauri::command]
+fn module_install_plan(module_id: String) -> Result<InstallPlan, String> {
+    let defs = module_definitions();
+    let (_, _, _, packages, _, _) = defs
+        .iter()
+        .find(|(id, _, _, _, _, _)| *id == module_id.as_str())
+        .ok_or_else(|| format!("未知模块: {}", module_id))?;
+
+    let wheels = bundled_module_wheels_dir(&module_id);
+    let offline_available = !wheels.as_os_str().is_empty() && wheels.exists();
+
+    // 与 install_module 中 run_pip_result 的收尾步骤保持一致
+    let post_install_steps = vec![
+        "写入安装标记 .installed".to_string(),
+        "重启 OpenAkita 服务以加载新模块".to_string(),
+    ];
+
+    Ok(InstallPlan {
+        offline_available,
+        wheels_dir: offline_available.then(|| wheels.to_string_lossy().to_string()),
+        packages: packages.iter().map(|p| p.to_string()).collect(),
+        post_install_steps,
+    })
+}
+
+/// 内置 pip 镜像源（按默认优先级排列）
+const DEFAULT_PIP_MIRRORS: &[&str] = &[
+    "https://mirrors.aliyun.com/pypi/simple/",
+    "https://pypi.tuna.tsinghua.edu.cn/simple/",
+    "https://pypi.org/simple/",
+];
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct MirrorProbe {
+    url: String,
+    reachable: bool,
+    /// 不可达时为 None
+    latency_ms: Option<u64>,
+}
+
+/// 并发对每个镜像的 /simple/ 根发 HEAD 请求（3 秒超时），按可达 + 延迟从快到慢排序。
+/// 只要服务器有响应（非 5xx）就视为可达——部分镜像不支持 HEAD 会返回 405。
+fn probe_pip_mirrors_sync(candidates: &[String]) -> Vec<MirrorProbe> {
+    let handles: Vec<_> = candidates
+        .iter()
+        .cloned()
+        .map(|url| {
+            std::thread::spawn(move || {
+                let t0 = std::time::Instant::now();
+                let reachable = http_client_builder(Duration::from_secs(3))
+                    .build()
+                    .ok()
+                    .and_then(|c| c.head(&url).send().ok())
+                    .map(|r| !r.status().is_server_error())
+                    .unwrap_or(false);
+                let latency_ms = reachable.then(|| t0.elapsed().as_millis() as u64);
+                MirrorProbe { url, reachable, latency_ms }
+            })
+        })
+        .collect();
+    let mut probes: Vec<MirrorProbe> = handles.into_iter().filter_map(|h| h.join().ok()).collect();
+    probes.sort_by_key(|p| p.latency_ms.unwrap_or(u64::MAX));
+    probes
+}
+
+/// 探测 pip 镜像可达性与延迟，供前端预选最快的源。`candidates` 为空时探测内置镜像列表。
+#[tauri::command]
+async fn probe_pip_mirrors(candidates: Option<Vec<String>>) -> Result<Vec<MirrorProbe>, String> {
+    let candidates = candidates
+        .filter(|c| !c.is_empty())
+        .unwrap_or_else(|| DEFAULT_PIP_MIRRORS.iter().map(|s| s.to_string()).collect());
+    spawn_blocking_result(move || Ok(probe_pip_mirrors_sync(&candidates))).await
+}
+
+/// 正在进行的模块安装（install_module / update_module），按 module_id 登记。
+/// `child` 为当前正在运行的 pip 子进程，供 cancel_module_install 终止。
+struct ModuleInstallJob {
+    child: Option<std::process::Child>,
+    cancelled: bool,
+    /// pip 已成功结束、正在替换 site-packages / 写入 .installed，此时不再接受取消
+    finalizing: bool,
+    /// 取消时要删除的未完成产物（目录或文件）
+    cleanup: Vec<PathBuf>,
+}
+
+static MODULE_INSTALLS: Lazy<Mutex<std::collections::HashMap<String, ModuleInstallJob>>> =
+    Lazy::new(|| Mutex::new(std::collections::HashMap::new()));
+
+const MODULE_INSTALL_CANCELLED: &str = "安装已取消";
+
+/// 模块安装登记守卫：离开作用域时从 MODULE_INSTALLS 移除
+struct ModuleInstallGuard(String);
+
+impl ModuleInstallGuard {
+    /// 同一模块已有安装在进行时拒绝。`cleanup` 为取消时要删除的未完成产物。
+    fn register(module_id: &str, cleanup: Vec<PathBuf>) -> Result<Self, String> {
+        let mut jobs = MODULE_INSTALLS.lock().unwrap_or_else(|e| e.into_inner());
+        if jobs.contains_key(module_id) {
+            return Err(format!("模块 {} 正在安装中，请等待完成或先取消", module_id));
+        }
+        jobs.insert(
+            module_id.to_string(),
+            ModuleInstallJob { child: None, cancelled: false, finalizing: false, cleanup },
+        );
+        Ok(Self(module_id.to_string()))
+    }
+
+    /// 进入收尾阶段（替换 site-packages、写安装标记）。已被取消时返回 MODULE_INSTALL_CANCELLED，
+    /// 否则之后的 cancel_module_install 会被拒绝，避免收尾到一半时产物被删除。
+    fn begin_finalize(&self) -> Result<(), String> {
+        let mut jobs = MODULE_INSTALLS.lock().unwrap_or_else(|e| e.into_inner());
+        match jobs.get_mut(&self.0) {
+            Some(job) if !job.cancelled => {
+                job.finalizing = true;
+                Ok(())
+            }
+            _ => Err(MODULE_INSTALL_CANCELLED.into()),
+        }
+    }
+}
+
+impl Drop for ModuleInstallGuard {
+    fn drop(&mut self) {
+        if let Ok(mut jobs) = MODULE_INSTALLS.lock() {
+            jobs.remove(&self.0);
+        }
+    }
+}
+
+/// 在后台线程读完子进程的 stdout / stderr
+fn spawn_pipe_reader<R: Read + Send + 'static>(pipe: Option<R>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut p) = pipe {
+            let _ = p.read_to_end(&mut buf);
+        }
+        buf
+    })
+}
+
+/// Command::output() 的带超时版本：超时后结束子进程树并返回错误
+fn command_output_with_timeout(mut cmd: Command, timeout: Duration) -> Result<std::process::Output, String> {
+    let mut child = cmd
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+    let stdout = spawn_pipe_reader(child.stdout.take());
+    let stderr = spawn_pipe_reader(child.stderr.take());
+    let deadline = std::time::Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if std::time::Instant::now() < deadline => thread::sleep(Duration::from_millis(100)),
+            _ => {
+                kill_process_tree(child.id(), true);
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("执行超时（{} 秒）", timeout.as_secs()));
+            }
+        }
+    };
+    Ok(std::process::Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+/// 启动安装子进程并登记到 MODULE_INSTALLS，等待其结束（等价于 Command::output()）。
+/// 安装已被取消时不再启动新进程；等待期间被取消则返回 MODULE_INSTALL_CANCELLED。
+fn run_module_install_child(module_id: &str, mut cmd: Command, what: &str) -> Result<std::process::Output, String> {
+    let mut child = cmd
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("执行 {} 失败: {e}", what))?;
+    // 管道必须持续读取，否则输出量大时 pip 会阻塞在写管道上
+    let stdout = spawn_pipe_reader(child.stdout.take());
+    let stderr = spawn_pipe_reader(child.stderr.take());
+
+    {
+        let mut jobs = MODULE_INSTALLS.lock().unwrap_or_else(|e| e.into_inner());
+        match jobs.get_mut(module_id) {
+            Some(job) if !job.cancelled => job.child = Some(child),
+            _ => {
+                drop(jobs);
+                kill_process_tree(child.id(), true);
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(MODULE_INSTALL_CANCELLED.into());
+            }
+        }
+    }
+
+    let status = loop {
+        {
+            let mut jobs = MODULE_INSTALLS.lock().unwrap_or_else(|e| e.into_inner());
+            match jobs.get_mut(module_id) {
+                Some(job) if !job.cancelled => {
+                    if let Some(child) = job.child.as_mut() {
+                        if let Ok(Some(status)) = child.try_wait() {
+                            job.child = None;
+                            break Some(status);
+                        }
+                    }
+                }
+                // cancel_module_install 已取走并结束了子进程
+                _ => break None,
+            }
+        }
+        thread::sleep(Duration::from_millis(200));
+    };
+
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+    match status {
+        Some(status) => Ok(std::process::Output { status, stdout, stderr }),
+        None => Err(MODULE_INSTALL_CANCELLED.into()),
+    }
+}
+
+/// 取消正在进行的模块安装：结束 pip 子进程树，删除未完成的产物，
+/// 并发出 status = "cancelled" 的 module-install-progress 事件。
+/// 首次安装删除写了一半的 site-packages；更新安装在暂存目录中进行，只删暂存目录，原有模块保持可用。
+#[tauri::command]
+async fn cancel_module_install(app: tauri::AppHandle, module_id: String) -> Result<String, String> {
+    let (child, cleanup) = {
+        let mut jobs = MODULE_INSTALLS.lock().unwrap_or_else(|e| e.into_inner());
+        let job = jobs
+            .get_mut(&module_id)
+            .ok_or_else(|| format!("模块 {} 没有正在进行的安装", module_id))?;
+        if job.finalizing {
+            return Err(format!("模块 {} 已完成，无法取消", module_id));
+        }
+        job.cancelled = true;
+        (job.child.take(), job.cleanup.clone())
+    };
+    spawn_blocking_result(move || {
+        if let Some(mut child) = child {
+            kill_process_tree(child.id(), true);
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+
+        for path in &cleanup {
+            if path.is_dir() {
+                force_remove_dir(path).map_err(|e| format!("删除未完成的安装目录失败: {e}"))?;
+            } else {
+                let _ = fs::remove_file(path);
+            }
+        }
+
+        let _ = app.emit("module-install-progress", serde_json::json!({
+            "moduleId": module_id, "status": "cancelled",
+            "message": format!("{} 安装已取消，已清理未完成的文件", module_id),
+        }));
+        Ok(format!("{} 安装已取消", module_id))
+    })
+    .await
+}
+
+/// 更新模块时 pip 安装到的暂存目录，成功后与 site-packages 交换
+fn module_staging_dir(module_id: &str) -> PathBuf {
+    modules_dir().join(module_id).join("site-packages.updating")
+}
+
+/// 用暂存目录替换 site-packages：旧目录先改名为 site-packages.old，替换失败时还原。
+/// Windows 上后端正在加载模块中的 .pyd 时改名会失败，需要先停止服务。
+fn swap_in_staged_site_packages(module_id: &str) -> Result<(), String> {
+    let module_path = modules_dir().join(module_id);
+    let live = module_path.join("site-packages");
+    let staging = module_staging_dir(module_id);
+    let backup = module_path.join("site-packages.old");
+    if backup.exists() {
+        force_remove_dir(&backup).map_err(|e| format!("删除旧备份目录失败: {e}"))?;
+    }
+    if live.exists() {
+        fs::rename(&live, &backup)
+            .map_err(|e| format!("替换 site-packages 失败（模块可能正被后端使用，请先停止服务）: {e}"))?;
+    }
+    if let Err(e) = fs::rename(&staging, &live) {
+        let _ = fs::rename(&backup, &live);
+        return Err(format!("替换 site-packages 失败: {e}"));
+    }
+    let _ = force_remove_dir(&backup);
+    Ok(())
+}
+
 #[tauri::command]
 async fn install_module(
     app: tauri::AppHandle,
     module_id: String,
     mirror: Option<String>,
+    probe_mirrors: Option<bool>,
+    versions: Option<std::collections::HashMap<String, String>>,
+) -> Result<String, String> {
+    pip_install_module(app, module_id, mirror, probe_mirrors, versions, false)
+}
+
+/// 把模块更新到最新版本：与 install_module 相同的镜像回退流程，pip 追加 -U。
+#[tauri::command]
+async fn update_module(app: tauri::AppHandle, module_id: String, mirror: Option<String>) -> Result<String, String> {
+    if !is_module_installed(&module_id) {
+        return Err(format!("模块 {} 尚未安装", module_id));
+    }
+    spawn_blocking_result(move || pip_install_module(app, module_id, mirror, None, None, true)).await
+}
+
+/// install_module / update_module 的实现。`upgrade` 为 true 时 pip 带 -U 安装到暂存目录，成功后再替换
+/// site-packages（取消或失败不影响原有模块），并跳过 PyTorch 预装。
+/// `versions` 为包名 → 版本，用于安装指定版本（见 module_pip_specs）。
+fn pip_install_module(
+    app: tauri::AppHandle,
+    module_id: String,
+    mirror: Option<String>,
+    probe_mirrors: Option<bool>,
+    versions: Option<std::collections::HashMap<String, String>>,
+    upgrade: bool,
 ) -> Result<String, String> {
+    let action = if upgrade { "更新" } else { "安装" };
     // 从 module_definitions() 获取包列表（单一数据源，避免重复定义）
     let defs = module_definitions();
     let (_, _, _, packages, _, _) = defs
         .iter()
         .find(|(id, _, _, _, _, _)| *id == module_id.as_str())
         .ok_or_else(|| format!("未知模块: {}", module_id))?;
+    // 先校验版本参数，避免白白下载 Python / 创建目录
+    let pip_specs = module_pip_specs(&module_id, packages, versions.as_ref(), true, upgrade)?;
 
-    let target_dir = modules_dir().join(&module_id).join("site-packages");
+    let live_dir = modules_dir().join(&module_id).join("site-packages");
+    let target_dir = if upgrade { module_staging_dir(&module_id) } else { live_dir.clone() };
+    let cleanup = if upgrade {
+        vec![target_dir.clone()]
+    } else {
+        vec![live_dir.clone(), modules_dir().join(&module_id).join(".installed")]
+    };
+    let install_guard = ModuleInstallGuard::register(&module_id, cleanup)?;
+    if upgrade && target_dir.exists() {
+        // 上次更新中断留下的暂存目录
+        force_remove_dir(&target_dir).map_err(|e| format!("清理暂存目录失败: {e}"))?;
+    }
     fs::create_dir_all(&target_dir)
         .map_err(|e| format!("创建模块目录失败: {e}"))?;
+    let discard_staging = || {
+        if upgrade {
+            let _ = force_remove_dir(&target_dir);
+        }
+    };
 
     // Check for bundled wheels first
-    let bundled_wheels = bundled_backend_dir()
-        .parent()
-        .map(|p| p.join("modules").join(&module_id).join("wheels"))
-        .unwrap_or_default();
+    let bundled_wheels = bundled_module_wheels_dir(&module_id);
 
+    // 显式传入的镜像 > 用户保存的偏好 > 阿里云
+    let mirror = mirror.or_else(preferred_pip_mirror);
     let effective_mirror = mirror.clone().unwrap_or_else(|| {
         "https://mirrors.aliyun.com/pypi/simple/".to_string()
     });
@@ -471,18 +1148,43 @@ async fn install_module(
             // ── Post-install hooks (模块特定的额外安装步骤) ──
             // 注: browser 模块已内置到 core 包，不再需要 post-install hook
 
-            let marker = modules_dir().join(&module_id).join(".installed");
-            let _ = fs::write(&marker, format!("installed_at={}", now_epoch_secs()));
+            // pip 结束后、收尾前被取消时，cancel_module_install 已清理产物，不能再写安装标记
+            install_guard.begin_finalize()?;
+            if upgrade {
+                if let Err(e) = swap_in_staged_site_packages(&module_id) {
+                    discard_staging();
+                    return Err(e);
+                }
+            }
+
+            // 记录 pip 实际解析出的版本（从 dist-info 读取），供 detect_modules 展示
+            let marker = ModuleMarker {
+                installed_at: now_epoch_secs(),
+                packages: packages
+                    .iter()
+                    .filter_map(|spec| {
+                        let name = package_spec_name(spec);
+                        installed_dist_version(&live_dir, name).map(|v| (name.to_string(), v))
+                    })
+                    .collect(),
+            };
+            if let Ok(json) = serde_json::to_string_pretty(&marker) {
+                let _ = fs::write(modules_dir().join(&module_id).join(".installed"), json);
+            }
             let _ = app.emit("module-install-progress", serde_json::json!({
                 "moduleId": module_id, "status": "done",
-                "message": format!("{} 安装完成 ({})", module_id, label),
+                "message": format!("{} {}完成 ({})", module_id, action, label),
             }));
             // 提示用户重启服务以加载新安装的模块
             let _ = app.emit("module-install-progress", serde_json::json!({
                 "moduleId": module_id, "status": "restart-hint",
-                "message": "模块已安装，建议重启 OpenAkita 服务以加载新模块",
+                "message": if upgrade {
+                    "模块已更新，建议重启 OpenAkita 服务以加载新版本"
+                } else {
+                    "模块已安装，建议重启 OpenAkita 服务以加载新模块"
+                },
             }));
-            Ok(format!("{} 安装成功", module_id))
+            Ok(format!("{} {}成功", module_id, action))
         } else {
             let stderr = String::from_utf8_lossy(&output.stderr);
             let stdout = String::from_utf8_lossy(&output.stdout);
@@ -506,12 +1208,19 @@ async fn install_module(
         c.args(["-m", "pip", "install", "--no-index", "--find-links"]);
         c.arg(&bundled_wheels);
         c.arg("--target").arg(&target_dir);
-        for pkg in *packages { c.arg(*pkg); }
+        if upgrade {
+            c.arg("-U");
+        }
+        // 离线 wheels 本身就是固定版本，不套用默认版本（可能与打包时的版本不一致）
+        c.args(module_pip_specs(&module_id, packages, versions.as_ref(), false, upgrade)?);
         apply_no_window(&mut c);
-        let output = c.stdout(std::process::Stdio::piped()).stderr(std::process::Stdio::piped())
-            .output().map_err(|e| format!("执行 pip 失败: {e}"))?;
+        let output = run_module_install_child(&module_id, c, "pip")?;
         let result = run_pip_result(output, "离线");
         if let Err(ref e) = result {
+            if e == MODULE_INSTALL_CANCELLED {
+                return result;
+            }
+            discard_staging();
             let _ = app.emit("module-install-progress", serde_json::json!({
                 "moduleId": module_id, "status": "error", "message": &e[..e.len().min(800)],
             }));
@@ -522,7 +1231,7 @@ async fn install_module(
     // ── 在线安装：多源自动切换 ──
     // 镜像优先级列表：用户指定源 > 阿里云 > 清华 > 官方 PyPI
     let user_host = effective_mirror.split("//").nth(1).unwrap_or("").split('/').next().unwrap_or("").to_string();
-    let mirror_list: Vec<(&str, String)> = if mirror.is_some() {
+    let mut mirror_list: Vec<(&str, String)> = if mirror.is_some() {
         vec![
             (effective_mirror.as_str(), user_host.clone()),
             ("https://mirrors.aliyun.com/pypi/simple/", "mirrors.aliyun.com".into()),
@@ -537,6 +1246,14 @@ async fn install_module(
         ]
     };
 
+    // 可选：按探测结果重排内置镜像（用户指定源始终排第一），避免在不可达的源上白等超时
+    if probe_mirrors.unwrap_or(false) {
+        let fixed = if mirror.is_some() { 1 } else { 0 };
+        let urls: Vec<String> = mirror_list[fixed..].iter().map(|(u, _)| u.to_string()).collect();
+        let order: Vec<String> = probe_pip_mirrors_sync(&urls).into_iter().map(|p| p.url).collect();
+        mirror_list[fixed..].sort_by_key(|(u, _)| order.iter().position(|o| o == u).unwrap_or(usize::MAX));
+    }
+
     // 根据模块估算大小调整超时时间
     // whisper/vector-memory 含 PyTorch(~2.5GB)，需要更长超时
     let is_heavy_module = module_id == "whisper" || module_id == "vector-memory";
@@ -544,7 +1261,7 @@ async fn install_module(
     let retry_timeout = if is_heavy_module { "300" } else { "60" };
 
     // 对含 PyTorch 的大模块，先单独安装 torch 以获得更好的错误提示
-    if is_heavy_module {
+    if is_heavy_module && !upgrade {
         let _ = app.emit("module-install-progress", serde_json::json!({
             "moduleId": module_id,
             "status": "installing",
@@ -561,7 +1278,8 @@ async fn install_module(
         torch_cmd.args(["--prefer-binary", "--no-cache-dir"]);
         torch_cmd.arg("torch");
         apply_no_window(&mut torch_cmd);
-        match torch_cmd.stdout(std::process::Stdio::piped()).stderr(std::process::Stdio::piped()).output() {
+        apply_proxy_env(&mut torch_cmd);
+        match run_module_install_child(&module_id, torch_cmd, "pip") {
             Ok(out) if out.status.success() => {
                 let _ = app.emit("module-install-progress", serde_json::json!({
                     "moduleId": module_id, "status": "installing",
@@ -585,7 +1303,7 @@ async fn install_module(
             "moduleId": module_id,
             "status": "installing",
             "message": if idx == 0 {
-                format!("正在安装 {} (源: {}) ...", module_id, trusted_host)
+                format!("正在{} {} (源: {}) ...", action, module_id, trusted_host)
             } else {
                 format!("切换镜像源: {} (第 {} 次重试) ...", trusted_host, idx)
             },
@@ -601,10 +1319,14 @@ async fn install_module(
         // --prefer-binary: 优先使用预编译 wheel，避免在无编译工具链的打包环境中构建失败
         // --no-cache-dir: 避免缓存损坏导致的安装失败
         c.args(["--prefer-binary", "--no-cache-dir"]);
-        for pkg in *packages { c.arg(*pkg); }
+        if upgrade {
+            c.arg("-U");
+        }
+        c.args(&pip_specs);
         apply_no_window(&mut c);
+        apply_proxy_env(&mut c);
 
-        match c.stdout(std::process::Stdio::piped()).stderr(std::process::Stdio::piped()).output() {
+        match run_module_install_child(&module_id, c, "pip") {
             Ok(output) => {
                 if output.status.success() {
                     return run_pip_result(output, trusted_host);
@@ -636,13 +1358,15 @@ async fn install_module(
                     "message": format!("源 {} 安装失败 (退出码 {})，尝试切换...", trusted_host, exit_code),
                 }));
             }
+            Err(e) if e == MODULE_INSTALL_CANCELLED => return Err(e),
             Err(e) => {
-                last_err = format!("执行 pip 失败: {}", e);
+                last_err = e;
                 break; // pip 本身执行失败
             }
         }
     }
 
+    discard_staging();
     let _ = app.emit("module-install-progress", serde_json::json!({
         "moduleId": module_id, "status": "error",
         "message": &last_err[..last_err.len().min(800)],
@@ -660,34 +1384,169 @@ fn uninstall_module(module_id: String) -> Result<String, String> {
     Ok(format!("{} 已卸载", module_id))
 }
 
-#[tauri::command]
-fn is_first_run() -> bool {
-    let state = read_state_file();
-    state.workspaces.is_empty()
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct PackageVerify {
+    /// module_definitions 中的 pip 包名（已去掉版本约束）
+    package: String,
+    /// 实际尝试 import 的名字（来自 top_level.txt，缺失时由包名推导）
+    import_names: Vec<String>,
+    ok: bool,
+    error: Option<String>,
 }
 
-// ── 环境检测 ──
-
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
-struct EnvironmentCheck {
-    /// 实际检查的根目录路径，便于用户核对是否与已删除的目录一致（如以管理员运行可能为另一用户目录）
-    openakita_root: String,
-    has_old_venv: bool,
-    has_old_runtime: bool,
-    has_old_workspaces: bool,
-    old_version: Option<String>,
-    current_version: String,
-    running_processes: Vec<String>,
-    disk_usage_mb: u64,
-    conflicts: Vec<String>,
+struct ModuleVerify {
+    module_id: String,
+    ok: bool,
+    packages: Vec<PackageVerify>,
 }
 
-fn dir_size_bytes(path: &Path) -> u64 {
-    if !path.exists() {
-        return 0;
-    }
-    let mut total: u64 = 0;
+/// pip 包名规范化（PEP 503 简化版）：小写，`-` / `.` 统一为 `_`
+fn normalize_dist_name(name: &str) -> String {
+    name.trim().to_lowercase().replace(['-', '.'], "_")
+}
+
+/// 从 pip 包说明（如 "regex>=2023.6.3"、"pkg[extra]"）中取出包名
+fn package_spec_name(spec: &str) -> &str {
+    let end = spec
+        .find(|c: char| "<>=!~[;@ ".contains(c))
+        .unwrap_or(spec.len());
+    spec[..end].trim()
+}
+
+/// 在 site-packages 中找到包对应的 *.dist-info，返回其 top_level.txt 中的可导入名。
+/// 找不到 dist-info 返回 None；没有 top_level.txt 时按包名推导。
+fn dist_import_names(site_packages: &Path, package: &str) -> Option<Vec<String>> {
+    let wanted = normalize_dist_name(package);
+    let dist_info = fs::read_dir(site_packages).ok()?.flatten().map(|e| e.path()).find(|p| {
+        let name = p.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        name.ends_with(".dist-info")
+            && normalize_dist_name(name.split('-').next().unwrap_or("")) == wanted
+    })?;
+    let names: Vec<String> = fs::read_to_string(dist_info.join("top_level.txt"))
+        .unwrap_or_default()
+        .lines()
+        .map(|l| l.trim().to_string())
+        .filter(|l| !l.is_empty() && !l.starts_with('_'))
+        .collect();
+    if names.is_empty() {
+        Some(vec![wanted])
+    } else {
+        Some(names)
+    }
+}
+
+/// 校验已安装模块是否完整：对每个包的顶层名执行一次 import（只使用模块自己的 site-packages 优先），
+/// 用于发现中断安装留下的半成品目录，前端据此提供“修复（重新安装）”。
+#[tauri::command]
+async fn module_verify(module_id: String) -> Result<ModuleVerify, String> {
+    spawn_blocking_result(move || {
+        let defs = module_definitions();
+        let (_, _, _, packages, _, _) = defs
+            .iter()
+            .find(|(id, _, _, _, _, _)| *id == module_id.as_str())
+            .ok_or_else(|| format!("未知模块: {}", module_id))?;
+        let site_packages = modules_dir().join(&module_id).join("site-packages");
+        if !site_packages.exists() {
+            return Err(format!("模块 {} 未安装", module_id));
+        }
+        let python_exe = find_pip_python().ok_or_else(|| "未找到 Python 环境".to_string())?;
+
+        let mut results = vec![];
+        for spec in packages.iter() {
+            let package = package_spec_name(spec).to_string();
+            let Some(import_names) = dist_import_names(&site_packages, &package) else {
+                results.push(PackageVerify {
+                    package,
+                    import_names: vec![],
+                    ok: false,
+                    error: Some("未找到 dist-info，包未安装或安装不完整".into()),
+                });
+                continue;
+            };
+            let mut error = None;
+            for name in &import_names {
+                let mut c = Command::new(&python_exe);
+                c.args([
+                    "-c",
+                    "import sys, importlib; sys.path.insert(0, sys.argv[1]); importlib.import_module(sys.argv[2])",
+                ]);
+                c.arg(&site_packages).arg(name);
+                c.env("PYTHONUTF8", "1");
+                c.env("PYTHONIOENCODING", "utf-8");
+                apply_no_window(&mut c);
+                match c.output() {
+                    Ok(out) if out.status.success() => {}
+                    Ok(out) => {
+                        let stderr = String::from_utf8_lossy(&out.stderr);
+                        let last = stderr.lines().rev().find(|l| !l.trim().is_empty()).unwrap_or("");
+                        error = Some(format!("import {name} 失败: {last}"));
+                        break;
+                    }
+                    Err(e) => {
+                        error = Some(format!("执行 Python 失败: {e}"));
+                        break;
+                    }
+                }
+            }
+            results.push(PackageVerify {
+                package,
+                import_names,
+                ok: error.is_none(),
+                error,
+            });
+        }
+        Ok(ModuleVerify {
+            module_id,
+            ok: results.iter().all(|r| r.ok),
+            packages: results,
+        })
+    })
+    .await
+}
+
+#[tauri::command]
+fn is_first_run() -> bool {
+    let state = read_state_file();
+    state.workspaces.is_empty()
+}
+
+// ── 环境检测 ──
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct EnvironmentCheck {
+    /// 实际检查的根目录路径，便于用户核对是否与已删除的目录一致（如以管理员运行可能为另一用户目录）
+    openakita_root: String,
+    has_old_venv: bool,
+    has_old_runtime: bool,
+    has_old_workspaces: bool,
+    old_version: Option<String>,
+    current_version: String,
+    running_processes: Vec<String>,
+    disk_usage_mb: u64,
+    /// 主要子目录（modules / runtime / venv / workspaces / logs）各自的占用
+    usage_breakdown: Vec<DirUsage>,
+    conflicts: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct DirUsage {
+    name: String,
+    size_mb: u64,
+}
+
+/// check_environment 中单独统计占用的顶层子目录
+const USAGE_BREAKDOWN_DIRS: &[&str] = &["modules", "runtime", "venv", "workspaces", "logs"];
+
+fn dir_size_bytes(path: &Path) -> u64 {
+    if !path.exists() {
+        return 0;
+    }
+    let mut total: u64 = 0;
     if let Ok(entries) = fs::read_dir(path) {
         for entry in entries.flatten() {
             let p = entry.path();
@@ -701,6 +1560,8 @@ fn dir_size_bytes(path: &Path) -> u64 {
     total
 }
 
+/// 只读的环境检测：不会删除任何目录。清理旧 venv / runtime 需由前端在向用户确认后
+/// 显式调用 cleanup_old_environment。
 #[tauri::command]
 fn check_environment() -> EnvironmentCheck {
     let root = openakita_root_dir();
@@ -720,7 +1581,7 @@ fn check_environment() -> EnvironmentCheck {
 
     // Read version from state.json
     let state = read_state_file();
-    let old_version = state.last_installed_version.clone();
+    let old_version = state.settings.last_installed_version.clone();
     let current_version = env!("CARGO_PKG_VERSION").to_string();
 
     // Check running processes (extract workspace_id from filename: openakita-{ws_id}.pid)
@@ -744,7 +1605,27 @@ fn check_environment() -> EnvironmentCheck {
         }
     }
 
-    let disk_usage_mb = dir_size_bytes(&root) / (1024 * 1024);
+    // 只遍历一次：先统计每个顶层条目的大小，总占用与分项都由此得出，避免对模型等大目录重复递归
+    let mut entry_sizes: std::collections::HashMap<String, u64> = std::collections::HashMap::new();
+    if let Ok(entries) = fs::read_dir(&root) {
+        for entry in entries.flatten() {
+            let p = entry.path();
+            let size = if p.is_dir() {
+                dir_size_bytes(&p)
+            } else {
+                p.metadata().map(|m| m.len()).unwrap_or(0)
+            };
+            entry_sizes.insert(entry.file_name().to_string_lossy().to_string(), size);
+        }
+    }
+    let disk_usage_mb = entry_sizes.values().sum::<u64>() / (1024 * 1024);
+    let usage_breakdown: Vec<DirUsage> = USAGE_BREAKDOWN_DIRS
+        .iter()
+        .map(|name| DirUsage {
+            name: name.to_string(),
+            size_mb: entry_sizes.get(*name).copied().unwrap_or(0) / (1024 * 1024),
+        })
+        .collect();
 
     // venv 和 runtime 是打包后应用运行时所必需的环境组件：
     // - venv: 用于 pip install 模块（vector-memory/whisper 等）和工具执行
@@ -766,6 +1647,7 @@ fn check_environment() -> EnvironmentCheck {
         current_version,
         running_processes: running,
         disk_usage_mb,
+        usage_breakdown,
         conflicts,
     }
 }
@@ -803,9 +1685,29 @@ fn force_remove_dir(path: &std::path::Path) -> Result<(), String> {
     }
 }
 
+/// 清理旧的 venv / runtime 目录。
+/// `dry_run` 默认为 true：只报告将要删除的目录及大小，调用方必须显式传 false 才会真正删除。
 #[tauri::command]
-fn cleanup_old_environment(clean_venv: bool, clean_runtime: bool) -> Result<String, String> {
+fn cleanup_old_environment(
+    clean_venv: bool,
+    clean_runtime: bool,
+    dry_run: Option<bool>,
+) -> Result<String, String> {
     let root = openakita_root_dir();
+    if dry_run.unwrap_or(true) {
+        let mut planned = Vec::new();
+        for (enabled, name) in [(clean_venv, "venv"), (clean_runtime, "runtime")] {
+            let path = root.join(name);
+            if enabled && path.exists() {
+                planned.push(format!("{} ({} MB)", path.display(), dir_size_bytes(&path) / (1024 * 1024)));
+            }
+        }
+        return Ok(if planned.is_empty() {
+            "无需清理".to_string()
+        } else {
+            format!("将清理: {}", planned.join(", "))
+        });
+    }
     let mut cleaned = Vec::new();
     let mut warnings = Vec::new();
 
@@ -863,17 +1765,32 @@ fn service_pid_file(workspace_id: &str) -> PathBuf {
 }
 
 // ── PID 文件 JSON 格式 ──
+// 约定：{run_dir}/openakita-{workspace_id}.pid，内容为
+//   {"pid": 1234, "started_by": "tauri" | "external", "started_at": <unix epoch 秒>}
+// - "tauri"：由 Setup Center 启动，退出时一并停止；缺省值（兼容旧版纯数字格式）
+// - "external"：由 CLI / 用户自行启动，退出时保留。CLI 包装脚本若写 PID 文件须标记为 external；
+//   未写 PID 文件的外部后端由 tag_external_backends 在启动对账与退出前补登记。
+// - "tauri-adopted"：外部启动后经 adopt_external_service 接管，退出时与 tauri 一样停止。
+// 可选字段 "control_token"：Setup Center 启动时生成并经 OPENAKITA_CONTROL_TOKEN 传给后端，
+//   调用 /api/shutdown 时作为 Bearer 令牌；缺失时（旧版 / 外部后端）不带令牌调用。
+const STARTED_BY_TAURI: &str = "tauri";
+const STARTED_BY_EXTERNAL: &str = "external";
+const STARTED_BY_TAURI_ADOPTED: &str = "tauri-adopted";
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 struct PidFileData {
     pid: u32,
     #[serde(default = "default_started_by")]
-    started_by: String, // "tauri" | "external"
+    started_by: String, // STARTED_BY_TAURI | STARTED_BY_EXTERNAL | STARTED_BY_TAURI_ADOPTED
     #[serde(default)]
     started_at: u64,    // unix epoch seconds
+    /// 调用 /api/shutdown 等控制接口时携带的令牌（旧版 PID 文件没有该字段）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    control_token: Option<String>,
 }
 
 fn default_started_by() -> String {
-    "tauri".to_string()
+    STARTED_BY_TAURI.to_string()
 }
 
 fn now_epoch_secs() -> u64 {
@@ -883,11 +1800,12 @@ fn now_epoch_secs() -> u64 {
         .unwrap_or(0)
 }
 
-fn write_pid_file(workspace_id: &str, pid: u32, started_by: &str) -> Result<(), String> {
+fn write_pid_file(workspace_id: &str, pid: u32, started_by: &str, control_token: Option<&str>) -> Result<(), String> {
     let data = PidFileData {
         pid,
         started_by: started_by.to_string(),
         started_at: now_epoch_secs(),
+        control_token: control_token.map(str::to_string),
     };
     let json = serde_json::to_string_pretty(&data).map_err(|e| format!("serialize pid: {e}"))?;
     let path = service_pid_file(workspace_id);
@@ -895,10 +1813,32 @@ fn write_pid_file(workspace_id: &str, pid: u32, started_by: &str) -> Result<(),
     Ok(())
 }
 
+/// 传给后端的控制令牌环境变量名（与 openakita/api/server.py 保持一致）
+const CONTROL_TOKEN_ENV: &str = "OPENAKITA_CONTROL_TOKEN";
+
+/// 生成 32 位十六进制的随机控制令牌（16 字节，取自操作系统随机源）。
+fn generate_control_token() -> String {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes).expect("操作系统随机源不可用");
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// 按 PID 查找对应 PID 文件中记录的控制令牌
+fn control_token_for_pid(pid: u32) -> Option<String> {
+    list_service_pids()
+        .into_iter()
+        .filter(|ent| ent.pid == pid)
+        .find_map(|ent| read_pid_file(&ent.workspace_id).and_then(|d| d.control_token))
+}
+
 /// 读取 PID 文件，兼容旧版纯数字格式
 fn read_pid_file(workspace_id: &str) -> Option<PidFileData> {
     let path = service_pid_file(workspace_id);
-    let content = fs::read_to_string(&path).ok()?;
+    parse_pid_file(&fs::read_to_string(&path).ok()?)
+}
+
+/// 解析 PID 文件内容：JSON 格式，或旧版的纯数字格式
+fn parse_pid_file(content: &str) -> Option<PidFileData> {
     let trimmed = content.trim();
     // 尝试 JSON 格式
     if let Ok(data) = serde_json::from_str::<PidFileData>(trimmed) {
@@ -911,8 +1851,9 @@ fn read_pid_file(workspace_id: &str) -> Option<PidFileData> {
         if pid > 0 {
             return Some(PidFileData {
                 pid,
-                started_by: "tauri".to_string(),
+                started_by: STARTED_BY_TAURI.to_string(),
                 started_at: 0,
+                control_token: None,
             });
         }
     }
@@ -927,6 +1868,8 @@ struct ServicePidEntry {
     pid_file: String,
     #[serde(default)]
     started_by: String,
+    #[serde(default)]
+    started_at: u64,
 }
 
 fn list_service_pids() -> Vec<ServicePidEntry> {
@@ -953,6 +1896,7 @@ fn list_service_pids() -> Vec<ServicePidEntry> {
                 pid: data.pid,
                 pid_file: p.to_string_lossy().to_string(),
                 started_by: data.started_by,
+                started_at: data.started_at,
             });
         }
     }
@@ -978,13 +1922,106 @@ fn service_heartbeat_file(workspace_id: &str) -> PathBuf {
     workspace_dir(workspace_id).join("data").join("backend.heartbeat")
 }
 
-/// 读取心跳文件
-fn read_heartbeat_file(workspace_id: &str) -> Option<HeartbeatData> {
+/// 心跳缓存条目：文件 mtime + 解析结果（文件不存在时两者均为 None）
+#[derive(Clone)]
+struct CachedHeartbeat {
+    modified: Option<std::time::SystemTime>,
+    data: Option<HeartbeatData>,
+}
+
+/// 由心跳监视线程维护的最新心跳（按 workspace_id）。
+/// 监视线程运行期间，状态查询直接读内存，避免前端 1 Hz 轮询时反复读盘解析。
+static HEARTBEAT_CACHE: Lazy<Mutex<std::collections::HashMap<String, CachedHeartbeat>>> =
+    Lazy::new(|| Mutex::new(std::collections::HashMap::new()));
+
+/// 直接从磁盘读取并解析心跳文件
+fn read_heartbeat_file_direct(workspace_id: &str) -> Option<HeartbeatData> {
     let path = service_heartbeat_file(workspace_id);
     let content = fs::read_to_string(&path).ok()?;
     serde_json::from_str::<HeartbeatData>(content.trim()).ok()
 }
 
+/// 仅当心跳文件 mtime 变化时重新解析并更新缓存，返回最新心跳
+fn refresh_heartbeat_cache(workspace_id: &str) -> Option<HeartbeatData> {
+    let modified = fs::metadata(service_heartbeat_file(workspace_id))
+        .and_then(|m| m.modified())
+        .ok();
+    if let Some(cached) = HEARTBEAT_CACHE.lock().unwrap().get(workspace_id) {
+        if cached.modified.is_some() && cached.modified == modified {
+            return cached.data.clone();
+        }
+    }
+    let data = modified.and_then(|_| read_heartbeat_file_direct(workspace_id));
+    HEARTBEAT_CACHE.lock().unwrap().insert(
+        workspace_id.to_string(),
+        CachedHeartbeat { modified, data: data.clone() },
+    );
+    data
+}
+
+/// 读取心跳：监视线程运行时读缓存，否则直接读文件
+fn read_heartbeat_file(workspace_id: &str) -> Option<HeartbeatData> {
+    if HEARTBEAT_WATCHERS.lock().unwrap().contains_key(workspace_id) {
+        if let Some(cached) = HEARTBEAT_CACHE.lock().unwrap().get(workspace_id) {
+            return cached.data.clone();
+        }
+    }
+    read_heartbeat_file_direct(workspace_id)
+}
+
+/// 心跳文件被 Tauri 自己改写/删除后，丢弃缓存，下次读取回到磁盘
+fn invalidate_heartbeat_cache(workspace_id: &str) {
+    HEARTBEAT_CACHE.lock().unwrap().remove(workspace_id);
+}
+
+/// 心跳超过该秒数未更新报告为过期（stale）
+const DEFAULT_HEARTBEAT_STALE_SECS: u64 = 30;
+/// 心跳超过该秒数未更新视为进程卡死，启动 / 对账 / 存活检查时清理
+const DEFAULT_HEARTBEAT_KILL_SECS: u64 = 60;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct HeartbeatThresholds {
+    stale_secs: u64,
+    kill_secs: u64,
+}
+
+/// 配置的 kill 不大于 stale 时取 stale 的两倍，保证先报告过期、再清理。
+fn heartbeat_thresholds_from_state(state: &AppStateFile) -> HeartbeatThresholds {
+    let stale_secs = state
+        .heartbeat_stale_secs
+        .unwrap_or(DEFAULT_HEARTBEAT_STALE_SECS)
+        .max(1);
+    let kill_secs = state.heartbeat_kill_secs.unwrap_or(DEFAULT_HEARTBEAT_KILL_SECS);
+    let kill_secs = if kill_secs > stale_secs { kill_secs } else { stale_secs * 2 };
+    HeartbeatThresholds { stale_secs, kill_secs }
+}
+
+/// 每次状态轮询都会用到，缓存在内存中，由 set_heartbeat_thresholds / reload_cached_settings 刷新
+static HEARTBEAT_THRESHOLDS: Lazy<Mutex<HeartbeatThresholds>> =
+    Lazy::new(|| Mutex::new(heartbeat_thresholds_from_state(&read_state_file())));
+
+/// 当前生效的心跳阈值
+fn heartbeat_thresholds() -> HeartbeatThresholds {
+    HEARTBEAT_THRESHOLDS.lock().unwrap().clone()
+}
+
+/// 自动拉起前判断后端是否已在运行：/api/health 成功，或心跳新鲜（未过期）且写心跳的进程仍存活、
+/// 并处于 starting / initializing / running（或已 http_ready）。
+/// 慢机器上 initializing 阶段 /api/health 可能短暂返回 500，只看 HTTP 会重复拉起第二个后端。
+fn backend_already_running(workspace_id: &str, port: u16) -> bool {
+    let token = read_pid_file(workspace_id).and_then(|d| d.control_token);
+    if probe_http_health_with_token(port, 0, 0, token.as_deref()).ok {
+        return true;
+    }
+    let Some(hb) = read_heartbeat_file(workspace_id) else {
+        return false;
+    };
+    let fresh = now_epoch_secs() as f64 - hb.timestamp <= heartbeat_thresholds().stale_secs as f64;
+    let live_phase = hb.http_ready || matches!(hb.phase.as_str(), "starting" | "initializing" | "running");
+    fresh && live_phase && is_pid_running(hb.pid)
+}
+
 /// 心跳是否过期。max_age_secs 为最大容忍的无心跳时间（秒）。
 /// 返回 None 表示没有心跳文件（旧版后端或尚未启动），
 /// 返回 Some(true) 表示心跳过期，Some(false) 表示心跳新鲜。
@@ -995,9 +2032,29 @@ fn is_heartbeat_stale(workspace_id: &str, max_age_secs: u64) -> Option<bool> {
     Some(age > max_age_secs as f64)
 }
 
+/// 由 Tauri 写入占位心跳（pid=0），用于在后端尚未写心跳的间隙表达预期阶段（如 "restarting"）。
+/// 新后端启动后会覆盖此文件。
+fn write_heartbeat_marker(workspace_id: &str, phase: &str) {
+    let path = service_heartbeat_file(workspace_id);
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    let hb = HeartbeatData {
+        pid: 0,
+        timestamp: now_epoch_secs() as f64,
+        phase: phase.to_string(),
+        http_ready: false,
+    };
+    if let Ok(json) = serde_json::to_string(&hb) {
+        let _ = fs::write(&path, json);
+    }
+    invalidate_heartbeat_cache(workspace_id);
+}
+
 /// 删除心跳文件（进程清理时调用）
 fn remove_heartbeat_file(workspace_id: &str) {
     let _ = fs::remove_file(service_heartbeat_file(workspace_id));
+    invalidate_heartbeat_cache(workspace_id);
 }
 
 /// 检测指定端口是否可用（未被占用）。
@@ -1006,56 +2063,170 @@ fn check_port_available(port: u16) -> bool {
     std::net::TcpListener::bind(("127.0.0.1", port)).is_ok()
 }
 
+/// 在 [start, start + count) 范围内查找第一个可用端口
+fn find_free_port_in_range(start: u16, count: u16) -> Option<u16> {
+    (0..count)
+        .filter_map(|i| start.checked_add(i))
+        .find(|p| *p != 0 && check_port_available(*p))
+}
+
+/// 前端调用：检查端口是否空闲（用于工作区设置页提前提示端口冲突）
+#[tauri::command]
+fn check_port_available_cmd(port: u16) -> bool {
+    check_port_available(port)
+}
+
+/// 前端调用：从 start 开始扫描 count 个端口，返回第一个空闲端口，用于建议备用 API_PORT
+#[tauri::command]
+fn find_free_port(start: u16, count: u16) -> Option<u16> {
+    find_free_port_in_range(start, count)
+}
+
 /// 等待端口释放，最多等 timeout_ms 毫秒。
 /// 返回 true 表示端口已释放。
-fn wait_for_port_free(port: u16, timeout_ms: u64) -> bool {
+/// `progress`: 可选，等待期间每秒发送一次 `port-wait-progress` 事件（port / waitedSecs / timeoutSecs）
+fn wait_for_port_free(port: u16, timeout_ms: u64, progress: Option<&StopProgress>) -> bool {
     let start = std::time::Instant::now();
     let timeout = std::time::Duration::from_millis(timeout_ms);
+    let mut last_reported: Option<u64> = None;
     while start.elapsed() < timeout {
         if check_port_available(port) {
             return true;
         }
+        if let Some(p) = progress {
+            let waited = start.elapsed().as_secs();
+            if last_reported != Some(waited) {
+                p.emit_port_wait(port, waited, timeout_ms / 1000);
+                last_reported = Some(waited);
+            }
+        }
         std::thread::sleep(std::time::Duration::from_millis(500));
     }
     false
 }
 
+/// 后端优雅关闭接口路径
+const BACKEND_SHUTDOWN_PATH: &str = "/api/shutdown";
+/// 调用关闭接口本身的 HTTP 超时（秒）
+const GRACEFUL_STOP_HTTP_TIMEOUT_SECS: u64 = 3;
+/// 关闭接口调用成功后等待进程自行退出的默认秒数
+const DEFAULT_GRACEFUL_STOP_TIMEOUT_SECS: u64 = 5;
+/// 优雅退出等待时间上限（秒），避免配置过大导致退出/切换工作区长时间卡住
+const MAX_GRACEFUL_STOP_TIMEOUT_SECS: u64 = 60;
+
+fn graceful_stop_timeout_from_state(state: &AppStateFile) -> u64 {
+    state
+        .graceful_stop_timeout_secs
+        .unwrap_or(DEFAULT_GRACEFUL_STOP_TIMEOUT_SECS)
+        .clamp(1, MAX_GRACEFUL_STOP_TIMEOUT_SECS)
+}
+
+/// 缓存在内存中，由 set_graceful_stop_timeout_secs / reload_cached_settings 刷新
+static GRACEFUL_STOP_TIMEOUT_SECS: Lazy<Mutex<u64>> =
+    Lazy::new(|| Mutex::new(graceful_stop_timeout_from_state(&read_state_file())));
+
+/// 当前配置的优雅退出等待秒数（限制在 1..=60）
+fn graceful_stop_timeout_secs() -> u64 {
+    *GRACEFUL_STOP_TIMEOUT_SECS.lock().unwrap()
+}
+
+/// 重新从 state.json 加载缓存的设置（心跳阈值、默认端口、优雅退出等待时间），
+/// 用于恢复备份等不经过对应 set_* 命令直接改写 state.json 的场景
+fn reload_cached_settings() {
+    let state = read_state_file();
+    *HEARTBEAT_THRESHOLDS.lock().unwrap() = heartbeat_thresholds_from_state(&state);
+    *DEFAULT_API_PORT_SETTING.lock().unwrap() = default_api_port_from_state(&state);
+    *GRACEFUL_STOP_TIMEOUT_SECS.lock().unwrap() = graceful_stop_timeout_from_state(&state);
+}
+
+/// 停止过程的进度上报目标：有 AppHandle 时通过 `service-stopping` 事件向前端报告倒计时
+struct StopProgress<'a> {
+    app: &'a tauri::AppHandle,
+    workspace_id: &'a str,
+}
+
+impl StopProgress<'_> {
+    fn emit(&self, pid: u32, elapsed_secs: u64, remaining_secs: u64) {
+        let _ = self.app.emit("service-stopping", serde_json::json!({
+            "workspaceId": self.workspace_id,
+            "pid": pid,
+            "elapsedSecs": elapsed_secs,
+            "remainingSecs": remaining_secs,
+        }));
+    }
+
+    fn emit_port_wait(&self, port: u16, waited_secs: u64, timeout_secs: u64) {
+        let _ = self.app.emit("port-wait-progress", serde_json::json!({
+            "workspaceId": self.workspace_id,
+            "port": port,
+            "waitedSecs": waited_secs,
+            "timeoutSecs": timeout_secs,
+        }));
+    }
+}
+
 /// 尝试通过 HTTP API 优雅关闭 Python 服务（POST /api/shutdown），
-/// 然后等待进程退出。如果 API 调用失败或超时则回退到 kill。
-/// `port`: 可选端口号，默认 18900
-fn graceful_stop_pid(pid: u32, port: Option<u16>) -> Result<(), String> {
+/// 然后最多等待 `timeout_secs` 秒让进程退出。如果 API 调用失败或超时则回退到 kill。
+/// `port`: 可选端口号，默认 default_api_port()
+/// `progress`: 可选，等待期间每秒发送一次 `service-stopping` 事件（elapsedSecs / remainingSecs）
+/// 返回强制阶段结束的全部 PID（含子进程树）；优雅退出时为空。
+fn graceful_stop_pid(
+    pid: u32,
+    port: Option<u16>,
+    timeout_secs: u64,
+    progress: Option<&StopProgress>,
+) -> Result<Vec<u32>, String> {
     if !is_pid_running(pid) {
-        return Ok(());
+        return Ok(Vec::new());
     }
 
-    let effective_port = port.unwrap_or(18900);
-    // 第一步：尝试通过 HTTP API 触发优雅关闭
+    let effective_port = port.unwrap_or_else(default_api_port);
+    // 第一步：尝试通过 HTTP API 触发优雅关闭（PID 文件里有控制令牌时携带；旧版后端没有令牌，直接调用）
+    let token = control_token_for_pid(pid);
     let api_ok = reqwest::blocking::Client::builder()
-        .timeout(std::time::Duration::from_secs(3))
+        .timeout(std::time::Duration::from_secs(GRACEFUL_STOP_HTTP_TIMEOUT_SECS))
         .build()
         .ok()
         .and_then(|client| {
-            client
-                .post(format!("http://127.0.0.1:{}/api/shutdown", effective_port))
-                .send()
-                .ok()
+            let mut req = client.post(format!("http://127.0.0.1:{}{}", effective_port, BACKEND_SHUTDOWN_PATH));
+            if let Some(t) = &token {
+                req = req.bearer_auth(t);
+            }
+            req.send().ok()
         })
         .map(|r| r.status().is_success())
         .unwrap_or(false);
 
     if api_ok {
-        // API 调用成功，给 Python 最多 5 秒优雅退出时间
-        for _ in 0..25 {
+        // API 调用成功，给 Python 最多 timeout_secs 秒优雅退出时间（用于把记忆等数据落盘）
+        let start = std::time::Instant::now();
+        let timeout = std::time::Duration::from_secs(timeout_secs);
+        let mut last_reported: Option<u64> = None;
+        while start.elapsed() < timeout {
             if !is_pid_running(pid) {
-                return Ok(());
+                return Ok(Vec::new());
+            }
+            let elapsed = start.elapsed().as_secs();
+            if let Some(p) = progress {
+                if last_reported != Some(elapsed) {
+                    p.emit(pid, elapsed, timeout_secs.saturating_sub(elapsed));
+                    last_reported = Some(elapsed);
+                }
             }
             std::thread::sleep(std::time::Duration::from_millis(200));
         }
+        if let Some(p) = progress {
+            p.emit(pid, timeout_secs, 0);
+        }
     }
 
-    // 第二步：进程仍然存活，强制 kill
+    // 第二步：进程仍然存活，强制结束整个进程树（Unix 上为 SIGTERM）
+    let mut killed = Vec::new();
     if is_pid_running(pid) {
-        kill_pid(pid)?;
+        killed = kill_process_tree(pid, false);
+        if !killed.contains(&pid) {
+            kill_pid(pid)?;
+        }
         // 等待最多 2s 确认退出
         for _ in 0..10 {
             if !is_pid_running(pid) {
@@ -1065,20 +2236,46 @@ fn graceful_stop_pid(pid: u32, port: Option<u16>) -> Result<(), String> {
         }
     }
 
+    // 第三步（Unix）：忽略 SIGTERM 的进程升级为 SIGKILL，再等最多 2s
+    #[cfg(unix)]
+    if is_pid_running(pid) {
+        for p in kill_process_tree(pid, true) {
+            if !killed.contains(&p) {
+                killed.push(p);
+            }
+        }
+        for _ in 0..10 {
+            if !is_pid_running(pid) {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(200));
+        }
+    }
+
+    if !killed.is_empty() {
+        eprintln!("Force-stopped backend process tree of pid {pid}: {killed:?}");
+    }
     if is_pid_running(pid) {
         Err(format!("pid {} still running after graceful + forced stop", pid))
     } else {
-        Ok(())
+        Ok(killed)
     }
 }
 
-fn stop_service_pid_entry(ent: &ServicePidEntry, port: Option<u16>) -> Result<(), String> {
+/// 停止 PID 文件登记的后端并删除其 PID / 心跳文件；返回强制阶段结束的 PID（见 graceful_stop_pid）
+fn stop_service_pid_entry(
+    ent: &ServicePidEntry,
+    port: Option<u16>,
+    timeout_secs: u64,
+    progress: Option<&StopProgress>,
+) -> Result<Vec<u32>, String> {
+    let mut killed = Vec::new();
     if is_pid_running(ent.pid) {
-        graceful_stop_pid(ent.pid, port)?;
+        killed = graceful_stop_pid(ent.pid, port, timeout_secs, progress)?;
     }
     let _ = fs::remove_file(PathBuf::from(&ent.pid_file));
     remove_heartbeat_file(&ent.workspace_id);
-    Ok(())
+    Ok(killed)
 }
 
 /// 启动锁文件路径
@@ -1086,6 +2283,15 @@ fn service_lock_file(workspace_id: &str) -> PathBuf {
     run_dir().join(format!("openakita-{}.lock", workspace_id))
 }
 
+/// 启动锁守卫：离开作用域时释放启动锁
+struct StartLockGuard(String);
+
+impl Drop for StartLockGuard {
+    fn drop(&mut self) {
+        release_start_lock(&self.0);
+    }
+}
+
 /// 尝试获取启动锁（原子创建文件），成功返回 true
 fn try_acquire_start_lock(workspace_id: &str) -> bool {
     let lock_path = service_lock_file(workspace_id);
@@ -1142,7 +2348,7 @@ fn get_process_create_time(pid: u32) -> Option<u64> {
     }
 }
 
-#[cfg(not(windows))]
+#[cfg(target_os = "linux")]
 fn get_process_create_time(pid: u32) -> Option<u64> {
     // On Unix, read /proc/{pid}/stat field 22 (starttime in clock ticks)
     // comm field (index 1) can contain spaces/parens, so we find the last ')' first
@@ -1154,7 +2360,7 @@ fn get_process_create_time(pid: u32) -> Option<u64> {
     // Fields after comm start at index 2; starttime is field 22 (index 20 after comm = 22-2)
     let fields: Vec<&str> = stat[after_comm..].split_whitespace().collect();
     let starttime = fields.get(19)?.parse::<u64>().ok()?; // field 22 → index 19 after comm
-    let clk_tck: u64 = 100; // typical default
+    let clk_tck = unix::clock_ticks_per_sec();
     // Read uptime to compute boot time
     let uptime_str = fs::read_to_string("/proc/uptime").ok()?;
     let uptime_secs: f64 = uptime_str.split_whitespace().next()?.parse().ok()?;
@@ -1163,6 +2369,12 @@ fn get_process_create_time(pid: u32) -> Option<u64> {
     Some(boot_time + starttime / clk_tck)
 }
 
+/// macOS 等没有 /proc 的平台读不到进程创建时间：PID 文件校验回退为进程身份检查，运行时长显示为未知
+#[cfg(not(any(windows, target_os = "linux")))]
+fn get_process_create_time(_pid: u32) -> Option<u64> {
+    None
+}
+
 /// 验证 PID 文件中的 started_at 是否与实际进程创建时间匹配（允许 5 秒误差）
 fn is_pid_file_valid(data: &PidFileData) -> bool {
     if !is_pid_running(data.pid) {
@@ -1193,14 +2405,12 @@ fn is_pid_file_valid(data: &PidFileData) -> bool {
 /// 从 workspace .env 文件读取 API_PORT
 fn read_workspace_api_port(workspace_id: &str) -> Option<u16> {
     let env_path = workspace_dir(workspace_id).join(".env");
-    let content = fs::read_to_string(&env_path).ok()?;
-    for line in content.lines() {
-        let t = line.trim();
-        if let Some(val) = t.strip_prefix("API_PORT=") {
-            return val.trim().parse::<u16>().ok();
-        }
-    }
-    None
+    // 与启动时注入环境变量的语义一致：重复键以最后一次出现为准
+    read_env_kv(&env_path)
+        .into_iter()
+        .rev()
+        .find(|(k, _)| k == "API_PORT")
+        .and_then(|(_, v)| v.trim().parse::<u16>().ok())
 }
 
 // --- Windows 原生 API FFI（进程检测/杀死/枚举，不依赖 cmd/tasklist/taskkill，中文 Windows 零编码问题）---
@@ -1224,12 +2434,143 @@ mod win {
             hSnapshot: *mut std::ffi::c_void,
             lppe: *mut PROCESSENTRY32W,
         ) -> i32;
+        pub fn GetProcessTimes(
+            hProcess: *mut std::ffi::c_void,
+            lpCreationTime: *mut FILETIME,
+            lpExitTime: *mut FILETIME,
+            lpKernelTime: *mut FILETIME,
+            lpUserTime: *mut FILETIME,
+        ) -> i32;
+        // psapi 的 GetProcessMemoryInfo 在 Win7+ 的 kernel32 中以 K32 前缀导出，无需额外链接 psapi.lib
+        pub fn K32GetProcessMemoryInfo(
+            hProcess: *mut std::ffi::c_void,
+            ppsmemCounters: *mut PROCESS_MEMORY_COUNTERS,
+            cb: u32,
+        ) -> i32;
+        pub fn CreateJobObjectW(
+            lpJobAttributes: *mut std::ffi::c_void,
+            lpName: *const u16,
+        ) -> *mut std::ffi::c_void;
+        pub fn SetInformationJobObject(
+            hJob: *mut std::ffi::c_void,
+            JobObjectInformationClass: i32,
+            lpJobObjectInformation: *mut std::ffi::c_void,
+            cbJobObjectInformationLength: u32,
+        ) -> i32;
+        pub fn AssignProcessToJobObject(
+            hJob: *mut std::ffi::c_void,
+            hProcess: *mut std::ffi::c_void,
+        ) -> i32;
+        pub fn AttachConsole(dwProcessId: u32) -> i32;
+        pub fn ReadProcessMemory(
+            hProcess: *mut std::ffi::c_void,
+            lpBaseAddress: *const std::ffi::c_void,
+            lpBuffer: *mut std::ffi::c_void,
+            nSize: usize,
+            lpNumberOfBytesRead: *mut usize,
+        ) -> i32;
+    }
+    #[link(name = "ntdll")]
+    extern "system" {
+        pub fn NtQueryInformationProcess(
+            ProcessHandle: *mut std::ffi::c_void,
+            ProcessInformationClass: u32,
+            ProcessInformation: *mut std::ffi::c_void,
+            ProcessInformationLength: u32,
+            ReturnLength: *mut u32,
+        ) -> i32;
+    }
+    pub const PROCESS_BASIC_INFORMATION_CLASS: u32 = 0;
+    pub const PROCESS_VM_READ: u32 = 0x0010;
+    // PEB.ProcessParameters 与 RTL_USER_PROCESS_PARAMETERS.CommandLine 的偏移（未公开结构，按位宽固定）
+    #[cfg(target_pointer_width = "64")]
+    pub const PEB_PROCESS_PARAMETERS_OFFSET: usize = 0x20;
+    #[cfg(target_pointer_width = "64")]
+    pub const PARAMETERS_COMMAND_LINE_OFFSET: usize = 0x70;
+    #[cfg(target_pointer_width = "32")]
+    pub const PEB_PROCESS_PARAMETERS_OFFSET: usize = 0x10;
+    #[cfg(target_pointer_width = "32")]
+    pub const PARAMETERS_COMMAND_LINE_OFFSET: usize = 0x40;
+
+    #[repr(C)]
+    pub struct PROCESS_BASIC_INFORMATION {
+        pub exit_status: i32,
+        pub peb_base_address: *mut std::ffi::c_void,
+        pub affinity_mask: usize,
+        pub base_priority: i32,
+        pub unique_process_id: usize,
+        pub inherited_from_unique_process_id: usize,
+    }
+
+    #[repr(C)]
+    #[derive(Copy, Clone)]
+    pub struct UNICODE_STRING {
+        pub length: u16,
+        pub maximum_length: u16,
+        pub buffer: *mut u16,
+    }
+    pub const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+    pub const JOB_OBJECT_EXTENDED_LIMIT_INFORMATION_CLASS: i32 = 9;
+    pub const JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE: u32 = 0x2000;
+
+    #[repr(C)]
+    pub struct JOBOBJECT_BASIC_LIMIT_INFORMATION {
+        pub per_process_user_time_limit: i64,
+        pub per_job_user_time_limit: i64,
+        pub limit_flags: u32,
+        pub minimum_working_set_size: usize,
+        pub maximum_working_set_size: usize,
+        pub active_process_limit: u32,
+        pub affinity: usize,
+        pub priority_class: u32,
+        pub scheduling_class: u32,
+    }
+
+    #[repr(C)]
+    pub struct IO_COUNTERS {
+        pub read_operation_count: u64,
+        pub write_operation_count: u64,
+        pub other_operation_count: u64,
+        pub read_transfer_count: u64,
+        pub write_transfer_count: u64,
+        pub other_transfer_count: u64,
+    }
+
+    #[repr(C)]
+    pub struct JOBOBJECT_EXTENDED_LIMIT_INFORMATION {
+        pub basic_limit_information: JOBOBJECT_BASIC_LIMIT_INFORMATION,
+        pub io_info: IO_COUNTERS,
+        pub process_memory_limit: usize,
+        pub job_memory_limit: usize,
+        pub peak_process_memory_used: usize,
+        pub peak_job_memory_used: usize,
     }
     pub const PROCESS_QUERY_LIMITED_INFORMATION: u32 = 0x1000;
     pub const PROCESS_TERMINATE: u32 = 0x0001;
     pub const TH32CS_SNAPPROCESS: u32 = 0x00000002;
     pub const INVALID_HANDLE_VALUE: *mut std::ffi::c_void = -1_isize as *mut std::ffi::c_void;
 
+    #[repr(C)]
+    #[derive(Copy, Clone)]
+    pub struct FILETIME {
+        pub dw_low_date_time: u32,
+        pub dw_high_date_time: u32,
+    }
+
+    #[repr(C)]
+    pub struct PROCESS_MEMORY_COUNTERS {
+        pub cb: u32,
+        pub page_fault_count: u32,
+        pub peak_working_set_size: usize,
+        pub working_set_size: usize,
+        pub quota_peak_paged_pool_usage: usize,
+        pub quota_paged_pool_usage: usize,
+        pub quota_peak_non_paged_pool_usage: usize,
+        pub quota_non_paged_pool_usage: usize,
+        pub pagefile_usage: usize,
+        pub peak_pagefile_usage: usize,
+    }
+
     #[repr(C)]
     pub struct PROCESSENTRY32W {
         pub dw_size: u32,
@@ -1245,33 +2586,146 @@ mod win {
     }
 }
 
-fn is_pid_running(pid: u32) -> bool {
-    if pid == 0 {
-        return false;
+// --- Unix 信号 FFI（直接调用 kill(2)，不依赖外部 kill 命令，并能区分 ESRCH / EPERM）---
+#[cfg(unix)]
+mod unix {
+    extern "C" {
+        pub fn kill(pid: i32, sig: i32) -> i32;
+        pub fn killpg(pgrp: i32, sig: i32) -> i32;
+        pub fn getpgid(pid: i32) -> i32;
     }
-    #[cfg(windows)]
-    {
-        // 直接用 Windows API 检查——最可靠，无 GBK 编码问题。
-        let handle =
-            unsafe { win::OpenProcess(win::PROCESS_QUERY_LIMITED_INFORMATION, 0, pid) };
-        if handle.is_null() {
-            return false;
-        }
-        unsafe {
-            win::CloseHandle(handle);
+    pub const SIGTERM: i32 = 15;
+    pub const SIGKILL: i32 = 9;
+    pub const ESRCH: i32 = 3;
+    pub const EPERM: i32 = 1;
+
+    /// 发送信号；失败时返回 errno
+    pub fn send_signal(pid: u32, sig: i32) -> Result<(), i32> {
+        if unsafe { kill(pid as i32, sig) } == 0 {
+            Ok(())
+        } else {
+            Err(std::io::Error::last_os_error().raw_os_error().unwrap_or(0))
         }
-        return true;
-    }
-    #[cfg(not(windows))]
-    {
-        let status = Command::new("kill")
-            .args(["-0", &pid.to_string()])
-            .status();
-        status.map(|s| s.success()).unwrap_or(false)
     }
-}
 
-fn kill_pid(pid: u32) -> Result<(), String> {
+    #[cfg(target_os = "linux")]
+    extern "C" {
+        fn sysconf(name: i32) -> std::ffi::c_long;
+    }
+    #[cfg(target_os = "linux")]
+    const SC_CLK_TCK: i32 = 2;
+    #[cfg(target_os = "linux")]
+    const SC_PAGESIZE: i32 = 30;
+
+    /// 每秒时钟滴答数（/proc 中 CPU 时间、starttime 的单位）；读取失败时退回常见值 100
+    #[cfg(target_os = "linux")]
+    pub fn clock_ticks_per_sec() -> u64 {
+        match unsafe { sysconf(SC_CLK_TCK) } {
+            n if n > 0 => n as u64,
+            _ => 100,
+        }
+    }
+
+    /// 内存页大小（/proc/{pid}/statm 的单位，arm64 上可能为 16K / 64K）；读取失败时退回 4096
+    #[cfg(target_os = "linux")]
+    pub fn page_size() -> u64 {
+        match unsafe { sysconf(SC_PAGESIZE) } {
+            n if n > 0 => n as u64,
+            _ => 4096,
+        }
+    }
+}
+
+/// Unix：列出所有进程的 (pid, ppid, pgid)，Linux / macOS 的 ps 均支持该格式
+#[cfg(unix)]
+fn unix_process_table() -> Vec<(u32, u32, u32)> {
+    let Ok(out) = Command::new("ps").args(["-A", "-o", "pid=,ppid=,pgid="]).output() else {
+        return Vec::new();
+    };
+    String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .filter_map(|line| {
+            let mut cols = line.split_whitespace().map(|c| c.parse::<u32>().ok());
+            Some((cols.next()??, cols.next()??, cols.next()??))
+        })
+        .collect()
+}
+
+/// Unix：向进程发送信号。进程已不存在（ESRCH）视为成功，权限不足（EPERM）单独报错。
+#[cfg(unix)]
+fn signal_pid(pid: u32, sig: i32) -> Result<(), String> {
+    match unix::send_signal(pid, sig) {
+        Ok(()) => Ok(()),
+        Err(unix::ESRCH) => Ok(()),
+        Err(unix::EPERM) => Err(format!("kill pid {} failed: permission denied (EPERM)", pid)),
+        Err(errno) => Err(format!("kill pid {} failed: errno {}", pid, errno)),
+    }
+}
+
+/// 后端共用的 Job Object 句柄（KILL_ON_JOB_CLOSE）。句柄在 Setup Center 生命周期内不关闭，
+/// 进程退出（包括被任务管理器结束）时由系统关闭句柄，Job 内的后端随之被终止。
+#[cfg(windows)]
+static BACKEND_JOB: Lazy<Mutex<usize>> = Lazy::new(|| Mutex::new(0));
+
+#[cfg(windows)]
+fn assign_to_backend_job(child: &std::process::Child) -> Result<(), String> {
+    use std::os::windows::io::AsRawHandle;
+    let mut job = BACKEND_JOB.lock().unwrap();
+    if *job == 0 {
+        unsafe {
+            let handle = win::CreateJobObjectW(std::ptr::null_mut(), std::ptr::null());
+            if handle.is_null() {
+                return Err(format!("CreateJobObjectW failed: {}", std::io::Error::last_os_error()));
+            }
+            let mut info: win::JOBOBJECT_EXTENDED_LIMIT_INFORMATION = std::mem::zeroed();
+            info.basic_limit_information.limit_flags = win::JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+            let ok = win::SetInformationJobObject(
+                handle,
+                win::JOB_OBJECT_EXTENDED_LIMIT_INFORMATION_CLASS,
+                &mut info as *mut _ as *mut std::ffi::c_void,
+                std::mem::size_of::<win::JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+            );
+            if ok == 0 {
+                let err = std::io::Error::last_os_error();
+                win::CloseHandle(handle);
+                return Err(format!("SetInformationJobObject failed: {err}"));
+            }
+            *job = handle as usize;
+        }
+    }
+    let ok = unsafe {
+        win::AssignProcessToJobObject(*job as *mut std::ffi::c_void, child.as_raw_handle() as *mut std::ffi::c_void)
+    };
+    if ok == 0 {
+        return Err(format!("AssignProcessToJobObject failed: {}", std::io::Error::last_os_error()));
+    }
+    Ok(())
+}
+
+fn is_pid_running(pid: u32) -> bool {
+    if pid == 0 {
+        return false;
+    }
+    #[cfg(windows)]
+    {
+        // 直接用 Windows API 检查——最可靠，无 GBK 编码问题。
+        let handle =
+            unsafe { win::OpenProcess(win::PROCESS_QUERY_LIMITED_INFORMATION, 0, pid) };
+        if handle.is_null() {
+            return false;
+        }
+        unsafe {
+            win::CloseHandle(handle);
+        }
+        return true;
+    }
+    #[cfg(not(windows))]
+    {
+        unix::send_signal(pid, 0).is_ok()
+    }
+}
+
+fn kill_pid(pid: u32) -> Result<(), String> {
     if pid == 0 {
         return Ok(());
     }
@@ -1302,14 +2756,501 @@ fn kill_pid(pid: u32) -> Result<(), String> {
     }
     #[cfg(not(windows))]
     {
-        let status = Command::new("kill")
-            .args(["-TERM", &pid.to_string()])
-            .status()
-            .map_err(|e| format!("kill failed: {e}"))?;
-        if !status.success() {
-            return Err(format!("kill failed: {status}"));
+        signal_pid(pid, unix::SIGTERM)
+    }
+}
+
+/// `root` 的所有后代进程（深度优先后序：子孙在前），`edges` 为 (pid, ppid) 列表。
+/// `is_real_child(child, parent)` 返回 false 的边被忽略（其子树也不再展开）。
+fn descendants_post_order(root: u32, edges: &[(u32, u32)], is_real_child: &dyn Fn(u32, u32) -> bool) -> Vec<u32> {
+    fn visit(
+        pid: u32,
+        edges: &[(u32, u32)],
+        is_real_child: &dyn Fn(u32, u32) -> bool,
+        seen: &mut Vec<u32>,
+        out: &mut Vec<u32>,
+    ) {
+        for &(child, parent) in edges {
+            // PID 复用可能构成环，seen 防止死循环
+            if parent == pid && child != pid && !seen.contains(&child) && is_real_child(child, parent) {
+                seen.push(child);
+                visit(child, edges, is_real_child, seen, out);
+                out.push(child);
+            }
         }
-        Ok(())
+    }
+    let mut seen = vec![root];
+    let mut out = Vec::new();
+    visit(root, edges, is_real_child, &mut seen, &mut out);
+    out
+}
+
+/// 子进程的创建时间早于“父进程”时，说明登记的父 PID 已被复用（真正的父进程早已退出），
+/// 两者并无父子关系。任一时间未知时按父子关系处理。
+#[cfg(any(windows, test))]
+fn started_before_parent(child_started: Option<u64>, parent_started: Option<u64>) -> bool {
+    matches!((child_started, parent_started), (Some(c), Some(p)) if c < p)
+}
+
+/// 结束整个后端进程树（playwright 的 node 驱动、Chromium、whisper 的 ffmpeg 等子进程会在父进程被杀后残留，
+/// 继续占用端口和文件）。返回已发出结束请求的全部 PID（含 `pid` 本身）。
+/// - Windows：按 Toolhelp32 的父 PID 链枚举后代，深度优先先杀子孙再杀根进程。
+///   Windows 会复用 PID 且不会改写孤儿进程的父 PID，创建时间早于父进程的“子进程”视为无关进程跳过
+/// - Unix：后端以独立进程组启动，`pid` 为组长时 killpg 整组；否则（旧版本启动的后端）按 ppid 链逐个发信号。
+///   `force` 为 true 时发送 SIGKILL，否则 SIGTERM；Windows 始终 TerminateProcess
+fn kill_process_tree(pid: u32, force: bool) -> Vec<u32> {
+    let mut killed = Vec::new();
+    if pid == 0 {
+        return killed;
+    }
+    #[cfg(windows)]
+    {
+        let _ = force;
+        let mut edges: Vec<(u32, u32)> = Vec::new();
+        let snap = unsafe { win::CreateToolhelp32Snapshot(win::TH32CS_SNAPPROCESS, 0) };
+        if snap != win::INVALID_HANDLE_VALUE && !snap.is_null() {
+            let mut pe: win::PROCESSENTRY32W = unsafe { std::mem::zeroed() };
+            pe.dw_size = std::mem::size_of::<win::PROCESSENTRY32W>() as u32;
+            if unsafe { win::Process32FirstW(snap, &mut pe) } != 0 {
+                loop {
+                    edges.push((pe.th32_process_id, pe.th32_parent_process_id));
+                    if unsafe { win::Process32NextW(snap, &mut pe) } == 0 {
+                        break;
+                    }
+                }
+            }
+            unsafe {
+                win::CloseHandle(snap);
+            }
+        }
+        let is_real_child = |child: u32, parent: u32| {
+            !started_before_parent(get_process_create_time(child), get_process_create_time(parent))
+        };
+        for child in descendants_post_order(pid, &edges, &is_real_child) {
+            if kill_pid(child).is_ok() {
+                killed.push(child);
+            }
+        }
+        if kill_pid(pid).is_ok() {
+            killed.push(pid);
+        }
+    }
+    #[cfg(unix)]
+    {
+        let sig = if force { unix::SIGKILL } else { unix::SIGTERM };
+        let table = unix_process_table();
+        let is_group_leader = unsafe { unix::getpgid(pid as i32) } == pid as i32;
+        let edges: Vec<(u32, u32)> = table.iter().map(|&(p, pp, _)| (p, pp)).collect();
+        // Unix 上父进程退出后子进程会被 init 收养，ppid 不会指向已复用的 PID，无需校验
+        let mut members = descendants_post_order(pid, &edges, &|_, _| true);
+        if is_group_leader {
+            for &(p, _, pgid) in &table {
+                if pgid == pid && p != pid && !members.contains(&p) {
+                    members.push(p);
+                }
+            }
+            if unsafe { unix::killpg(pid as i32, sig) } == 0 {
+                killed.extend(members.iter().copied());
+                killed.push(pid);
+            }
+        }
+        // 进程组之外的后代（自行 setsid 的子进程）或非组长的旧后端：逐个发信号
+        for child in members {
+            if !killed.contains(&child) && signal_pid(child, sig).is_ok() {
+                killed.push(child);
+            }
+        }
+        if !killed.contains(&pid) && signal_pid(pid, sig).is_ok() {
+            killed.push(pid);
+        }
+    }
+    killed
+}
+
+/// 查找监听指定 TCP 端口的进程 PID。查不到返回 None。
+/// Windows 解析 `netstat -ano`；Linux 通过 /proc/net/tcp(6) 找到监听 socket 的 inode，
+/// 再在 /proc/*/fd 中匹配持有该 socket 的进程；其他平台（及 Linux 兜底）使用 lsof。
+fn find_port_owner_pid(port: u16) -> Option<u32> {
+    #[cfg(windows)]
+    {
+        let mut c = Command::new("netstat");
+        c.args(["-ano", "-p", "TCP"]);
+        apply_no_window(&mut c);
+        let out = c.output().ok()?;
+        let text = String::from_utf8_lossy(&out.stdout);
+        let suffix = format!(":{port}");
+        text.lines().find_map(|line| {
+            let cols: Vec<&str> = line.split_whitespace().collect();
+            // Proto  Local Address  Foreign Address  State  PID
+            if cols.len() >= 5 && cols[1].ends_with(&suffix) && cols[3].eq_ignore_ascii_case("LISTENING") {
+                cols[4].parse::<u32>().ok()
+            } else {
+                None
+            }
+        })
+    }
+    #[cfg(not(windows))]
+    {
+        #[cfg(target_os = "linux")]
+        if let Some(pid) = find_port_owner_pid_procfs(port) {
+            return Some(pid);
+        }
+        let out = Command::new("lsof")
+            .args(["-nP", &format!("-iTCP:{port}"), "-sTCP:LISTEN", "-t"])
+            .output()
+            .ok()?;
+        String::from_utf8_lossy(&out.stdout)
+            .lines()
+            .find_map(|l| l.trim().parse::<u32>().ok())
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn find_port_owner_pid_procfs(port: u16) -> Option<u32> {
+    // /proc/net/tcp 每行：sl local_address rem_address st ... uid timeout inode
+    // local_address 形如 0100007F:49D4（端口为十六进制），st == 0A 表示 LISTEN
+    let mut inodes: Vec<String> = vec![];
+    for table in ["/proc/net/tcp", "/proc/net/tcp6"] {
+        let Ok(content) = fs::read_to_string(table) else { continue };
+        for line in content.lines().skip(1) {
+            let cols: Vec<&str> = line.split_whitespace().collect();
+            if cols.len() < 10 || cols[3] != "0A" {
+                continue;
+            }
+            let local_port = cols[1]
+                .rsplit(':')
+                .next()
+                .and_then(|h| u16::from_str_radix(h, 16).ok());
+            if local_port == Some(port) && cols[9] != "0" {
+                inodes.push(format!("socket:[{}]", cols[9]));
+            }
+        }
+    }
+    if inodes.is_empty() {
+        return None;
+    }
+    for proc_entry in fs::read_dir("/proc").ok()?.flatten() {
+        let Some(pid) = proc_entry.file_name().to_str().and_then(|n| n.parse::<u32>().ok()) else {
+            continue;
+        };
+        let Ok(fds) = fs::read_dir(proc_entry.path().join("fd")) else { continue };
+        for fd in fds.flatten() {
+            if let Ok(target) = fs::read_link(fd.path()) {
+                if inodes.iter().any(|i| target.as_os_str() == i.as_str()) {
+                    return Some(pid);
+                }
+            }
+        }
+    }
+    None
+}
+
+/// 进程可执行文件名（如 python.exe / openakita-server）
+fn process_exe_name(pid: u32) -> Option<String> {
+    #[cfg(windows)]
+    {
+        let snap = unsafe { win::CreateToolhelp32Snapshot(win::TH32CS_SNAPPROCESS, 0) };
+        if snap == win::INVALID_HANDLE_VALUE || snap.is_null() {
+            return None;
+        }
+        let mut pe: win::PROCESSENTRY32W = unsafe { std::mem::zeroed() };
+        pe.dw_size = std::mem::size_of::<win::PROCESSENTRY32W>() as u32;
+        let mut name = None;
+        if unsafe { win::Process32FirstW(snap, &mut pe) } != 0 {
+            loop {
+                if pe.th32_process_id == pid {
+                    let len = pe.sz_exe_file.iter().position(|&c| c == 0).unwrap_or(pe.sz_exe_file.len());
+                    name = Some(String::from_utf16_lossy(&pe.sz_exe_file[..len]));
+                    break;
+                }
+                if unsafe { win::Process32NextW(snap, &mut pe) } == 0 {
+                    break;
+                }
+            }
+        }
+        unsafe {
+            win::CloseHandle(snap);
+        }
+        name
+    }
+    #[cfg(not(windows))]
+    {
+        if let Ok(comm) = fs::read_to_string(format!("/proc/{}/comm", pid)) {
+            return Some(comm.trim().to_string());
+        }
+        let out = Command::new("ps")
+            .args(["-p", &pid.to_string(), "-o", "comm="])
+            .output()
+            .ok()?;
+        let name = String::from_utf8_lossy(&out.stdout).trim().to_string();
+        (!name.is_empty()).then_some(name)
+    }
+}
+
+/// 从目标进程内存读取 `len` 字节到 `dst`，必须完整读取才算成功
+#[cfg(windows)]
+fn read_remote_memory(handle: *mut std::ffi::c_void, addr: usize, dst: *mut std::ffi::c_void, len: usize) -> bool {
+    let mut read = 0usize;
+    let ok = unsafe { win::ReadProcessMemory(handle, addr as *const std::ffi::c_void, dst, len, &mut read) };
+    ok != 0 && read == len
+}
+
+/// Windows 原生读取进程命令行：NtQueryInformationProcess 取 PEB 地址，
+/// 再经 ReadProcessMemory 读取 PEB → ProcessParameters → CommandLine，不启动 PowerShell。
+/// 受保护进程或权限不足时返回 None，由调用方回退到 CIM 查询。
+#[cfg(windows)]
+fn native_command_line(pid: u32) -> Option<String> {
+    use std::ffi::c_void;
+    let handle = unsafe {
+        win::OpenProcess(win::PROCESS_QUERY_LIMITED_INFORMATION | win::PROCESS_VM_READ, 0, pid)
+    };
+    if handle.is_null() {
+        return None;
+    }
+    let read = || -> Option<String> {
+        let mut pbi: win::PROCESS_BASIC_INFORMATION = unsafe { std::mem::zeroed() };
+        let status = unsafe {
+            win::NtQueryInformationProcess(
+                handle,
+                win::PROCESS_BASIC_INFORMATION_CLASS,
+                &mut pbi as *mut _ as *mut c_void,
+                std::mem::size_of::<win::PROCESS_BASIC_INFORMATION>() as u32,
+                std::ptr::null_mut(),
+            )
+        };
+        if status < 0 || pbi.peb_base_address.is_null() {
+            return None;
+        }
+        let mut params: usize = 0;
+        if !read_remote_memory(
+            handle,
+            pbi.peb_base_address as usize + win::PEB_PROCESS_PARAMETERS_OFFSET,
+            &mut params as *mut usize as *mut c_void,
+            std::mem::size_of::<usize>(),
+        ) || params == 0
+        {
+            return None;
+        }
+        let mut cmd: win::UNICODE_STRING = unsafe { std::mem::zeroed() };
+        if !read_remote_memory(
+            handle,
+            params + win::PARAMETERS_COMMAND_LINE_OFFSET,
+            &mut cmd as *mut _ as *mut c_void,
+            std::mem::size_of::<win::UNICODE_STRING>(),
+        ) || cmd.length == 0
+            || cmd.buffer.is_null()
+        {
+            return None;
+        }
+        let mut buf = vec![0u16; cmd.length as usize / 2];
+        if !read_remote_memory(handle, cmd.buffer as usize, buf.as_mut_ptr() as *mut c_void, buf.len() * 2) {
+            return None;
+        }
+        let line = String::from_utf16_lossy(&buf).trim().to_string();
+        (!line.is_empty()).then_some(line)
+    };
+    let result = read();
+    unsafe {
+        win::CloseHandle(handle);
+    }
+    result
+}
+
+/// 单个进程的 CIM 查询（每次启动一个 PowerShell，仅作原生读取失败时的回退）
+#[cfg(windows)]
+fn cim_command_line(pid: u32) -> Option<String> {
+    let mut c = Command::new("powershell");
+    c.args([
+        "-NoProfile",
+        "-NonInteractive",
+        "-Command",
+        &format!(
+            "(Get-CimInstance Win32_Process -Filter 'ProcessId={}').CommandLine",
+            pid
+        ),
+    ]);
+    apply_no_window(&mut c);
+    let out = c.output().ok()?;
+    let cmd = String::from_utf8_lossy(&out.stdout).trim().to_string();
+    (!cmd.is_empty()).then_some(cmd)
+}
+
+/// 进程完整命令行
+fn process_command_line(pid: u32) -> Option<String> {
+    #[cfg(windows)]
+    {
+        native_command_line(pid).or_else(|| cim_command_line(pid))
+    }
+    #[cfg(not(windows))]
+    {
+        if let Ok(raw) = fs::read(format!("/proc/{}/cmdline", pid)) {
+            let cmd = String::from_utf8_lossy(&raw).replace('\0', " ").trim().to_string();
+            if !cmd.is_empty() {
+                return Some(cmd);
+            }
+        }
+        let out = Command::new("ps")
+            .args(["-p", &pid.to_string(), "-o", "args="])
+            .output()
+            .ok()?;
+        let cmd = String::from_utf8_lossy(&out.stdout).trim().to_string();
+        (!cmd.is_empty()).then_some(cmd)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct PortOwner {
+    port: u16,
+    pid: u32,
+    exe_name: Option<String>,
+    command_line: Option<String>,
+    is_openakita: bool,
+}
+
+fn port_owner_info(port: u16) -> Option<PortOwner> {
+    let pid = find_port_owner_pid(port)?;
+    Some(PortOwner {
+        port,
+        pid,
+        exe_name: process_exe_name(pid),
+        command_line: process_command_line(pid),
+        is_openakita: is_openakita_process(pid),
+    })
+}
+
+/// 诊断面板：查看占用指定端口的进程。端口空闲或无法识别时返回 None。
+#[tauri::command]
+async fn find_port_owner(port: u16) -> Result<Option<PortOwner>, String> {
+    spawn_blocking_result(move || Ok(port_owner_info(port))).await
+}
+
+/// python 进程命令行缓存的有效期：同一轮扫描（如托盘退出时逐个校验 PID）复用一次查询
+#[cfg(windows)]
+const PYTHON_CMDLINE_CACHE_MS: u128 = 2000;
+
+#[cfg(windows)]
+static PYTHON_CMDLINE_CACHE: Lazy<Mutex<Option<(std::time::Instant, std::collections::HashMap<u32, String>)>>> =
+    Lazy::new(|| Mutex::new(None));
+
+/// 用一次 PowerShell Get-CimInstance 查询取回所有 python / openakita-server 进程的命令行（PID → CommandLine），
+/// 替代逐个 PID 启动 PowerShell（每次数百毫秒且会闪窗）。
+/// 使用 Get-CimInstance 而非已废弃的 wmic（Windows 11 已移除 wmic）。
+/// `force` 为 true 时忽略缓存重新查询。PowerShell 启动失败或输出无法解析时返回 None。
+#[cfg(windows)]
+fn python_command_lines(force: bool) -> Option<std::collections::HashMap<u32, String>> {
+    if !force {
+        let cache = PYTHON_CMDLINE_CACHE.lock().unwrap();
+        if let Some((at, map)) = cache.as_ref() {
+            if at.elapsed().as_millis() < PYTHON_CMDLINE_CACHE_MS {
+                return Some(map.clone());
+            }
+        }
+    }
+    let mut c = Command::new("powershell");
+    c.args([
+        "-NoProfile",
+        "-NonInteractive",
+        "-Command",
+        "[Console]::OutputEncoding = [Text.Encoding]::UTF8; \
+         Get-CimInstance Win32_Process -Filter \"Name LIKE '%python%' OR Name LIKE '%openakita-server%'\" | \
+         Select-Object ProcessId, CommandLine | ConvertTo-Json -Compress",
+    ]);
+    apply_no_window(&mut c);
+    let out = c.output().ok().filter(|o| o.status.success())?;
+    let map = parse_cim_command_lines(&String::from_utf8_lossy(&out.stdout))?;
+    *PYTHON_CMDLINE_CACHE.lock().unwrap() = Some((std::time::Instant::now(), map.clone()));
+    Some(map)
+}
+
+/// 解析 `Select-Object ProcessId, CommandLine | ConvertTo-Json` 的输出。
+/// 只有一个进程时 PowerShell 输出单个对象而不是数组；没有匹配进程时输出为空。
+#[cfg(windows)]
+fn parse_cim_command_lines(stdout: &str) -> Option<std::collections::HashMap<u32, String>> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct CimProcess {
+        process_id: u32,
+        command_line: Option<String>,
+    }
+    let trimmed = stdout.trim();
+    if trimmed.is_empty() {
+        return Some(std::collections::HashMap::new());
+    }
+    let items: Vec<CimProcess> = match serde_json::from_str::<serde_json::Value>(trimmed).ok()? {
+        v @ serde_json::Value::Array(_) => serde_json::from_value(v).ok()?,
+        v => vec![serde_json::from_value(v).ok()?],
+    };
+    Some(
+        items
+            .into_iter()
+            .filter_map(|p| Some((p.process_id, p.command_line?.trim().to_string())))
+            .filter(|(_, cmd)| !cmd.is_empty())
+            .collect(),
+    )
+}
+
+/// 一轮扫描所需的命令行：先逐个原生读取；读不到的（受保护进程等）用一次批量 CIM 查询补齐，
+/// 批量查询也失败时才逐个 PID 启动 PowerShell
+#[cfg(windows)]
+fn command_lines_for(pids: &[u32]) -> std::collections::HashMap<u32, String> {
+    resolve_command_lines(pids, native_command_line, || python_command_lines(true), cim_command_line)
+}
+
+/// command_lines_for 的查找顺序，各来源以参数注入：`native` 不启动子进程，
+/// `batch`（一次 PowerShell）只在有原生读不到的 PID 时调用一次，`per_pid`（每次一个 PowerShell）仅在批量查询失败时使用
+#[cfg(any(windows, test))]
+fn resolve_command_lines(
+    pids: &[u32],
+    native: impl Fn(u32) -> Option<String>,
+    batch: impl FnOnce() -> Option<std::collections::HashMap<u32, String>>,
+    per_pid: impl Fn(u32) -> Option<String>,
+) -> std::collections::HashMap<u32, String> {
+    let mut map = std::collections::HashMap::new();
+    let mut missing = Vec::new();
+    for &pid in pids {
+        match native(pid) {
+            Some(cmd) => {
+                map.insert(pid, cmd);
+            }
+            None => missing.push(pid),
+        }
+    }
+    if missing.is_empty() {
+        return map;
+    }
+    match batch() {
+        Some(cim) => {
+            for pid in missing {
+                if let Some(cmd) = cim.get(&pid) {
+                    map.insert(pid, cmd.clone());
+                }
+            }
+        }
+        None => {
+            eprintln!("Batched CIM command-line query failed, falling back to per-pid lookup");
+            for pid in missing {
+                if let Some(cmd) = per_pid(pid) {
+                    map.insert(pid, cmd);
+                }
+            }
+        }
+    }
+    map
+}
+
+/// 单个 python 进程的命令行：优先原生读取；否则查 CIM 缓存，未命中时（可能是缓存之后新启动的进程）强制刷新一次
+#[cfg(windows)]
+fn python_command_line(pid: u32) -> Option<String> {
+    if let Some(cmd) = native_command_line(pid) {
+        return Some(cmd);
+    }
+    if let Some(cmd) = python_command_lines(false).and_then(|m| m.get(&pid).cloned()) {
+        return Some(cmd);
+    }
+    match python_command_lines(true) {
+        Some(map) => map.get(&pid).cloned(),
+        None => cim_command_line(pid),
     }
 }
 
@@ -1360,23 +3301,10 @@ fn is_openakita_process(pid: u32) -> bool {
             return false; // 既不是 python 也不是 openakita-server，肯定不是后端
         }
 
-        // Step 2: python 进程需进一步检查命令行是否包含 openakita
-        let mut c = Command::new("powershell");
-        c.args([
-            "-NoProfile",
-            "-NonInteractive",
-            "-Command",
-            &format!(
-                "(Get-CimInstance Win32_Process -Filter 'ProcessId={}').CommandLine",
-                pid
-            ),
-        ]);
-        apply_no_window(&mut c);
-        if let Ok(out) = c.output() {
-            let s = String::from_utf8_lossy(&out.stdout).to_lowercase();
-            return s.contains("openakita");
-        }
-        false
+        // Step 2: python 进程需进一步检查命令行是否包含 openakita（批量查询结果带短时缓存）
+        python_command_line(pid)
+            .map(|s| s.to_lowercase().contains("openakita"))
+            .unwrap_or(false)
     }
     #[cfg(not(windows))]
     {
@@ -1396,17 +3324,171 @@ fn is_openakita_process(pid: u32) -> bool {
     }
 }
 
-/// 扫描并杀死所有进程名为 python/pythonw 且命令行包含 "openakita" 和 "serve" 的进程。
-/// 用于托盘退出时兜底清理孤儿进程（PID 文件可能已被删除但进程仍存活）。
-/// 返回被杀掉的 PID 列表。
-fn kill_openakita_orphans() -> Vec<u32> {
-    let mut killed = Vec::new();
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ResourceUsage {
+    pid: u32,
+    /// 占整机 CPU 的百分比（0-100，已按逻辑核数归一化）
+    cpu_percent: f64,
+    rss_bytes: u64,
+    num_threads: u32,
+}
+
+/// CPU 采样间隔：两次读取进程 CPU 时间之间的等待
+const RESOURCE_SAMPLE_MS: u64 = 200;
+
+/// 进程累计 CPU 时间（秒）、常驻内存（字节）、线程数
+#[cfg(windows)]
+fn sample_process_resources(pid: u32) -> Option<(f64, u64, u32)> {
+    fn filetime_secs(ft: &win::FILETIME) -> f64 {
+        let v = ((ft.dw_high_date_time as u64) << 32) | (ft.dw_low_date_time as u64);
+        v as f64 / 10_000_000.0
+    }
+
+    let (cpu_secs, rss) = unsafe {
+        let handle = win::OpenProcess(win::PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if handle.is_null() {
+            return None;
+        }
+        let mut creation: win::FILETIME = std::mem::zeroed();
+        let mut exit: win::FILETIME = std::mem::zeroed();
+        let mut kernel: win::FILETIME = std::mem::zeroed();
+        let mut user: win::FILETIME = std::mem::zeroed();
+        let times_ok =
+            win::GetProcessTimes(handle, &mut creation, &mut exit, &mut kernel, &mut user);
+        let mut pmc: win::PROCESS_MEMORY_COUNTERS = std::mem::zeroed();
+        pmc.cb = std::mem::size_of::<win::PROCESS_MEMORY_COUNTERS>() as u32;
+        let mem_ok = win::K32GetProcessMemoryInfo(handle, &mut pmc, pmc.cb);
+        win::CloseHandle(handle);
+        if times_ok == 0 || mem_ok == 0 {
+            return None;
+        }
+        (
+            filetime_secs(&kernel) + filetime_secs(&user),
+            pmc.working_set_size as u64,
+        )
+    };
+
+    // 线程数从 Toolhelp32 快照中取（cntThreads）
+    let mut threads = 0u32;
+    let snap = unsafe { win::CreateToolhelp32Snapshot(win::TH32CS_SNAPPROCESS, 0) };
+    if snap != win::INVALID_HANDLE_VALUE && !snap.is_null() {
+        let mut pe: win::PROCESSENTRY32W = unsafe { std::mem::zeroed() };
+        pe.dw_size = std::mem::size_of::<win::PROCESSENTRY32W>() as u32;
+        if unsafe { win::Process32FirstW(snap, &mut pe) } != 0 {
+            loop {
+                if pe.th32_process_id == pid {
+                    threads = pe.cnt_threads;
+                    break;
+                }
+                if unsafe { win::Process32NextW(snap, &mut pe) } == 0 {
+                    break;
+                }
+            }
+        }
+        unsafe {
+            win::CloseHandle(snap);
+        }
+    }
+    Some((cpu_secs, rss, threads))
+}
+
+#[cfg(target_os = "linux")]
+fn sample_process_resources(pid: u32) -> Option<(f64, u64, u32)> {
+    // /proc/{pid}/stat：comm 可能包含空格/括号，先定位最后一个 ')'
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    let after_comm = stat.rfind(')')? + 2;
+    if after_comm >= stat.len() {
+        return None;
+    }
+    let fields: Vec<&str> = stat[after_comm..].split_whitespace().collect();
+    // utime = field 14, stime = field 15, num_threads = field 20（after comm 的下标 = 字段号 - 3）
+    let utime = fields.get(11)?.parse::<u64>().ok()?;
+    let stime = fields.get(12)?.parse::<u64>().ok()?;
+    let threads = fields.get(17)?.parse::<u32>().ok()?;
+    let clk_tck = unix::clock_ticks_per_sec();
+
+    // /proc/{pid}/statm 第二列为常驻页数
+    let statm = fs::read_to_string(format!("/proc/{}/statm", pid)).ok()?;
+    let resident_pages = statm.split_whitespace().nth(1)?.parse::<u64>().ok()?;
+    let page_size = unix::page_size();
+
+    Some((
+        (utime + stime) as f64 / clk_tck as f64,
+        resident_pages * page_size,
+        threads,
+    ))
+}
+
+/// macOS 等平台暂不支持（没有 /proc，需要 libproc 的 proc_pidinfo）：始终返回 None，
+/// 状态面板的内存 / CPU 显示为未知，openakita_service_resources 报告“不支持”
+#[cfg(not(any(windows, target_os = "linux")))]
+fn sample_process_resources(_pid: u32) -> Option<(f64, u64, u32)> {
+    None
+}
+
+/// 当前平台能否读取进程资源占用（见 sample_process_resources）
+const PROCESS_RESOURCES_SUPPORTED: bool = cfg!(any(windows, target_os = "linux"));
+
+/// 后端进程的 CPU/内存占用，供状态面板排查失控的 agent。
+/// CPU 通过间隔 RESOURCE_SAMPLE_MS 的两次采样计算。
+#[tauri::command]
+async fn openakita_service_resources(workspace_id: String) -> Result<ResourceUsage, String> {
+    spawn_blocking_result(move || {
+        if !PROCESS_RESOURCES_SUPPORTED {
+            return Err("当前平台暂不支持读取进程资源占用".into());
+        }
+        let pid = running_backend_pid(&workspace_id)
+            .ok_or_else(|| "服务未运行".to_string())?;
+        let (cpu_before, _, _) = sample_process_resources(pid)
+            .ok_or_else(|| format!("读取进程资源失败 (PID={pid})"))?;
+        let t0 = std::time::Instant::now();
+        std::thread::sleep(std::time::Duration::from_millis(RESOURCE_SAMPLE_MS));
+        let (cpu_after, rss_bytes, num_threads) = sample_process_resources(pid)
+            .ok_or_else(|| format!("读取进程资源失败 (PID={pid})"))?;
+        let elapsed = t0.elapsed().as_secs_f64();
+        let cores = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1) as f64;
+        let cpu_percent = if elapsed > 0.0 {
+            ((cpu_after - cpu_before).max(0.0) / elapsed / cores * 100.0).min(100.0)
+        } else {
+            0.0
+        };
+        Ok(ResourceUsage {
+            pid,
+            cpu_percent,
+            rss_bytes,
+            num_threads,
+        })
+    })
+    .await
+}
+
+/// 孤儿扫描的候选进程（只列出，不结束）
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct OrphanCandidate {
+    pid: u32,
+    cmd: String,
+    /// 进程创建时间（Unix epoch 秒）
+    started_at: Option<u64>,
+    /// 记录该 PID 的 PID 文件所属工作区（没有 PID 文件登记时为 None）
+    workspace_id: Option<String>,
+    /// PID 文件中的 started_by
+    started_by: Option<String>,
+}
+
+/// 扫描所有 OpenAkita 后端进程：命令行为 `openakita.main ... serve` 的 python 进程，
+/// 以及 PyInstaller 打包的 openakita-server。`keep` 中的 PID 不列入。不结束任何进程。
+fn scan_openakita_orphans(keep: &[u32]) -> Vec<OrphanCandidate> {
+    let mut found: Vec<(u32, String)> = Vec::new();
     #[cfg(windows)]
     {
-        // Step 1: 用 Toolhelp32 枚举所有进程，找到进程名含 python 的
+        // Step 1: 用 Toolhelp32 枚举所有进程，找到进程名含 python / openakita-server 的
         let snap = unsafe { win::CreateToolhelp32Snapshot(win::TH32CS_SNAPPROCESS, 0) };
         if snap == win::INVALID_HANDLE_VALUE || snap.is_null() {
-            return killed;
+            return Vec::new();
         }
         let mut pe: win::PROCESSENTRY32W = unsafe { std::mem::zeroed() };
         pe.dw_size = std::mem::size_of::<win::PROCESSENTRY32W>() as u32;
@@ -1440,36 +3522,18 @@ fn kill_openakita_orphans() -> Vec<u32> {
             win::CloseHandle(snap);
         }
 
-        // Step 1.5: 直接 kill 孤立的 openakita-server.exe (PyInstaller bundled backend)
+        // Step 2: 一次取回全部命令行；python 进程按模块调用签名精确匹配，openakita-server 直接列入
+        let all_pids: Vec<u32> = python_pids.iter().chain(bundled_pids.iter()).copied().collect();
+        let cmdlines = command_lines_for(&all_pids);
         for ppid in bundled_pids {
-            if is_pid_running(ppid) {
-                let _ = kill_pid(ppid);
-                killed.push(ppid);
-            }
+            let cmd = cmdlines.get(&ppid).cloned().unwrap_or_else(|| "openakita-server.exe".to_string());
+            found.push((ppid, cmd));
         }
-
-        // Step 2: 对每个 python 进程查命令行，判断是否是 openakita serve 进程
-        // 使用 PowerShell Get-CimInstance 替代已废弃的 wmic（Windows 11 已移除 wmic）
         for ppid in python_pids {
-            let mut c = Command::new("powershell");
-            c.args([
-                "-NoProfile",
-                "-NonInteractive",
-                "-Command",
-                &format!(
-                    "(Get-CimInstance Win32_Process -Filter 'ProcessId={}').CommandLine",
-                    ppid
-                ),
-            ]);
-            apply_no_window(&mut c);
-            if let Ok(out) = c.output() {
-                let s = String::from_utf8_lossy(&out.stdout).to_lowercase();
-                // 精确匹配模块调用签名
+            if let Some(cmd) = cmdlines.get(&ppid) {
+                let s = cmd.to_lowercase();
                 if s.contains("openakita.main") && (s.contains(" serve") || s.ends_with("serve")) {
-                    if is_pid_running(ppid) {
-                        let _ = kill_pid(ppid);
-                        killed.push(ppid);
-                    }
+                    found.push((ppid, cmd.clone()));
                 }
             }
         }
@@ -1489,32 +3553,183 @@ fn kill_openakita_orphans() -> Vec<u32> {
                 let stdout = String::from_utf8_lossy(&out.stdout);
                 for line in stdout.lines() {
                     if let Ok(pid) = line.trim().parse::<u32>() {
-                        if is_pid_running(pid) && !killed.contains(&pid) {
-                            let _ = Command::new("kill")
-                                .args(["-TERM", &pid.to_string()])
-                                .status();
-                            killed.push(pid);
+                        if !found.iter().any(|(p, _)| *p == pid) {
+                            found.push((pid, process_command_line(pid).unwrap_or_default()));
                         }
                     }
                 }
             }
         }
     }
-    killed
+
+    let pid_entries = list_service_pids();
+    found
+        .into_iter()
+        .filter(|(pid, _)| !keep.contains(pid) && is_pid_running(*pid))
+        .map(|(pid, cmd)| {
+            let ent = pid_entries.iter().find(|e| e.pid == pid);
+            OrphanCandidate {
+                pid,
+                cmd,
+                started_at: get_process_create_time(pid),
+                workspace_id: ent.map(|e| e.workspace_id.clone()),
+                started_by: ent.map(|e| e.started_by.clone()),
+            }
+        })
+        .collect()
+}
+
+/// 扫描并结束所有 OpenAkita 后端进程（见 scan_openakita_orphans），连同各自的子进程树。
+/// 用于托盘退出时兜底清理孤儿进程（PID 文件可能已被删除但进程仍存活）。
+/// `keep`：需要保留的 PID（如 external 后端），不会被杀掉。
+/// 返回被杀掉的 PID 列表。
+fn kill_openakita_orphans(keep: &[u32]) -> Vec<u32> {
+    let mut killed: Vec<u32> = scan_openakita_orphans(keep)
+        .into_iter()
+        .flat_map(|c| kill_process_tree(c.pid, false))
+        .collect();
+    killed.sort_unstable();
+    killed.dedup();
+    killed
+}
+
+/// 诊断面板：列出孤儿扫描会命中的进程（命令行、启动时间、PID 文件对应的工作区），不结束任何进程。
+/// 用户确认后再调用 openakita_kill_processes。
+#[tauri::command]
+async fn openakita_scan_orphans() -> Result<Vec<OrphanCandidate>, String> {
+    spawn_blocking_result(|| Ok(scan_openakita_orphans(&[]))).await
+}
+
+/// 结束用户在扫描结果中确认的进程（连同子进程树）。
+/// 只处理仍是 OpenAkita 进程的 PID（防止扫描后 PID 被复用），其余忽略。返回实际结束的 PID。
+#[tauri::command]
+async fn openakita_kill_processes(pids: Vec<u32>) -> Result<Vec<u32>, String> {
+    spawn_blocking_result(move || {
+        let pid_entries = list_service_pids();
+        let mut killed = Vec::new();
+        for pid in pids {
+            if !is_openakita_process(pid) {
+                continue;
+            }
+            killed.extend(kill_process_tree(pid, false));
+            for ent in pid_entries.iter().filter(|e| e.pid == pid) {
+                let _ = fs::remove_file(&ent.pid_file);
+                remove_heartbeat_file(&ent.workspace_id);
+            }
+        }
+        killed.sort_unstable();
+        killed.dedup();
+        Ok(killed)
+    })
+    .await
+}
+
+/// 扫描所有进程名含 python 且命令行包含 "openakita" 和 "serve" 的进程。
+/// 返回 OpenAkitaProcess 列表，供前端多进程检测使用。
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct OpenAkitaProcess {
+    pid: u32,
+    cmd: String,
+    /// 所属工作区：优先匹配 PID 文件，其次从进程工作目录 / 命令行中的 workspaces/<id> 推断
+    #[serde(default)]
+    workspace_id: Option<String>,
+    /// 进程创建时间（Unix epoch 秒）
+    #[serde(default)]
+    started_at: Option<u64>,
+}
+
+/// 从路径中取出 workspaces/<id> 的 id
+fn workspace_id_from_path(path: &str) -> Option<String> {
+    let normalized = path.replace('\\', "/");
+    let rest = normalized.split("/workspaces/").nth(1)?;
+    let id = rest.split(['/', ' ', '"']).next()?;
+    (!id.is_empty()).then(|| id.to_string())
+}
+
+/// 推断进程所属工作区（后端以工作区目录为 cwd 启动，命令行本身不含工作区 id）
+fn process_workspace_id(pid: u32, cmd: &str, pid_entries: &[ServicePidEntry]) -> Option<String> {
+    if let Some(ent) = pid_entries.iter().find(|e| e.pid == pid) {
+        return Some(ent.workspace_id.clone());
+    }
+    #[cfg(target_os = "linux")]
+    if let Some(id) = fs::read_link(format!("/proc/{}/cwd", pid))
+        .ok()
+        .and_then(|cwd| workspace_id_from_path(&cwd.to_string_lossy()))
+    {
+        return Some(id);
+    }
+    workspace_id_from_path(cmd)
+}
+
+/// 为扫描到的进程补充工作区与启动时间
+fn enrich_openakita_process(pid: u32, cmd: String, pid_entries: &[ServicePidEntry]) -> OpenAkitaProcess {
+    OpenAkitaProcess {
+        workspace_id: process_workspace_id(pid, &cmd, pid_entries),
+        started_at: get_process_create_time(pid),
+        pid,
+        cmd,
+    }
+}
+
+/// 本进程（Setup Center）的启动时间，用于区分本次会话期间由 CLI 启动的后端与上次会话遗留的孤儿
+static SESSION_STARTED_AT: Lazy<u64> =
+    Lazy::new(|| get_process_create_time(std::process::id()).unwrap_or_else(now_epoch_secs));
+
+/// 没有 PID 文件登记的后端进程的所有权判定：
+/// - 本次会话托管（MANAGED_CHILDREN）的归 Tauri；
+/// - 本次会话启动之后才创建的视为外部（CLI）启动；
+/// - 早于本次会话创建、或取不到创建时间的返回 None：多半是上次 Tauri 会话遗留的孤儿，交给孤儿清理处理。
+fn backend_owner(managed: bool, process_started_at: Option<u64>, session_started_at: u64) -> Option<&'static str> {
+    if managed {
+        return Some(STARTED_BY_TAURI);
+    }
+    match process_started_at {
+        Some(t) if t > session_started_at => Some(STARTED_BY_EXTERNAL),
+        _ => None,
+    }
+}
+
+/// 为本次会话期间启动、既不在 MANAGED_CHILDREN 也没有 PID 文件登记的后端（通常是 CLI 直接启动的
+/// openakita serve）补写 external PID 文件，使退出清理保留它们。
+/// 无法推断工作区、或工作区已登记了另一个存活进程的不处理。返回新登记的 PID。
+/// 不在启动对账时调用：那时 MANAGED_CHILDREN 必然为空，会把上次会话的孤儿误登记为 external。
+fn tag_external_backends() -> Vec<u32> {
+    let managed: Vec<u32> = MANAGED_CHILDREN.lock().unwrap().values().map(|mp| mp.pid).collect();
+    let session_started_at = *SESSION_STARTED_AT;
+    let mut tagged = Vec::new();
+    for proc_info in openakita_list_processes() {
+        let Some(ws) = proc_info.workspace_id.as_deref() else {
+            continue;
+        };
+        let existing = read_pid_file(ws);
+        if existing.as_ref().is_some_and(|d| d.pid == proc_info.pid || is_pid_running(d.pid)) {
+            // 已登记（按登记的 started_by 处理），或工作区被另一个存活进程占用
+            continue;
+        }
+        let owner = backend_owner(managed.contains(&proc_info.pid), proc_info.started_at, session_started_at);
+        if owner == Some(STARTED_BY_EXTERNAL)
+            && write_pid_file(ws, proc_info.pid, STARTED_BY_EXTERNAL, None).is_ok()
+        {
+            tagged.push(proc_info.pid);
+        }
+    }
+    tagged
 }
 
-/// 扫描所有进程名含 python 且命令行包含 "openakita" 和 "serve" 的进程。
-/// 返回 OpenAkitaProcess 列表，供前端多进程检测使用。
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(rename_all = "camelCase")]
-struct OpenAkitaProcess {
-    pid: u32,
-    cmd: String,
+/// 当前登记为 external 的存活后端 PID
+fn external_backend_pids() -> Vec<u32> {
+    list_service_pids()
+        .into_iter()
+        .filter(|e| e.started_by == STARTED_BY_EXTERNAL && is_pid_running(e.pid))
+        .map(|e| e.pid)
+        .collect()
 }
 
 #[tauri::command]
 fn openakita_list_processes() -> Vec<OpenAkitaProcess> {
     let mut out = Vec::new();
+    let pid_entries = list_service_pids();
     #[cfg(windows)]
     {
         // Step 1: 枚举所有进程，找到进程名含 python 的 PID
@@ -1549,29 +3764,15 @@ fn openakita_list_processes() -> Vec<OpenAkitaProcess> {
             win::CloseHandle(snap);
         }
 
-        // Step 2: 对每个 python 进程查命令行
+        // Step 2: 一次 CIM 查询取回所有 python 进程的命令行，本地匹配
+        let cmdlines = command_lines_for(&python_pids);
         for ppid in python_pids {
-            let mut c = Command::new("powershell");
-            c.args([
-                "-NoProfile",
-                "-NonInteractive",
-                "-Command",
-                &format!(
-                    "(Get-CimInstance Win32_Process -Filter 'ProcessId={}').CommandLine",
-                    ppid
-                ),
-            ]);
-            apply_no_window(&mut c);
-            if let Ok(cmd_out) = c.output() {
-                let s = String::from_utf8_lossy(&cmd_out.stdout).to_string();
+            if let Some(s) = cmdlines.get(&ppid) {
                 let s_lower = s.to_lowercase();
                 // 精确匹配模块调用签名，避免 venv 路径中 .openakita 误报
                 if s_lower.contains("openakita.main") && (s_lower.contains(" serve") || s_lower.ends_with("serve")) {
                     if is_pid_running(ppid) {
-                        out.push(OpenAkitaProcess {
-                            pid: ppid,
-                            cmd: s.trim().to_string(),
-                        });
+                        out.push(enrich_openakita_process(ppid, s.trim().to_string(), &pid_entries));
                     }
                 }
             }
@@ -1590,10 +3791,7 @@ fn openakita_list_processes() -> Vec<OpenAkitaProcess> {
                 if parts.len() >= 2 {
                     if let Ok(pid) = parts[1].parse::<u32>() {
                         if is_pid_running(pid) {
-                            out.push(OpenAkitaProcess {
-                                pid,
-                                cmd: parts[10..].join(" "),
-                            });
+                            out.push(enrich_openakita_process(pid, parts[10..].join(" "), &pid_entries));
                         }
                     }
                 }
@@ -1604,7 +3802,7 @@ fn openakita_list_processes() -> Vec<OpenAkitaProcess> {
 }
 
 /// 停止所有检测到的 OpenAkita serve 进程。
-/// 返回被停止的 PID 列表。
+/// 返回被停止的 PID 列表（含强制结束的子进程树）。
 #[tauri::command]
 fn openakita_stop_all_processes() -> Vec<u32> {
     let mut stopped = Vec::new();
@@ -1614,13 +3812,18 @@ fn openakita_stop_all_processes() -> Vec<u32> {
     for ent in &entries {
         if is_pid_running(ent.pid) {
             let port = read_workspace_api_port(&ent.workspace_id);
-            let _ = stop_service_pid_entry(ent, port);
+            let killed = stop_service_pid_entry(ent, port, graceful_stop_timeout_secs(), None).unwrap_or_default();
             stopped.push(ent.pid);
+            for pid in killed {
+                if !stopped.contains(&pid) {
+                    stopped.push(pid);
+                }
+            }
         }
     }
 
     // 第 2 层：兜底扫描所有命令行含 openakita serve 的 python 进程并杀掉
-    let orphans = kill_openakita_orphans();
+    let orphans = kill_openakita_orphans(&[]);
     for pid in orphans {
         if !stopped.contains(&pid) {
             stopped.push(pid);
@@ -1630,15 +3833,77 @@ fn openakita_stop_all_processes() -> Vec<u32> {
     stopped
 }
 
+/// 停止指定 PID 的 OpenAkita 进程（openakita_list_processes 中的单个条目）。
+/// 先确认 PID 确实是 OpenAkita 进程，避免误杀 PID 复用后的无关进程；
+/// 能确定其监听端口时先走 HTTP 优雅关闭，否则直接强制结束。
+/// 返回强制结束的 PID（含子进程树）；优雅退出时为空。
+#[tauri::command]
+async fn openakita_kill_process(pid: u32) -> Result<Vec<u32>, String> {
+    spawn_blocking_result(move || kill_openakita_process(pid)).await
+}
+
+/// openakita_kill_process 的同步实现（优雅关闭最多等待 graceful_stop_timeout_secs 秒，勿在主线程调用）
+fn kill_openakita_process(pid: u32) -> Result<Vec<u32>, String> {
+    if !is_openakita_process(pid) {
+        return Err(format!("PID {pid} 不是 OpenAkita 进程，已拒绝操作"));
+    }
+
+    // 由 PID 文件登记的后端：按工作区正常停止（同时清理 PID/心跳文件，并告知看门狗这是用户主动停止）
+    if let Some(ent) = list_service_pids().into_iter().find(|e| e.pid == pid) {
+        watchdog_mark_user_stopped(&ent.workspace_id);
+        stop_heartbeat_watcher(&ent.workspace_id);
+        let managed = MANAGED_CHILDREN.lock().unwrap().remove(&ent.workspace_id);
+        let port = read_workspace_api_port(&ent.workspace_id);
+        let killed = stop_service_pid_entry(&ent, port, graceful_stop_timeout_secs(), None)?;
+        if let Some(mut mp) = managed {
+            let _ = mp.child.wait();
+        }
+        return Ok(killed);
+    }
+
+    // 未登记的进程：只有确认端口确实由该 PID 监听时才调用 /api/shutdown，
+    // 以免误关同端口上的其他后端
+    let mut candidate_ports: Vec<u16> = read_state_file()
+        .workspaces
+        .iter()
+        .filter_map(|w| read_workspace_api_port(&w.id))
+        .collect();
+    candidate_ports.push(default_api_port());
+    candidate_ports.sort_unstable();
+    candidate_ports.dedup();
+    let port = candidate_ports
+        .into_iter()
+        .find(|&p| find_port_owner_pid(p) == Some(pid));
+    if port.is_some() {
+        return graceful_stop_pid(pid, port, graceful_stop_timeout_secs(), None);
+    }
+    kill_pid(pid)?;
+    for _ in 0..10 {
+        if !is_pid_running(pid) {
+            return Ok(vec![pid]);
+        }
+        std::thread::sleep(std::time::Duration::from_millis(200));
+    }
+    Err(format!("pid {} still running after forced stop", pid))
+}
+
 fn read_state_file() -> AppStateFile {
     let p = state_file_path();
     let Ok(content) = fs::read_to_string(&p) else {
         return AppStateFile::default();
     };
-    serde_json::from_str(&content).unwrap_or_default()
+    let mut state: AppStateFile = serde_json::from_str(&content).unwrap_or_default();
+    state.fold_legacy_settings();
+    state
 }
 
+/// state.json 由更新版本写入时记录其 configVersion；此时只读不写，避免丢失旧版不认识的字段
+static NEWER_CONFIG_VERSION: Lazy<Mutex<Option<u32>>> = Lazy::new(|| Mutex::new(None));
+
 fn write_state_file(state: &AppStateFile) -> Result<(), String> {
+    if let Some(version) = *NEWER_CONFIG_VERSION.lock().unwrap() {
+        return Err(migrations::newer_version_message(version));
+    }
     let p = state_file_path();
     if let Some(parent) = p.parent() {
         fs::create_dir_all(parent).map_err(|e| format!("create_dir_all failed: {e}"))?;
@@ -1648,10 +3913,82 @@ fn write_state_file(state: &AppStateFile) -> Result<(), String> {
     Ok(())
 }
 
+/// identity 目录下由仓库模板生成的文件（相对 identity/ 的路径 → 嵌入内容）。
+/// 8 个标配人格预设 + user_custom 模板从仓库 identity/personas/ 嵌入，确保新工作区开箱即用。
+const IDENTITY_TEMPLATES: &[(&str, &str)] = &[
+    ("SOUL.md", include_str!("../../../../identity/SOUL.md.example")),
+    ("AGENT.md", include_str!("../../../../identity/AGENT.md.example")),
+    ("USER.md", include_str!("../../../../identity/USER.md.example")),
+    ("MEMORY.md", include_str!("../../../../identity/MEMORY.md.example")),
+    ("personas/default.md", include_str!("../../../../identity/personas/default.md")),
+    ("personas/business.md", include_str!("../../../../identity/personas/business.md")),
+    ("personas/tech_expert.md", include_str!("../../../../identity/personas/tech_expert.md")),
+    ("personas/butler.md", include_str!("../../../../identity/personas/butler.md")),
+    ("personas/girlfriend.md", include_str!("../../../../identity/personas/girlfriend.md")),
+    ("personas/boyfriend.md", include_str!("../../../../identity/personas/boyfriend.md")),
+    ("personas/family.md", include_str!("../../../../identity/personas/family.md")),
+    ("personas/jarvis.md", include_str!("../../../../identity/personas/jarvis.md")),
+    ("personas/user_custom.md", include_str!("../../../../identity/personas/user_custom.md")),
+    // policies 文件：运行时策略规则，builder.py 会读取
+    ("prompts/policies.md", include_str!("../../../../identity/prompts/policies.md")),
+];
+
+/// compiled 黄金文件：预编译的身份摘要（文件名 → 嵌入内容）
+const COMPILED_IDENTITY_FILES: &[(&str, &str)] = &[
+    ("soul.summary.md", include_str!("../../../../identity/compiled/soul.summary.md")),
+    ("agent.core.md", include_str!("../../../../identity/compiled/agent.core.md")),
+    ("agent.tooling.md", include_str!("../../../../identity/compiled/agent.tooling.md")),
+];
+
+/// 记录"写入时模板内容哈希"的 manifest（位于 identity/ 下）
+const TEMPLATE_MANIFEST_FILE: &str = ".template-manifest.json";
+
+/// 模板内容哈希（FNV-1a 64 位）。只用于检测文件是否被改动，需要跨版本稳定，
+/// 因此不用 std 的 DefaultHasher（其算法不保证稳定）。
+fn template_content_hash(content: &str) -> String {
+    let mut h: u64 = 0xcbf29ce484222325;
+    for b in content.as_bytes() {
+        h ^= *b as u64;
+        h = h.wrapping_mul(0x100000001b3);
+    }
+    format!("fnv1a64:{h:016x}")
+}
+
+/// 旧版本内置模板的内容哈希（相对路径 → 哈希列表）。本功能之前创建的工作区没有 manifest，
+/// 其中内容与任一旧版模板一致的文件视为用户未改动。修改内置模板时把旧版本的哈希追加到这里。
+/// 不要收录空文件的哈希：用户可能是有意清空文件。
+const KNOWN_TEMPLATE_HASHES: &[(&str, &[&str])] = &[];
+
+fn is_known_template_hash(rel: &str, hash: &str) -> bool {
+    KNOWN_TEMPLATE_HASHES
+        .iter()
+        .any(|(r, hashes)| *r == rel && hashes.contains(&hash))
+}
+
+fn read_template_manifest(identity_dir: &Path) -> std::collections::BTreeMap<String, String> {
+    fs::read_to_string(identity_dir.join(TEMPLATE_MANIFEST_FILE))
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+fn write_template_manifest(
+    identity_dir: &Path,
+    manifest: &std::collections::BTreeMap<String, String>,
+) -> Result<(), String> {
+    let data = serde_json::to_string_pretty(manifest)
+        .map_err(|e| format!("serialize template manifest failed: {e}"))?;
+    write_file_atomic(&identity_dir.join(TEMPLATE_MANIFEST_FILE), data.as_bytes())
+        .map_err(|e| format!("write identity/{TEMPLATE_MANIFEST_FILE} failed: {e}"))
+}
+
 fn ensure_workspace_scaffold(dir: &Path) -> Result<(), String> {
     fs::create_dir_all(dir.join("data")).map_err(|e| format!("create data dir failed: {e}"))?;
     fs::create_dir_all(dir.join("identity")).map_err(|e| format!("create identity dir failed: {e}"))?;
 
+    // 本次新建的文件（相对工作区路径），写入 .scaffold.json
+    let mut created: Vec<String> = vec![];
+
     // 默认 .env：Setup Center 会按“你实际填写的字段”生成/维护。
     // 不再把完整模板复制进工作区，避免产生大量空值键（会导致 pydantic 解析失败/污染配置）。
     let env_path = dir.join(".env");
@@ -1667,79 +4004,33 @@ fn ensure_workspace_scaffold(dir: &Path) -> Result<(), String> {
         ]
         .join("\n");
         fs::write(&env_path, content).map_err(|e| format!("write .env failed: {e}"))?;
+        created.push(".env".to_string());
     }
 
-    // identity 文件：从仓库模板复制生成，保证字段完整性与一致性（而不是随意占位）
-    const DEFAULT_SOUL: &str = include_str!("../../../../identity/SOUL.md.example");
-    const DEFAULT_AGENT: &str = include_str!("../../../../identity/AGENT.md.example");
-    const DEFAULT_USER: &str = include_str!("../../../../identity/USER.md.example");
-    const DEFAULT_MEMORY: &str = include_str!("../../../../identity/MEMORY.md.example");
-
-    let soul = dir.join("identity").join("SOUL.md");
-    if !soul.exists() {
-        fs::write(&soul, DEFAULT_SOUL).map_err(|e| format!("write identity/SOUL.md failed: {e}"))?;
-    }
-    let agent_md = dir.join("identity").join("AGENT.md");
-    if !agent_md.exists() {
-        fs::write(&agent_md, DEFAULT_AGENT).map_err(|e| format!("write identity/AGENT.md failed: {e}"))?;
-    }
-    let user_md = dir.join("identity").join("USER.md");
-    if !user_md.exists() {
-        fs::write(&user_md, DEFAULT_USER).map_err(|e| format!("write identity/USER.md failed: {e}"))?;
-    }
-    let memory_md = dir.join("identity").join("MEMORY.md");
-    if !memory_md.exists() {
-        fs::write(&memory_md, DEFAULT_MEMORY).map_err(|e| format!("write identity/MEMORY.md failed: {e}"))?;
-    }
-
-    // 人格预设文件：8 个标配预设 + user_custom 模板
-    // 从仓库 identity/personas/ 目录嵌入，确保新工作区开箱即用
+    // identity / 人格预设 / policies：从仓库模板复制生成，保证字段完整性与一致性（而不是随意占位）。
+    // 写入时记录模板哈希到 manifest，供 sync_workspace_templates 判断用户是否修改过。
     {
-        const PERSONA_DEFAULT: &str = include_str!("../../../../identity/personas/default.md");
-        const PERSONA_BUSINESS: &str = include_str!("../../../../identity/personas/business.md");
-        const PERSONA_TECH_EXPERT: &str = include_str!("../../../../identity/personas/tech_expert.md");
-        const PERSONA_BUTLER: &str = include_str!("../../../../identity/personas/butler.md");
-        const PERSONA_GIRLFRIEND: &str = include_str!("../../../../identity/personas/girlfriend.md");
-        const PERSONA_BOYFRIEND: &str = include_str!("../../../../identity/personas/boyfriend.md");
-        const PERSONA_FAMILY: &str = include_str!("../../../../identity/personas/family.md");
-        const PERSONA_JARVIS: &str = include_str!("../../../../identity/personas/jarvis.md");
-        const PERSONA_USER_CUSTOM: &str = include_str!("../../../../identity/personas/user_custom.md");
-
-        let personas_dir = dir.join("identity").join("personas");
-        fs::create_dir_all(&personas_dir)
+        let identity_dir = dir.join("identity");
+        fs::create_dir_all(identity_dir.join("personas"))
             .map_err(|e| format!("create identity/personas dir failed: {e}"))?;
+        fs::create_dir_all(identity_dir.join("prompts"))
+            .map_err(|e| format!("create identity/prompts dir failed: {e}"))?;
 
-        let presets: &[(&str, &str)] = &[
-            ("default.md", PERSONA_DEFAULT),
-            ("business.md", PERSONA_BUSINESS),
-            ("tech_expert.md", PERSONA_TECH_EXPERT),
-            ("butler.md", PERSONA_BUTLER),
-            ("girlfriend.md", PERSONA_GIRLFRIEND),
-            ("boyfriend.md", PERSONA_BOYFRIEND),
-            ("family.md", PERSONA_FAMILY),
-            ("jarvis.md", PERSONA_JARVIS),
-            ("user_custom.md", PERSONA_USER_CUSTOM),
-        ];
-
-        for (filename, content) in presets {
-            let path = personas_dir.join(filename);
+        let mut written = vec![];
+        for (rel, content) in IDENTITY_TEMPLATES {
+            let path = identity_dir.join(rel);
             if !path.exists() {
-                fs::write(&path, content)
-                    .map_err(|e| format!("write identity/personas/{filename} failed: {e}"))?;
+                fs::write(&path, content).map_err(|e| format!("write identity/{rel} failed: {e}"))?;
+                written.push((*rel, *content));
+                created.push(format!("identity/{rel}"));
             }
         }
-    }
-
-    // policies 文件：运行时策略规则，builder.py 会读取
-    {
-        let prompts_dir = dir.join("identity").join("prompts");
-        fs::create_dir_all(&prompts_dir)
-            .map_err(|e| format!("create identity/prompts dir failed: {e}"))?;
-        let policies = prompts_dir.join("policies.md");
-        if !policies.exists() {
-            const DEFAULT_POLICIES: &str = include_str!("../../../../identity/prompts/policies.md");
-            fs::write(&policies, DEFAULT_POLICIES)
-                .map_err(|e| format!("write identity/prompts/policies.md failed: {e}"))?;
+        if !written.is_empty() {
+            let mut manifest = read_template_manifest(&identity_dir);
+            for (rel, content) in written {
+                manifest.insert(rel.to_string(), template_content_hash(content));
+            }
+            write_template_manifest(&identity_dir, &manifest)?;
         }
     }
 
@@ -1749,20 +4040,12 @@ fn ensure_workspace_scaffold(dir: &Path) -> Result<(), String> {
         fs::create_dir_all(&compiled_dir)
             .map_err(|e| format!("create identity/compiled dir failed: {e}"))?;
 
-        const SOUL_SUMMARY: &str = include_str!("../../../../identity/compiled/soul.summary.md");
-        const AGENT_CORE: &str = include_str!("../../../../identity/compiled/agent.core.md");
-        const AGENT_TOOLING: &str = include_str!("../../../../identity/compiled/agent.tooling.md");
-
-        let golden_files: &[(&str, &str)] = &[
-            ("soul.summary.md", SOUL_SUMMARY),
-            ("agent.core.md", AGENT_CORE),
-            ("agent.tooling.md", AGENT_TOOLING),
-        ];
-        for (filename, content) in golden_files {
+        for (filename, content) in COMPILED_IDENTITY_FILES {
             let path = compiled_dir.join(filename);
             if !path.exists() {
                 fs::write(&path, content)
                     .map_err(|e| format!("write identity/compiled/{filename} failed: {e}"))?;
+                created.push(format!("identity/compiled/{filename}"));
             }
         }
     }
@@ -1773,11 +4056,192 @@ fn ensure_workspace_scaffold(dir: &Path) -> Result<(), String> {
         const DEFAULT_LLM_ENDPOINTS: &str = include_str!("../../../../data/llm_endpoints.json.example");
         fs::write(&llm, DEFAULT_LLM_ENDPOINTS)
             .map_err(|e| format!("write data/llm_endpoints.json failed: {e}"))?;
+        created.push("data/llm_endpoints.json".to_string());
+    }
+
+    if !created.is_empty() {
+        record_scaffold(dir, created)?;
     }
 
     Ok(())
 }
 
+/// 工作区根目录下记录脚手架版本的文件
+const SCAFFOLD_FILE: &str = ".scaffold.json";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+struct ScaffoldRecord {
+    /// 最近一次写入脚手架文件的 Setup Center 版本
+    version: String,
+    /// 由脚手架创建过的文件（相对工作区路径，累积记录）
+    created_files: Vec<String>,
+}
+
+fn read_scaffold_record(dir: &Path) -> Option<ScaffoldRecord> {
+    let raw = fs::read_to_string(dir.join(SCAFFOLD_FILE)).ok()?;
+    serde_json::from_str(&raw).ok()
+}
+
+/// 更新 .scaffold.json：版本号改为当前版本，合并本次创建的文件
+fn record_scaffold(dir: &Path, created: Vec<String>) -> Result<(), String> {
+    let mut record = read_scaffold_record(dir).unwrap_or_default();
+    record.version = env!("CARGO_PKG_VERSION").to_string();
+    for f in created {
+        if !record.created_files.contains(&f) {
+            record.created_files.push(f);
+        }
+    }
+    let data = serde_json::to_string_pretty(&record)
+        .map_err(|e| format!("serialize {SCAFFOLD_FILE} failed: {e}"))?;
+    write_file_atomic(&dir.join(SCAFFOLD_FILE), data.as_bytes())
+        .map_err(|e| format!("write {SCAFFOLD_FILE} failed: {e}"))
+}
+
+/// ensure_workspace_scaffold 负责的全部文件（相对工作区路径）
+fn scaffold_expected_files() -> Vec<String> {
+    let mut files = vec![".env".to_string(), "data/llm_endpoints.json".to_string()];
+    files.extend(IDENTITY_TEMPLATES.iter().map(|(rel, _)| format!("identity/{rel}")));
+    files.extend(
+        COMPILED_IDENTITY_FILES
+            .iter()
+            .map(|(name, _)| format!("identity/compiled/{name}")),
+    );
+    files
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ScaffoldStatus {
+    /// .scaffold.json 中记录的版本；老版本创建的工作区没有记录时为 None
+    recorded_version: Option<String>,
+    current_version: String,
+    up_to_date: bool,
+    missing_files: Vec<String>,
+}
+
+/// 报告工作区由哪个版本搭建、以及哪些脚手架文件缺失（只读，不会补齐文件）
+#[tauri::command]
+fn workspace_scaffold_status(workspace_id: String) -> Result<ScaffoldStatus, String> {
+    let dir = workspace_dir(&workspace_id);
+    if !dir.exists() {
+        return Err(format!("workspace not found: {workspace_id}"));
+    }
+    let recorded_version = read_scaffold_record(&dir).map(|r| r.version);
+    let current_version = env!("CARGO_PKG_VERSION").to_string();
+    let missing_files: Vec<String> = scaffold_expected_files()
+        .into_iter()
+        .filter(|rel| !dir.join(rel).exists())
+        .collect();
+    Ok(ScaffoldStatus {
+        up_to_date: recorded_version.as_deref() == Some(current_version.as_str())
+            && missing_files.is_empty(),
+        recorded_version,
+        current_version,
+        missing_files,
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+struct TemplateSyncReport {
+    /// 工作区中缺失的模板文件
+    missing: Vec<String>,
+    /// 用户未改动、但已落后于当前内置模板的文件
+    outdated: Vec<String>,
+    /// 用户改动过的文件（永不覆盖）
+    modified: Vec<String>,
+    /// 本次实际写入的文件
+    updated: Vec<String>,
+    /// 被替换文件的备份目录（仅在有文件被替换时返回）
+    backup_dir: Option<String>,
+}
+
+/// 对比工作区 identity 文件与内置模板（应用更新后老工作区拿不到新人格/默认文件修复）。
+/// 是否被用户改动以 manifest 中记录的"写入时模板哈希"为准；没有记录时，内容与当前模板及
+/// KNOWN_TEMPLATE_HASHES 中的旧版模板都不同的文件才视为用户改动。
+/// `overwrite_unmodified` 为 true 时补齐缺失文件、更新未改动的过期文件，被替换的文件备份到
+/// identity/.template-backup/<时间戳>/ 下。
+#[tauri::command]
+fn sync_workspace_templates(
+    workspace_id: String,
+    overwrite_unmodified: bool,
+) -> Result<TemplateSyncReport, String> {
+    let dir = workspace_dir(&workspace_id);
+    if !dir.exists() {
+        return Err(format!("workspace not found: {workspace_id}"));
+    }
+    let identity_dir = dir.join("identity");
+    let lock = workspace_write_lock(&workspace_id);
+    let _guard = lock.lock().unwrap();
+
+    let mut manifest = read_template_manifest(&identity_dir);
+    let mut manifest_changed = false;
+    let mut report = TemplateSyncReport::default();
+    let backup_root = identity_dir
+        .join(".template-backup")
+        .join(now_epoch_secs().to_string());
+
+    for (rel, template) in IDENTITY_TEMPLATES {
+        let path = identity_dir.join(rel);
+        let display = format!("identity/{rel}");
+        let template_hash = template_content_hash(template);
+        let current = match fs::read_to_string(&path) {
+            Ok(c) => c,
+            Err(_) => {
+                report.missing.push(display.clone());
+                if overwrite_unmodified {
+                    if let Some(parent) = path.parent() {
+                        fs::create_dir_all(parent)
+                            .map_err(|e| format!("create dir for {display} failed: {e}"))?;
+                    }
+                    write_file_atomic(&path, template.as_bytes())
+                        .map_err(|e| format!("write {display} failed: {e}"))?;
+                    manifest.insert(rel.to_string(), template_hash);
+                    manifest_changed = true;
+                    report.updated.push(display);
+                }
+                continue;
+            }
+        };
+
+        let current_hash = template_content_hash(&current);
+        if current_hash == template_hash {
+            // 已是最新模板；补记 manifest 以便后续判断
+            if manifest.get(*rel) != Some(&template_hash) {
+                manifest.insert(rel.to_string(), template_hash);
+                manifest_changed = true;
+            }
+            continue;
+        }
+        if manifest.get(*rel) != Some(&current_hash) && !is_known_template_hash(rel, &current_hash) {
+            report.modified.push(display);
+            continue;
+        }
+
+        report.outdated.push(display.clone());
+        if overwrite_unmodified {
+            let backup = backup_root.join(rel);
+            if let Some(parent) = backup.parent() {
+                fs::create_dir_all(parent)
+                    .map_err(|e| format!("create template backup dir failed: {e}"))?;
+            }
+            fs::copy(&path, &backup).map_err(|e| format!("backup {display} failed: {e}"))?;
+            report.backup_dir = Some(backup_root.to_string_lossy().to_string());
+            write_file_atomic(&path, template.as_bytes())
+                .map_err(|e| format!("write {display} failed: {e}"))?;
+            manifest.insert(rel.to_string(), template_hash);
+            manifest_changed = true;
+            report.updated.push(display);
+        }
+    }
+
+    if manifest_changed {
+        write_template_manifest(&identity_dir, &manifest)?;
+    }
+    Ok(report)
+}
+
 #[tauri::command]
 fn list_workspaces() -> Result<Vec<WorkspaceSummary>, String> {
     let root = openakita_root_dir();
@@ -1796,6 +4260,8 @@ fn list_workspaces() -> Result<Vec<WorkspaceSummary>, String> {
             name: w.name.clone(),
             path: dir.to_string_lossy().to_string(),
             is_current: current.as_deref() == Some(&w.id),
+            created_at: w.created_at,
+            last_started_at: w.last_started_at,
         });
     }
     Ok(out)
@@ -1816,9 +4282,19 @@ fn create_workspace(id: String, name: String, set_current: bool) -> Result<Works
     if state.workspaces.iter().any(|w| w.id == id) {
         return Err("workspace id already exists".into());
     }
+
+    // 先生成脚手架并分配端口，全部成功后再写入 state：
+    // 否则中途失败会留下没有 API_PORT 的工作区，回退到默认端口与其他工作区冲突
+    let dir = workspace_dir(&id);
+    ensure_workspace_scaffold(&dir)?;
+    assign_workspace_port(&id)?;
+
+    let created_at = now_epoch_secs();
     state.workspaces.push(WorkspaceMeta {
         id: id.clone(),
         name: name.clone(),
+        created_at: Some(created_at),
+        last_started_at: None,
     });
     if set_current {
         state.current_workspace_id = Some(id.clone());
@@ -1827,26 +4303,474 @@ fn create_workspace(id: String, name: String, set_current: bool) -> Result<Works
     }
     write_state_file(&state)?;
 
-    let dir = workspace_dir(&id);
-    ensure_workspace_scaffold(&dir)?;
+    Ok(WorkspaceSummary {
+        id: id.clone(),
+        name,
+        path: dir.to_string_lossy().to_string(),
+        is_current: state.current_workspace_id.as_deref() == Some(&id),
+        created_at: Some(created_at),
+        last_started_at: None,
+    })
+}
+
+/// 为工作区分配一个不与其他工作区冲突的 API_PORT，并写入其 .env。
+/// 从 default_api_port() 起递增，跳过其他工作区已配置的端口以及当前被占用的端口。
+fn assign_workspace_port(workspace_id: &str) -> Result<u16, String> {
+    let state = read_state_file();
+    let base = default_api_port();
+    let taken: std::collections::HashSet<u16> = state
+        .workspaces
+        .iter()
+        .filter(|w| w.id != workspace_id)
+        .map(|w| read_workspace_api_port(&w.id).unwrap_or(base))
+        .collect();
+    let port = (0..1000u16)
+        .filter_map(|i| base.checked_add(i))
+        .find(|p| !taken.contains(p) && check_port_available(*p))
+        .ok_or_else(|| "no free API_PORT available for workspace".to_string())?;
+    workspace_update_env(
+        workspace_id.to_string(),
+        vec![EnvEntry {
+            key: "API_PORT".into(),
+            value: port.to_string(),
+        }],
+    )?;
+    Ok(port)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct WorkspacePort {
+    workspace_id: String,
+    port: u16,
+    in_use: bool,
+}
+
+/// 列出各工作区配置的 API_PORT 及其当前是否被占用，供 UI 在启动前提示端口冲突
+#[tauri::command]
+fn list_workspace_ports() -> Result<Vec<WorkspacePort>, String> {
+    let state = read_state_file();
+    let base = default_api_port();
+    Ok(state
+        .workspaces
+        .iter()
+        .map(|w| {
+            let port = read_workspace_api_port(&w.id).unwrap_or(base);
+            WorkspacePort {
+                workspace_id: w.id.clone(),
+                port,
+                in_use: !check_port_available(port),
+            }
+        })
+        .collect())
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct PersonaInfo {
+    /// 预设名（文件名去掉 .md），即 .env 中 PERSONA_NAME 的取值
+    name: String,
+    filename: String,
+    /// 文件首个非空行（去掉 Markdown 标题符号）
+    title: String,
+    /// 是否为内置预设（否则为用户自行添加）
+    builtin: bool,
+    active: bool,
+}
+
+/// user_custom.md 是叠加在预设之上的用户偏好层，不是可选预设（后端 available_presets 同样排除它）
+const USER_CUSTOM_PERSONA: &str = "user_custom";
+
+/// 列出 identity/personas/ 下的人格预设文件
+#[tauri::command]
+fn list_personas(workspace_id: String) -> Result<Vec<PersonaInfo>, String> {
+    let personas_dir = workspace_dir(&workspace_id).join("identity").join("personas");
+    let active = read_env_kv(&workspace_dir(&workspace_id).join(".env"))
+        .into_iter()
+        .rev()
+        .find(|(k, _)| k == "PERSONA_NAME")
+        .map(|(_, v)| v)
+        .unwrap_or_else(|| "default".to_string());
+
+    let entries = match fs::read_dir(&personas_dir) {
+        Ok(e) => e,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(format!("read identity/personas failed: {e}")),
+    };
+    let mut out = vec![];
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.is_file() || path.extension().and_then(|e| e.to_str()) != Some("md") {
+            continue;
+        }
+        let filename = entry.file_name().to_string_lossy().to_string();
+        let name = filename.trim_end_matches(".md").to_string();
+        if name == USER_CUSTOM_PERSONA {
+            continue;
+        }
+        let title = fs::read_to_string(&path)
+            .ok()
+            .and_then(|c| {
+                c.lines()
+                    .map(|l| l.trim())
+                    .find(|l| !l.is_empty())
+                    .map(|l| l.trim_start_matches('#').trim().to_string())
+            })
+            .unwrap_or_default();
+        let builtin = IDENTITY_TEMPLATES
+            .iter()
+            .any(|(rel, _)| rel.strip_prefix("personas/") == Some(filename.as_str()));
+        out.push(PersonaInfo {
+            active: name == active,
+            name,
+            filename,
+            title,
+            builtin,
+        });
+    }
+    out.sort_by(|a, b| b.builtin.cmp(&a.builtin).then_with(|| a.filename.cmp(&b.filename)));
+    Ok(out)
+}
+
+/// 设置当前人格预设（写入 .env 的 PERSONA_NAME）。后端运行中时发送重启提示事件。
+#[tauri::command]
+fn set_active_persona(app: tauri::AppHandle, workspace_id: String, filename: String) -> Result<(), String> {
+    if filename.is_empty() || filename.contains(['/', '\\']) || filename.contains("..") {
+        return Err(format!("invalid persona filename: {filename}"));
+    }
+    let path = workspace_dir(&workspace_id)
+        .join("identity")
+        .join("personas")
+        .join(&filename);
+    if !path.is_file() || !filename.ends_with(".md") {
+        return Err(format!("persona not found: identity/personas/{filename}"));
+    }
+    let name = filename.trim_end_matches(".md").to_string();
+    if name == USER_CUSTOM_PERSONA {
+        return Err("user_custom.md 是用户偏好叠加层，不能作为人格预设".into());
+    }
+    workspace_update_env(
+        workspace_id.clone(),
+        vec![EnvEntry {
+            key: "PERSONA_NAME".into(),
+            value: name.clone(),
+        }],
+    )?;
+    if running_backend_pid(&workspace_id).is_some() {
+        let _ = app.emit("service-restart-hint", serde_json::json!({
+            "workspaceId": workspace_id,
+            "reason": "persona-changed",
+            "persona": name,
+        }));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct SwitchWorkspaceResult {
+    switched: bool,
+    previous_running: bool,
+    previous_pid: Option<u32>,
+}
+
+/// 查询工作区后端是否在运行：优先 MANAGED_CHILDREN，其次校验 PID 文件。返回运行中的 PID。
+fn running_backend_pid(workspace_id: &str) -> Option<u32> {
+    {
+        let mut guard = MANAGED_CHILDREN.lock().unwrap();
+        if let Some(mp) = guard.get_mut(workspace_id) {
+            if matches!(mp.child.try_wait(), Ok(None)) {
+                return Some(mp.pid);
+            }
+        }
+    }
+    read_pid_file(workspace_id)
+        .filter(is_pid_file_valid)
+        .map(|d| d.pid)
+}
+
+/// 切换当前工作区。
+/// - 旧工作区后端仍在运行时，`stop_previous`：
+///   - `Some(true)`：先优雅停止旧后端再切换（停止失败则报错，不切换）
+///   - `Some(false)`：不切换，返回 switched=false，由前端提示用户
+///   - `None`：保持旧行为直接切换，仅在返回值中报告 previousRunning
+/// - 切换后按新工作区的运行状态刷新托盘 tooltip
+#[tauri::command]
+async fn set_current_workspace(
+    app: tauri::AppHandle,
+    id: String,
+    stop_previous: Option<bool>,
+) -> Result<SwitchWorkspaceResult, String> {
+    spawn_blocking_result(move || switch_current_workspace(&app, &id, stop_previous)).await
+}
+
+/// set_current_workspace 的同步实现（停止旧后端时会等待优雅关闭，勿在主线程调用）
+fn switch_current_workspace(
+    app: &tauri::AppHandle,
+    id: &str,
+    stop_previous: Option<bool>,
+) -> Result<SwitchWorkspaceResult, String> {
+    let id = id.to_string();
+    let mut state = read_state_file();
+    if !state.workspaces.iter().any(|w| w.id == id) {
+        return Err("workspace id not found".into());
+    }
+
+    let previous_pid = state
+        .current_workspace_id
+        .as_deref()
+        .filter(|prev| *prev != id)
+        .and_then(running_backend_pid);
+    let previous_running = previous_pid.is_some();
+
+    if previous_running {
+        match stop_previous {
+            Some(true) => {
+                let prev_id = state.current_workspace_id.clone().unwrap_or_default();
+                service_stop(app, &prev_id)?;
+            }
+            Some(false) => {
+                return Ok(SwitchWorkspaceResult {
+                    switched: false,
+                    previous_running,
+                    previous_pid,
+                });
+            }
+            None => {}
+        }
+    }
+
+    state.current_workspace_id = Some(id.clone());
+    write_state_file(&state)?;
+
+    let status = if running_backend_pid(&id).is_some() { "alive" } else { "dead" };
+    set_tray_tooltip(app, status);
+
+    Ok(SwitchWorkspaceResult {
+        switched: true,
+        previous_running,
+        previous_pid,
+    })
+}
+
+/// 按前端拖拽后的顺序重排工作区列表并持久化。
+/// ordered_ids 必须与现有工作区 id 集合完全一致（不多、不少、不重复）。
+#[tauri::command]
+fn reorder_workspaces(ordered_ids: Vec<String>) -> Result<(), String> {
+    let mut state = read_state_file();
+    let mut seen = std::collections::HashSet::new();
+    for id in &ordered_ids {
+        if !seen.insert(id.as_str()) {
+            return Err(format!("duplicated workspace id in order list: {id}"));
+        }
+        if !state.workspaces.iter().any(|w| &w.id == id) {
+            return Err(format!("unknown workspace id in order list: {id}"));
+        }
+    }
+    let missing: Vec<&str> = state
+        .workspaces
+        .iter()
+        .filter(|w| !seen.contains(w.id.as_str()))
+        .map(|w| w.id.as_str())
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!("order list is missing workspace ids: {}", missing.join(", ")));
+    }
+
+    let mut old = std::mem::take(&mut state.workspaces);
+    for id in &ordered_ids {
+        if let Some(pos) = old.iter().position(|w| &w.id == id) {
+            state.workspaces.push(old.remove(pos));
+        }
+    }
+    write_state_file(&state)
+}
+
+/// 记录工作区最近一次成功启动后端的时间（best-effort，失败不影响启动结果）
+fn touch_workspace_last_started(workspace_id: &str) {
+    let mut state = read_state_file();
+    if let Some(w) = state.workspaces.iter_mut().find(|w| w.id == workspace_id) {
+        w.last_started_at = Some(now_epoch_secs());
+        let _ = write_state_file(&state);
+    }
+}
+
+/// 看门狗检查间隔
+const WATCHDOG_INTERVAL_SECS: u64 = 5;
+/// 崩溃循环判定窗口：窗口内自动重启超过 WATCHDOG_LOOP_MAX_RESTARTS 次即放弃
+const WATCHDOG_LOOP_WINDOW_SECS: u64 = 120;
+const WATCHDOG_LOOP_MAX_RESTARTS: usize = 3;
+/// 指数退避基数：第 n 次重启前等待 BASE * 2^(n-1) 秒
+const WATCHDOG_BACKOFF_BASE_SECS: u64 = 5;
+/// 心跳持续健康超过该时长后重置退避状态
+const WATCHDOG_HEALTHY_RESET_SECS: u64 = 600;
+/// backend-crash-loop 事件附带的日志尾部大小
+const WATCHDOG_LOG_TAIL_BYTES: u64 = 4096;
+
+fn watchdog_register_start(workspace_id: &str, venv_dir: &str) {
+    let mut targets = WATCHDOG_TARGETS.lock().unwrap();
+    let t = targets
+        .entry(workspace_id.to_string())
+        .or_insert_with(|| WatchdogTarget {
+            venv_dir: String::new(),
+            user_stopped: false,
+            restart_count: 0,
+            restart_times: vec![],
+            crashed_at: None,
+            healthy_since: None,
+            last_exit_code: None,
+        });
+    t.venv_dir = venv_dir.to_string();
+    t.user_stopped = false;
+    t.crashed_at = None;
+    t.healthy_since = None;
+    t.last_exit_code = None;
+}
+
+fn watchdog_mark_user_stopped(workspace_id: &str) {
+    if let Some(t) = WATCHDOG_TARGETS.lock().unwrap().get_mut(workspace_id) {
+        t.user_stopped = true;
+    }
+}
+
+/// 状态查询清理已退出的子进程时记录退出码，供看门狗上报
+fn watchdog_record_exit(workspace_id: &str, code: Option<i32>) {
+    if let Some(t) = WATCHDOG_TARGETS.lock().unwrap().get_mut(workspace_id) {
+        t.last_exit_code = code;
+    }
+}
+
+/// 读取工作区服务日志末尾 max_bytes 字节
+fn read_service_log_tail(workspace_id: &str, max_bytes: u64) -> String {
+    let path = workspace_dir(workspace_id).join("logs").join("openakita-serve.log");
+    let Ok(mut f) = fs::File::open(&path) else {
+        return String::new();
+    };
+    let len = f.metadata().map(|m| m.len()).unwrap_or(0);
+    let _ = f.seek(SeekFrom::Start(len.saturating_sub(max_bytes)));
+    let mut buf = Vec::new();
+    let _ = f.read_to_end(&mut buf);
+    String::from_utf8_lossy(&buf).to_string()
+}
+
+/// 后台看门狗：auto_restart_backend 开启时，Tauri 启动的后端异常退出后按指数退避自动重启，
+/// 并发送 `backend-restarted` 事件（workspaceId / exitCode / restartCount）。
+/// - 用户主动停止的工作区（user_stopped）不会被拉起；启动/重启进行中（持有启动锁）时跳过
+/// - 2 分钟内重启超过 3 次视为崩溃循环：停止重试，发送 `backend-crash-loop` 事件（含日志尾部 4 KB），
+///   需用户手动重新启动
+/// - 心跳持续健康 10 分钟后重置退避状态
+fn spawn_backend_watchdog(app: tauri::AppHandle) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(Duration::from_secs(WATCHDOG_INTERVAL_SECS));
+        if !read_state_file().auto_restart_backend.unwrap_or(false) {
+            continue;
+        }
+        let candidates: Vec<(String, String)> = WATCHDOG_TARGETS
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, t)| !t.user_stopped)
+            .map(|(id, t)| (id.clone(), t.venv_dir.clone()))
+            .collect();
+
+        for (ws, venv_dir) in candidates {
+            if service_lock_file(&ws).exists() {
+                continue;
+            }
+            let now = now_epoch_secs();
+            if running_backend_pid(&ws).is_some() {
+                let healthy = is_heartbeat_stale(&ws, heartbeat_thresholds().kill_secs) != Some(true);
+                if let Some(t) = WATCHDOG_TARGETS.lock().unwrap().get_mut(&ws) {
+                    t.crashed_at = None;
+                    if !healthy {
+                        t.healthy_since = None;
+                    } else {
+                        let since = *t.healthy_since.get_or_insert(now);
+                        if now.saturating_sub(since) >= WATCHDOG_HEALTHY_RESET_SECS {
+                            t.restart_times.clear();
+                            t.restart_count = 0;
+                        }
+                    }
+                }
+                continue;
+            }
+
+            // 进程已退出：优先从仍在表中的子进程 handle 取退出码
+            let reaped_code = {
+                let mut guard = MANAGED_CHILDREN.lock().unwrap();
+                let code = guard
+                    .get_mut(&ws)
+                    .and_then(|mp| mp.child.try_wait().ok().flatten())
+                    .map(|st| st.code());
+                if code.is_some() {
+                    guard.remove(&ws);
+                }
+                code.flatten()
+            };
 
-    Ok(WorkspaceSummary {
-        id: id.clone(),
-        name,
-        path: dir.to_string_lossy().to_string(),
-        is_current: state.current_workspace_id.as_deref() == Some(&id),
-    })
-}
+            enum Action {
+                Wait,
+                CrashLoop(usize),
+                Restart(Option<i32>, u32),
+            }
+            let action = {
+                let mut targets = WATCHDOG_TARGETS.lock().unwrap();
+                let Some(t) = targets.get_mut(&ws) else {
+                    continue;
+                };
+                // 再次确认：检测期间用户可能刚点了停止
+                if t.user_stopped {
+                    continue;
+                }
+                if reaped_code.is_some() {
+                    t.last_exit_code = reaped_code;
+                }
+                t.healthy_since = None;
+                t.restart_times
+                    .retain(|ts| now.saturating_sub(*ts) < WATCHDOG_LOOP_WINDOW_SECS);
+                let crashed_at = *t.crashed_at.get_or_insert(now);
+                if t.restart_times.len() >= WATCHDOG_LOOP_MAX_RESTARTS {
+                    t.user_stopped = true;
+                    Action::CrashLoop(t.restart_times.len())
+                } else {
+                    let backoff = WATCHDOG_BACKOFF_BASE_SECS << t.restart_times.len();
+                    if now.saturating_sub(crashed_at) < backoff {
+                        Action::Wait
+                    } else {
+                        t.restart_times.push(now);
+                        t.restart_count += 1;
+                        t.crashed_at = None;
+                        Action::Restart(t.last_exit_code, t.restart_count)
+                    }
+                }
+            };
 
-#[tauri::command]
-fn set_current_workspace(id: String) -> Result<(), String> {
-    let mut state = read_state_file();
-    if !state.workspaces.iter().any(|w| w.id == id) {
-        return Err("workspace id not found".into());
-    }
-    state.current_workspace_id = Some(id);
-    write_state_file(&state)?;
-    Ok(())
+            match action {
+                Action::Wait => {}
+                Action::CrashLoop(restarts) => {
+                    eprintln!("Watchdog: backend for {ws} is crash-looping ({restarts} restarts), giving up");
+                    let _ = app.emit("backend-crash-loop", serde_json::json!({
+                        "workspaceId": ws,
+                        "restarts": restarts,
+                        "windowSecs": WATCHDOG_LOOP_WINDOW_SECS,
+                        "logTail": read_service_log_tail(&ws, WATCHDOG_LOG_TAIL_BYTES),
+                    }));
+                }
+                Action::Restart(exit_code, restart_count) => {
+                    match service_start(&app, &venv_dir, &ws, false, false) {
+                        Ok(_) => {
+                            let _ = app.emit("backend-restarted", serde_json::json!({
+                                "workspaceId": ws,
+                                "exitCode": exit_code,
+                                "restartCount": restart_count,
+                            }));
+                        }
+                        Err(e) => eprintln!("Watchdog: restart backend for {ws} failed: {e}"),
+                    }
+                }
+            }
+        }
+    });
 }
 
 /// 启动对账：清理残留锁文件和已死的 PID 文件
@@ -1868,6 +4792,17 @@ fn startup_reconcile() {
         }
     }
 
+    // 1.5 上次强制退出时遗留的进程：仍是 OpenAkita 进程则再尝试结束一次
+    if let Ok(content) = fs::read_to_string(residual_pids_file()) {
+        let pids: Vec<u32> = serde_json::from_str(&content).unwrap_or_default();
+        for pid in pids {
+            if is_openakita_process(pid) {
+                let _ = kill_pid(pid);
+            }
+        }
+        let _ = fs::remove_file(residual_pids_file());
+    }
+
     // 2. 扫描 PID 文件，清理已死进程的 stale 条目
     let entries = list_service_pids();
     for ent in &entries {
@@ -1876,10 +4811,10 @@ fn startup_reconcile() {
                 // 进程已死或 PID 被复用，清理 PID 文件和心跳文件
                 let _ = fs::remove_file(service_pid_file(&ent.workspace_id));
                 remove_heartbeat_file(&ent.workspace_id);
-            } else if let Some(true) = is_heartbeat_stale(&ent.workspace_id, 60) {
+            } else if let Some(true) = is_heartbeat_stale(&ent.workspace_id, heartbeat_thresholds().kill_secs) {
                 // PID 文件有效但心跳超时（进程可能卡死），强制清理
                 let port = read_workspace_api_port(&ent.workspace_id);
-                let _ = graceful_stop_pid(data.pid, port);
+                let _ = graceful_stop_pid(data.pid, port, graceful_stop_timeout_secs(), None);
                 let _ = fs::remove_file(service_pid_file(&ent.workspace_id));
                 remove_heartbeat_file(&ent.workspace_id);
             }
@@ -1887,9 +4822,35 @@ fn startup_reconcile() {
     }
 }
 
+/// 命令行模式（--headless / --status）：不显示窗口 / 托盘，输出 JSON 后退出
+fn is_cli_mode() -> bool {
+    std::env::args().any(|a| a == "--headless" || a == "--status")
+}
+
+/// 命令行模式下向 stdout 输出一行 JSON。
+/// Windows release 版是 GUI 子系统，没有控制台，先附加到父进程（终端）的控制台。
+fn print_cli_json(value: &serde_json::Value) {
+    #[cfg(windows)]
+    unsafe {
+        win::AttachConsole(win::ATTACH_PARENT_PROCESS);
+    }
+    let mut out = std::io::stdout();
+    let _ = writeln!(out, "{}", value);
+    let _ = out.flush();
+}
+
 fn main() {
-    tauri::Builder::default()
-        .plugin(tauri_plugin_single_instance::init(|app, _args, _cwd| {
+    // 自启参数在插件初始化时固定：--background + 用户配置的 autostart_args
+    let autostart = tauri_plugin_autostart::Builder::new().args(autostart_launch_args());
+    #[cfg(target_os = "macos")]
+    let autostart = autostart.macos_launcher(MacosLauncher::LaunchAgent);
+
+    let builder = tauri::Builder::default();
+    // 命令行模式不注册单实例插件：GUI 已在运行时也要能执行，而不是把参数转给已有实例后退出
+    let builder = if is_cli_mode() {
+        builder
+    } else {
+        builder.plugin(tauri_plugin_single_instance::init(|app, _args, _cwd| {
             // 第二个实例启动时，聚焦已有窗口并退出自身
             if let Some(w) = app.get_webview_window("main") {
                 let _ = w.show();
@@ -1897,10 +4858,9 @@ fn main() {
                 let _ = w.set_focus();
             }
         }))
-        .plugin(tauri_plugin_autostart::init(
-            MacosLauncher::LaunchAgent,
-            Some(vec!["--background"]),
-        ))
+    };
+    builder
+        .plugin(autostart.build())
         .plugin(tauri_plugin_updater::Builder::new().build())
         .plugin(tauri_plugin_process::init())
         .setup(|app| {
@@ -1921,7 +4881,7 @@ fn main() {
                     }
                 }
                 if clean_venv || clean_runtime {
-                    match cleanup_old_environment(clean_venv, clean_runtime) {
+                    match cleanup_old_environment(clean_venv, clean_runtime, Some(false)) {
                         Ok(msg) => eprintln!("Clean env: {}", msg),
                         Err(e) => eprintln!("Clean env failed: {}", e),
                     }
@@ -1929,16 +4889,77 @@ fn main() {
                 }
             }
 
+            // ── 状态查询：--status [workspace_id] 输出 ServiceStatus JSON，运行中退出码 0，否则 1 ──
+            // 放在启动对账之前，查询不应产生清理进程等副作用；未指定工作区时使用当前工作区。
+            if std::env::args().any(|a| a == "--status") {
+                if let Some(w) = app.get_webview_window("main") {
+                    let _ = w.hide();
+                }
+                let ws_id = cli_arg_value("--status").or_else(|| read_state_file().current_workspace_id);
+                let Some(ws_id) = ws_id else {
+                    print_cli_json(&serde_json::json!({ "error": "未指定工作区，且没有当前工作区" }));
+                    std::process::exit(2);
+                };
+                match openakita_service_status(ws_id.clone()) {
+                    Ok(status) => {
+                        print_cli_json(&serde_json::to_value(&status).unwrap_or_default());
+                        std::process::exit(if status.running { 0 } else { 1 });
+                    }
+                    Err(e) => {
+                        print_cli_json(&serde_json::json!({ "workspaceId": ws_id, "error": e }));
+                        std::process::exit(2);
+                    }
+                }
+            }
+
             // ── 启动对账：清理残留 .lock 和 stale PID 文件 ──
             startup_reconcile();
 
             // ── 配置文件版本迁移 ──
             let root = openakita_root_dir();
             let state_path = state_file_path();
-            if let Err(e) = migrations::run_migrations(&state_path, &root) {
+            if let Some(version) = migrations::newer_config_version(&state_path) {
+                // 降级安装：不迁移、不回写，通知前端提示用户升级
+                eprintln!("Config was created by a newer version (configVersion {version}), running read-only");
+                *NEWER_CONFIG_VERSION.lock().unwrap() = Some(version);
+                let _ = app.emit("config-version-newer", serde_json::json!({
+                    "configVersion": version,
+                    "supportedVersion": migrations::CURRENT_CONFIG_VERSION,
+                    "message": migrations::newer_version_message(version),
+                }));
+            } else if let Err(e) = migrations::run_migrations(&state_path, &root) {
                 eprintln!("Config migration error: {e}");
             }
 
+            // ── 版本变化后重新生成 CLI wrapper（venv 模式的绝对路径可能随安装路径失效） ──
+            if NEWER_CONFIG_VERSION.lock().unwrap().is_none() {
+                refresh_cli_after_upgrade();
+            }
+
+            // ── 无界面模式：--headless [workspace_id] 启动后端，输出 ServiceStatus JSON 后退出 ──
+            // 后端以分离进程运行，本进程退出后继续运行；未指定工作区时使用当前工作区。
+            if is_cli_mode() {
+                if let Some(w) = app.get_webview_window("main") {
+                    let _ = w.hide();
+                }
+                let ws_id = cli_arg_value("--headless").or_else(|| read_state_file().current_workspace_id);
+                let Some(ws_id) = ws_id else {
+                    print_cli_json(&serde_json::json!({ "error": "未指定工作区，且没有当前工作区" }));
+                    std::process::exit(2);
+                };
+                let venv_dir = openakita_root_dir().join("venv").to_string_lossy().to_string();
+                match service_start(app.handle(), &venv_dir, &ws_id, false, false) {
+                    Ok(status) => {
+                        print_cli_json(&serde_json::to_value(&status).unwrap_or_default());
+                        std::process::exit(0);
+                    }
+                    Err(e) => {
+                        print_cli_json(&serde_json::json!({ "workspaceId": ws_id, "error": e }));
+                        std::process::exit(1);
+                    }
+                }
+            }
+
             setup_tray(app)?;
 
             // ── 自启自修复：防止注册表条目意外丢失（上游 Issue #771） ──
@@ -1947,7 +4968,7 @@ fn main() {
             #[cfg(desktop)]
             {
                 let repair_state = read_state_file();
-                if repair_state.auto_start_backend.unwrap_or(false) {
+                if repair_state.settings.auto_start_backend.unwrap_or(false) {
                     let mgr = app.autolaunch();
                     match mgr.is_enabled() {
                         Ok(false) => {
@@ -1978,27 +4999,44 @@ fn main() {
             // ── 自动拉起后端（所有启动模式都生效） ──
             // 如果有已配置的工作区且后端未在运行，则自动启动后端。
             // 前端通过 is_backend_auto_starting 查询此状态，
-            // 在启动期间显示提示并禁用启动/重启按钮。
+            // 在启动期间显示提示并禁用启动/重启按钮；结束时收到 `auto-start-result` 事件。
+            // 命令行 --workspace <id>（开机自启参数）优先于 current_workspace_id
+            // 健康探测也放在线程里：端口被防火墙过滤时 GET 会一直挂到超时，不能拖慢首屏。
             let state = read_state_file();
-            if let Some(ref ws_id) = state.current_workspace_id {
-                let port = read_workspace_api_port(ws_id).unwrap_or(18900);
-                let already_running = reqwest::blocking::Client::builder()
-                    .timeout(std::time::Duration::from_secs(2))
-                    .build()
-                    .ok()
-                    .and_then(|c| c.get(format!("http://127.0.0.1:{}/api/health", port)).send().ok())
-                    .map(|r| r.status().is_success())
-                    .unwrap_or(false);
-                if !already_running {
-                    AUTO_START_IN_PROGRESS.store(true, Ordering::SeqCst);
-                    let venv_dir = openakita_root_dir().join("venv").to_string_lossy().to_string();
-                    let ws_clone = ws_id.clone();
-                    std::thread::spawn(move || {
-                        let _ = openakita_service_start(venv_dir, ws_clone);
-                        AUTO_START_IN_PROGRESS.store(false, Ordering::SeqCst);
-                    });
-                }
+            let auto_start_ws = cli_arg_value("--workspace")
+                .filter(|id| state.workspaces.iter().any(|w| &w.id == id))
+                .or_else(|| state.current_workspace_id.clone());
+            if let Some(ws_id) = auto_start_ws {
+                AUTO_START_IN_PROGRESS.store(true, Ordering::SeqCst);
+                let venv_dir = openakita_root_dir().join("venv").to_string_lossy().to_string();
+                let app_handle = app.handle().clone();
+                std::thread::spawn(move || {
+                    let port = read_workspace_api_port(&ws_id).unwrap_or_else(default_api_port);
+                    let payload = if backend_already_running(&ws_id, port) {
+                        serde_json::json!({ "workspaceId": ws_id, "outcome": "already-running" })
+                    } else {
+                        match service_start(&app_handle, &venv_dir, &ws_id, false, false) {
+                            Ok(status) => serde_json::json!({
+                                "workspaceId": ws_id,
+                                "outcome": "started",
+                                "status": status,
+                            }),
+                            Err(e) => serde_json::json!({
+                                "workspaceId": ws_id,
+                                "outcome": "failed",
+                                "error": e,
+                            }),
+                        }
+                    };
+                    AUTO_START_IN_PROGRESS.store(false, Ordering::SeqCst);
+                    let _ = app_handle.emit("auto-start-result", payload);
+                });
             }
+
+            // ── 崩溃看门狗（由 auto_restart_backend 开关控制） ──
+            spawn_backend_watchdog(app.handle().clone());
+            // ── 内存守护（由 max_backend_memory_mb 控制，默认关闭） ──
+            spawn_memory_guard(app.handle().clone());
             Ok(())
         })
         .on_window_event(|window, event| match event {
@@ -2013,11 +5051,26 @@ fn main() {
             get_platform_info,
             list_workspaces,
             create_workspace,
+            list_workspace_ports,
+            sync_workspace_templates,
+            list_personas,
+            set_active_persona,
+            workspace_scaffold_status,
             set_current_workspace,
+            reorder_workspaces,
             get_current_workspace_id,
             workspace_read_file,
+            workspace_read_files,
             workspace_write_file,
             workspace_update_env,
+            llm_endpoints_list,
+            llm_endpoints_upsert,
+            llm_endpoints_delete,
+            validate_llm_endpoints,
+            workspace_validate_llm_endpoints,
+            workspace_read_env,
+            workspace_read_env_masked,
+            workspace_reveal_env_value,
             detect_python,
             check_python_for_pip,
             install_embedded_python,
@@ -2027,22 +5080,66 @@ fn main() {
             remove_openakita_runtime,
             autostart_is_enabled,
             autostart_set_enabled,
+            get_autostart_args,
             openakita_service_status,
+            openakita_all_service_status,
+            adopt_external_service,
             openakita_service_start,
             openakita_service_stop,
+            list_running_services,
+            subscribe_heartbeat,
+            unsubscribe_heartbeat,
+            openakita_read_heartbeat,
+            openakita_service_restart,
+            openakita_service_resources,
+            openakita_http_health,
             openakita_service_log,
+            openakita_service_log_follow,
+            openakita_service_log_unfollow,
             openakita_check_pid_alive,
+            check_port_available_cmd,
+            find_free_port,
             set_tray_backend_status,
             is_backend_auto_starting,
             get_auto_start_backend,
             set_auto_start_backend,
             get_auto_update,
             set_auto_update,
+            check_for_update,
+            download_and_install_update,
+            prepare_for_update,
+            refresh_cli,
+            openakita_scan_orphans,
+            openakita_kill_processes,
+            get_default_api_port,
+            set_default_api_port,
+            get_pip_mirror,
+            set_pip_mirror,
+            get_playwright_download_mirror,
+            set_playwright_download_mirror,
+            get_auto_restart_backend,
+            get_kill_backend_with_app,
+            get_graceful_stop_timeout_secs,
+            get_heartbeat_thresholds,
+            get_max_backend_memory_mb,
+            list_state_backups,
+            get_config_version_warning,
+            restore_state_backup,
+            set_max_backend_memory_mb,
+            set_heartbeat_thresholds,
+            set_graceful_stop_timeout_secs,
+            set_kill_backend_with_app,
+            set_auto_restart_backend,
+            get_http_proxy,
+            set_http_proxy,
+            get_startup_failure_patterns,
+            set_startup_failure_patterns,
             openakita_list_skills,
             openakita_list_providers,
             openakita_list_models,
             openakita_version,
             openakita_health_check_endpoint,
+            openakita_preflight,
             openakita_health_check_im,
             openakita_ensure_channel_deps,
             openakita_install_skill,
@@ -2050,26 +5147,45 @@ fn main() {
             openakita_list_marketplace,
             openakita_get_skill_config,
             fetch_pypi_versions,
+            fetch_release_notes,
             http_get_json,
             http_proxy_request,
             read_file_base64,
             download_file,
             show_item_in_folder,
             open_file_with_default,
+            reveal_file,
+            open_path_in_explorer,
+            open_workspace_folder,
             open_external_url,
             openakita_list_processes,
             openakita_stop_all_processes,
+            openakita_kill_process,
+            force_quit,
+            find_port_owner,
             detect_modules,
             install_module,
+            cancel_module_install,
+            update_module,
+            check_module_updates,
+            probe_pip_mirrors,
+            module_install_plan,
             uninstall_module,
+            module_verify,
             is_first_run,
             check_environment,
             cleanup_old_environment,
             start_onboarding_log,
             append_onboarding_log,
             append_onboarding_log_lines,
+            read_onboarding_log,
+            onboarding_log_timestamp_format,
+            list_onboarding_logs,
             register_cli,
             unregister_cli,
+            cli_verify,
+            cli_scan_stale_path_entries,
+            cli_clean_stale_path,
             get_cli_status
         ])
         .run(tauri::generate_context!())
@@ -2085,24 +5201,76 @@ struct ServiceStatus {
     /// 后端心跳阶段："starting" | "initializing" | "running" | "restarting" | "stopping" | ""
     #[serde(default)]
     heartbeat_phase: String,
-    /// 心跳是否过期（超过 30 秒没更新）。None = 没有心跳文件（旧版后端）
+    /// 心跳是否过期（超过 heartbeat_stale_secs 秒没更新）。None = 没有心跳文件（旧版后端）
     #[serde(default)]
     heartbeat_stale: Option<bool>,
     /// 距上次心跳的秒数。None = 没有心跳文件
     #[serde(default)]
     heartbeat_age_secs: Option<f64>,
+    /// 进程已运行秒数。None = 未运行或无法读取
+    #[serde(default)]
+    uptime_secs: Option<u64>,
+    /// 常驻内存（MB）
+    #[serde(default)]
+    memory_mb: Option<f64>,
+    /// 自上次状态查询以来的 CPU 占用百分比（已按逻辑核数归一化）；首次查询为 None
+    #[serde(default)]
+    cpu_percent: Option<f64>,
+    /// 启动时要求等待就绪（wait_ready_secs > 0）才填充：HTTP API 是否已就绪
+    #[serde(default)]
+    ready: Option<bool>,
+    /// 工作区 .env 中的 API_PORT（未配置时为默认端口）；auto_port 换端口后即为新端口
+    #[serde(default)]
+    api_port: Option<u16>,
+    /// 后端版本（BACKEND_VERSION 缓存，服务启动时刷新）。未知时为 None
+    #[serde(default)]
+    backend_version: Option<String>,
+    /// 仅停止操作填充：优雅关闭超时后被强制结束的 PID（含子进程树）；优雅退出时为空
+    #[serde(default)]
+    killed_pids: Vec<u32>,
 }
 
-/// 构造 ServiceStatus，自动填充心跳信息
+/// 每个 PID 上一次状态查询时的 (采样时刻, 累计 CPU 秒)，用于在相邻两次查询间计算 CPU 占用
+static CPU_SAMPLES: Lazy<Mutex<std::collections::HashMap<u32, (std::time::Instant, f64)>>> =
+    Lazy::new(|| Mutex::new(std::collections::HashMap::new()));
+
+/// 读取进程运行时长 / 内存 / CPU（增量）。任一信息读取失败时对应字段为 None。
+fn sample_status_resources(pid: u32) -> (Option<u64>, Option<f64>, Option<f64>) {
+    let uptime_secs = get_process_create_time(pid).map(|t| now_epoch_secs().saturating_sub(t));
+    let Some((cpu_secs, rss_bytes, _)) = sample_process_resources(pid) else {
+        CPU_SAMPLES.lock().unwrap().remove(&pid);
+        return (uptime_secs, None, None);
+    };
+    let memory_mb = Some(rss_bytes as f64 / (1024.0 * 1024.0));
+    let now = std::time::Instant::now();
+    let prev = CPU_SAMPLES.lock().unwrap().insert(pid, (now, cpu_secs));
+    let cpu_percent = prev.and_then(|(t, prev_cpu)| {
+        let elapsed = now.duration_since(t).as_secs_f64();
+        if elapsed <= 0.0 {
+            return None;
+        }
+        let cores = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1) as f64;
+        Some(((cpu_secs - prev_cpu).max(0.0) / elapsed / cores * 100.0).min(100.0))
+    });
+    (uptime_secs, memory_mb, cpu_percent)
+}
+
+/// 构造 ServiceStatus，自动填充心跳信息和进程资源占用
 fn build_service_status(workspace_id: &str, running: bool, pid: Option<u32>, pid_file_str: String) -> ServiceStatus {
     let (heartbeat_phase, heartbeat_stale, heartbeat_age_secs) = if let Some(hb) = read_heartbeat_file(workspace_id) {
         let now = now_epoch_secs() as f64;
         let age = now - hb.timestamp;
-        let stale = age > 30.0; // 超过 30 秒无心跳视为过期
+        let stale = age > heartbeat_thresholds().stale_secs as f64;
         (hb.phase, Some(stale), Some(age))
     } else {
         (String::new(), None, None)
     };
+    let (uptime_secs, memory_mb, cpu_percent) = match pid {
+        Some(p) if running => sample_status_resources(p),
+        _ => (None, None, None),
+    };
     ServiceStatus {
         running,
         pid,
@@ -2110,7 +5278,178 @@ fn build_service_status(workspace_id: &str, running: bool, pid: Option<u32>, pid
         heartbeat_phase,
         heartbeat_stale,
         heartbeat_age_secs,
+        uptime_secs,
+        memory_mb,
+        cpu_percent,
+        ready: None,
+        api_port: Some(read_workspace_api_port(workspace_id).unwrap_or_else(default_api_port)),
+        backend_version: BACKEND_VERSION.lock().unwrap().clone(),
+        killed_pids: Vec::new(),
+    }
+}
+
+/// 后端版本缓存：来自打包后端的 _bundled_version.txt 或最近一次成功的 openakita_version，
+/// 仅在服务启动时刷新，状态轮询直接读取。
+static BACKEND_VERSION: Lazy<Mutex<Option<String>>> = Lazy::new(|| Mutex::new(None));
+
+/// 读取打包后端的 _bundled_version.txt（无需 Python）
+fn read_bundled_version() -> Option<String> {
+    let version_file = bundled_backend_dir()
+        .join("_internal")
+        .join("openakita")
+        .join("_bundled_version.txt");
+    let v = fs::read_to_string(version_file).ok()?;
+    let v = v.trim().to_string();
+    (!v.is_empty()).then_some(v)
+}
+
+/// 服务启动时刷新版本缓存：打包后端以 _bundled_version.txt 为准，venv 模式保留上次 openakita_version 的结果
+fn refresh_backend_version() {
+    if let Some(v) = read_bundled_version() {
+        *BACKEND_VERSION.lock().unwrap() = Some(v);
+    }
+}
+
+/// 各工作区心跳监视线程的停止标记
+static HEARTBEAT_WATCHERS: Lazy<Mutex<std::collections::HashMap<String, std::sync::Arc<AtomicBool>>>> =
+    Lazy::new(|| Mutex::new(std::collections::HashMap::new()));
+
+/// 心跳文件的变化由 notify 推送；没有事件时最多间隔该时长醒来一次，检查停止标记
+const HEARTBEAT_WATCH_IDLE_MS: u64 = 5000;
+/// 无法监听文件系统事件时（如网络盘）回退为轮询的间隔（只 stat，mtime 变化时才重新读取解析）
+const HEARTBEAT_WATCH_INTERVAL_MS: u64 = 1000;
+/// 轮询回退且 HTTP 尚未就绪（启动阶段）时的间隔：starting → initializing → running 切换很快，放慢会漏掉中间阶段
+const HEARTBEAT_WATCH_STARTUP_INTERVAL_MS: u64 = 200;
+
+/// 监听心跳文件所在的 data 目录（后端以“写临时文件 + 重命名”方式更新心跳，且启动时文件可能尚未创建），
+/// 心跳文件有变化时向 `tx` 发送通知。无法建立监听时返回 None。
+fn watch_heartbeat_file(path: &Path, tx: std::sync::mpsc::Sender<()>) -> Option<notify::RecommendedWatcher> {
+    use notify::Watcher;
+    let dir = path.parent()?;
+    fs::create_dir_all(dir).ok()?;
+    let name = path.file_name()?.to_os_string();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        if let Ok(ev) = res {
+            if ev.paths.iter().any(|p| p.file_name() == Some(name.as_os_str())) {
+                let _ = tx.send(());
+            }
+        }
+    })
+    .ok()?;
+    watcher.watch(dir, notify::RecursiveMode::NonRecursive).ok()?;
+    Some(watcher)
+}
+
+/// 启动（若尚未启动）工作区的心跳监视线程：维护 HEARTBEAT_CACHE，并在 phase / http_ready /
+/// 是否过期 发生变化时发送 `backend-heartbeat` 事件，前端无需频繁轮询 openakita_service_status。
+/// 另外仅在 phase / http_ready 变化时发送 `backend-phase` 事件，供启动进度展示使用。
+/// 心跳文件变化通过 notify 监听；监听失败时回退为按 HEARTBEAT_WATCH_INTERVAL_MS 轮询。
+fn start_heartbeat_watcher(app: &tauri::AppHandle, workspace_id: &str) {
+    let stop = {
+        let mut watchers = HEARTBEAT_WATCHERS.lock().unwrap();
+        if watchers.contains_key(workspace_id) {
+            return;
+        }
+        let stop = std::sync::Arc::new(AtomicBool::new(false));
+        watchers.insert(workspace_id.to_string(), stop.clone());
+        stop
+    };
+    let app = app.clone();
+    let ws = workspace_id.to_string();
+    std::thread::spawn(move || {
+        let stale_secs = heartbeat_thresholds().stale_secs as f64;
+        let (tx, rx) = std::sync::mpsc::channel::<()>();
+        let watcher = watch_heartbeat_file(&service_heartbeat_file(&ws), tx);
+        let mut last: Option<(String, bool, Option<bool>)> = None;
+        let mut last_phase: Option<(String, bool)> = None;
+        while !stop.load(Ordering::SeqCst) {
+            let hb = refresh_heartbeat_cache(&ws);
+            let age = hb
+                .as_ref()
+                .map(|h| (now_epoch_secs() as f64 - h.timestamp).max(0.0));
+            let current = (
+                hb.as_ref().map(|h| h.phase.clone()).unwrap_or_default(),
+                hb.as_ref().map(|h| h.http_ready).unwrap_or(false),
+                age.map(|a| a > stale_secs),
+            );
+            if last.as_ref() != Some(&current) {
+                let _ = app.emit("backend-heartbeat", serde_json::json!({
+                    "workspaceId": ws,
+                    "phase": current.0,
+                    "httpReady": current.1,
+                    "stale": current.2,
+                    "ageSecs": age,
+                }));
+                last = Some(current);
+            }
+            let phase = (
+                hb.as_ref().map(|h| h.phase.clone()).unwrap_or_default(),
+                hb.as_ref().map(|h| h.http_ready).unwrap_or(false),
+            );
+            if last_phase.as_ref() != Some(&phase) {
+                let _ = app.emit("backend-phase", serde_json::json!({
+                    "workspaceId": ws,
+                    "phase": phase.0,
+                    "httpReady": phase.1,
+                }));
+                last_phase = Some(phase);
+            }
+            if watcher.is_some() {
+                // 心跳未过期时，到过期时刻醒来一次以发出 stale 变化
+                let until_stale = age
+                    .filter(|a| *a <= stale_secs)
+                    .map(|a| ((stale_secs - a) * 1000.0) as u64 + 100);
+                let wait = until_stale.map_or(HEARTBEAT_WATCH_IDLE_MS, |ms| ms.min(HEARTBEAT_WATCH_IDLE_MS));
+                let _ = rx.recv_timeout(Duration::from_millis(wait));
+                // 一次重命名会产生多个事件，合并处理
+                while rx.try_recv().is_ok() {}
+            } else {
+                let interval = if last_phase.as_ref().is_some_and(|p| p.1) {
+                    HEARTBEAT_WATCH_INTERVAL_MS
+                } else {
+                    HEARTBEAT_WATCH_STARTUP_INTERVAL_MS
+                };
+                std::thread::sleep(Duration::from_millis(interval));
+            }
+        }
+    });
+}
+
+fn stop_heartbeat_watcher(workspace_id: &str) {
+    if let Some(stop) = HEARTBEAT_WATCHERS.lock().unwrap().remove(workspace_id) {
+        stop.store(true, Ordering::SeqCst);
     }
+    invalidate_heartbeat_cache(workspace_id);
+}
+
+#[tauri::command]
+fn subscribe_heartbeat(app: tauri::AppHandle, workspace_id: String) -> Result<(), String> {
+    start_heartbeat_watcher(&app, &workspace_id);
+    Ok(())
+}
+
+#[tauri::command]
+fn unsubscribe_heartbeat(workspace_id: String) -> Result<(), String> {
+    stop_heartbeat_watcher(&workspace_id);
+    Ok(())
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct HeartbeatReport {
+    /// 心跳文件原始内容（字段名与 Python 后端写入的一致）
+    heartbeat: HeartbeatData,
+    /// 距心跳 timestamp 的秒数（与内部 stale 判定使用同一算法）
+    age_secs: f64,
+}
+
+/// 诊断用：返回 Rust 侧判定所依据的心跳数据（read_heartbeat_file）及其年龄。没有心跳文件时返回 None。
+#[tauri::command]
+fn openakita_read_heartbeat(workspace_id: String) -> Result<Option<HeartbeatReport>, String> {
+    Ok(read_heartbeat_file(&workspace_id).map(|hb| HeartbeatReport {
+        age_secs: (now_epoch_secs() as f64 - hb.timestamp).max(0.0),
+        heartbeat: hb,
+    }))
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -2119,6 +5458,233 @@ struct ServiceLogChunk {
     path: String,
     content: String,
     truncated: bool,
+    /// 下次增量读取应传入的 from_offset
+    #[serde(default)]
+    next_offset: u64,
+    /// 读取时的日志文件长度
+    #[serde(default)]
+    file_len: u64,
+    /// 文件比 from_offset 短（被截断/轮转），本次从新文件开头读取
+    #[serde(default)]
+    reset: bool,
+    /// 按 level / query 过滤时的匹配行（未过滤时为空）
+    #[serde(default)]
+    matches: Vec<LogMatch>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct LogMatch {
+    /// 匹配行在日志文件中的起始字节偏移
+    offset: u64,
+    line: String,
+    before: Vec<String>,
+    after: Vec<String>,
+}
+
+/// 按级别/关键字过滤时最多扫描日志末尾的字节数
+const LOG_SEARCH_MAX_BYTES: u64 = 8 * 1024 * 1024;
+/// 单次过滤最多返回的匹配数
+const LOG_SEARCH_MAX_MATCHES: usize = 1000;
+
+/// 识别一行日志的级别：3 = ERROR/CRITICAL/Traceback，2 = WARNING，1 = INFO，0 = DEBUG
+fn log_line_level(line: &str) -> Option<u8> {
+    if line.contains("ERROR") || line.contains("CRITICAL") || line.starts_with("Traceback") {
+        Some(3)
+    } else if line.contains("WARNING") || line.contains("WARN") {
+        Some(2)
+    } else if line.contains("INFO") {
+        Some(1)
+    } else if line.contains("DEBUG") {
+        Some(0)
+    } else {
+        None
+    }
+}
+
+/// 是否为上一条日志的续行：缩进行（traceback 的 `  File "…"` / 源码行）、空行、
+/// 异常摘要行（`ValueError: …`）以及链式异常之间的说明行
+fn is_log_continuation(line: &str) -> bool {
+    if line.trim().is_empty() || line.starts_with([' ', '\t']) {
+        return true;
+    }
+    if line.starts_with("During handling of the above exception")
+        || line.starts_with("The above exception was the direct cause")
+    {
+        return true;
+    }
+    let head = line.split(':').next().unwrap_or("");
+    let name = head.rsplit('.').next().unwrap_or("");
+    !head.is_empty()
+        && head.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+        && ["Error", "Exception", "Exit", "Interrupt"].iter().any(|s| name.ends_with(s))
+}
+
+/// 逐行计算日志级别：没有级别标记的续行沿用上一行的级别，
+/// 这样按 level=error 过滤时 traceback 的各行会和触发它的 ERROR 行一起保留
+fn effective_log_levels<'a>(lines: impl IntoIterator<Item = &'a str>) -> Vec<Option<u8>> {
+    let mut prev = None;
+    lines
+        .into_iter()
+        .map(|line| {
+            let level = log_line_level(line).or_else(|| prev.filter(|_| is_log_continuation(line)));
+            prev = level;
+            level
+        })
+        .collect()
+}
+
+/// 在日志文件末尾 LOG_SEARCH_MAX_BYTES 内逐行过滤：级别（续行沿用上一行，见 effective_log_levels）
+/// 不低于 `level` 且包含 `query`（不区分大小写），
+/// 每个匹配附带前后 `context` 行。
+fn search_log_file(
+    log_path: &Path,
+    level: Option<&str>,
+    query: Option<&str>,
+    context: usize,
+    clean: bool,
+) -> Result<ServiceLogChunk, String> {
+    let min_level = match level.map(|l| l.to_lowercase()) {
+        None => None,
+        Some(l) if l == "error" => Some(3),
+        Some(l) if l == "warning" || l == "warn" => Some(2),
+        Some(l) if l == "info" => Some(1),
+        Some(l) => return Err(format!("unknown log level: {l}")),
+    };
+    let query = query.map(|q| q.to_lowercase()).filter(|q| !q.is_empty());
+
+    let mut f = std::fs::File::open(log_path).map_err(|e| format!("open log failed: {e}"))?;
+    let len = f.metadata().map_err(|e| format!("stat log failed: {e}"))?.len();
+    let start = len.saturating_sub(LOG_SEARCH_MAX_BYTES);
+    f.seek(SeekFrom::Start(start))
+        .map_err(|e| format!("seek log failed: {e}"))?;
+    let mut buf = Vec::new();
+    f.read_to_end(&mut buf).map_err(|e| format!("read log failed: {e}"))?;
+
+    // 拆行并记录每行的文件偏移；从文件中间开始时丢掉第一行残片
+    let mut lines: Vec<(u64, String)> = vec![];
+    let mut pos = 0usize;
+    for raw in buf.split(|b| *b == b'\n') {
+        let offset = start + pos as u64;
+        pos += raw.len() + 1;
+        if start > 0 && offset == start {
+            continue;
+        }
+        let text = String::from_utf8_lossy(raw).to_string();
+        let text = if clean { clean_log_text(&text) } else { text.trim_end_matches('\r').to_string() };
+        lines.push((offset, text));
+    }
+    if lines.last().map(|(_, l)| l.is_empty()).unwrap_or(false) {
+        lines.pop();
+    }
+
+    let levels = effective_log_levels(lines.iter().map(|(_, l)| l.as_str()));
+    let mut matches = vec![];
+    for (i, (offset, line)) in lines.iter().enumerate() {
+        if let Some(min) = min_level {
+            if levels[i].map(|lv| lv < min).unwrap_or(true) {
+                continue;
+            }
+        }
+        if let Some(ref q) = query {
+            if !line.to_lowercase().contains(q.as_str()) {
+                continue;
+            }
+        }
+        matches.push(LogMatch {
+            offset: *offset,
+            line: line.clone(),
+            before: lines[i.saturating_sub(context)..i].iter().map(|(_, l)| l.clone()).collect(),
+            after: lines[i + 1..(i + 1 + context).min(lines.len())].iter().map(|(_, l)| l.clone()).collect(),
+        });
+        if matches.len() >= LOG_SEARCH_MAX_MATCHES {
+            break;
+        }
+    }
+
+    Ok(ServiceLogChunk {
+        path: log_path.to_string_lossy().to_string(),
+        content: matches.iter().map(|m| m.line.as_str()).collect::<Vec<_>>().join("\n"),
+        truncated: start > 0 || matches.len() >= LOG_SEARCH_MAX_MATCHES,
+        next_offset: len,
+        file_len: len,
+        reset: false,
+        matches,
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct WorkspaceServiceStatus {
+    workspace_id: String,
+    status: ServiceStatus,
+}
+
+/// 一次返回所有工作区的服务状态（工作区列表 + 有 PID 文件但已不在列表中的工作区）。
+/// MANAGED_CHILDREN 只加锁一次、run_dir 只扫描一次，判定规则与 openakita_service_status 相同。
+#[tauri::command]
+fn openakita_all_service_status() -> Vec<WorkspaceServiceStatus> {
+    // workspace_id -> Some(pid) 运行中 / None 已确认停止
+    let mut decided: std::collections::HashMap<String, Option<u32>> = std::collections::HashMap::new();
+
+    // ── 1. MANAGED_CHILDREN（精确 try_wait）──
+    {
+        let mut guard = MANAGED_CHILDREN.lock().unwrap();
+        let mut exited_ids = Vec::new();
+        for (ws, mp) in guard.iter_mut() {
+            match mp.child.try_wait() {
+                Ok(None) => {
+                    decided.insert(ws.clone(), Some(mp.pid));
+                }
+                exited => {
+                    watchdog_record_exit(ws, exited.ok().flatten().and_then(|st| st.code()));
+                    exited_ids.push(ws.clone());
+                }
+            }
+        }
+        for ws in exited_ids {
+            guard.remove(&ws);
+            let _ = fs::remove_file(service_pid_file(&ws));
+            remove_heartbeat_file(&ws);
+            decided.insert(ws, None);
+        }
+    }
+
+    // ── 2. PID 文件 ──
+    let entries = list_service_pids();
+    for ent in &entries {
+        if decided.contains_key(&ent.workspace_id) {
+            continue;
+        }
+        let data = PidFileData {
+            pid: ent.pid,
+            started_by: ent.started_by.clone(),
+            started_at: ent.started_at,
+            control_token: None,
+        };
+        if is_pid_file_valid(&data) {
+            decided.insert(ent.workspace_id.clone(), Some(ent.pid));
+        } else {
+            let _ = fs::remove_file(&ent.pid_file);
+            remove_heartbeat_file(&ent.workspace_id);
+            decided.insert(ent.workspace_id.clone(), None);
+        }
+    }
+
+    let mut ids: Vec<String> = read_state_file().workspaces.into_iter().map(|w| w.id).collect();
+    for ws in decided.keys() {
+        if !ids.contains(ws) {
+            ids.push(ws.clone());
+        }
+    }
+    ids.into_iter()
+        .map(|ws| {
+            let pid = decided.get(&ws).copied().flatten();
+            let pf = service_pid_file(&ws).to_string_lossy().to_string();
+            let status = build_service_status(&ws, pid.is_some(), pid, pf);
+            WorkspaceServiceStatus { workspace_id: ws, status }
+        })
+        .collect()
 }
 
 #[tauri::command]
@@ -2126,22 +5692,21 @@ fn openakita_service_status(workspace_id: String) -> Result<ServiceStatus, Strin
     let pid_file = service_pid_file(&workspace_id);
     let pf = pid_file.to_string_lossy().to_string();
 
-    // ── 1. 优先用 MANAGED_CHILD（精确 try_wait）──
+    // ── 1. 优先用 MANAGED_CHILDREN（精确 try_wait）──
     {
-        let mut guard = MANAGED_CHILD.lock().unwrap();
-        if let Some(ref mut mp) = *guard {
-            if mp.workspace_id == workspace_id {
-                match mp.child.try_wait() {
-                    Ok(None) => {
-                        return Ok(build_service_status(&workspace_id, true, Some(mp.pid), pf));
-                    }
-                    _ => {
-                        // 进程已退出，清理 handle、PID 文件和心跳文件
-                        *guard = None;
-                        let _ = fs::remove_file(&pid_file);
-                        remove_heartbeat_file(&workspace_id);
-                        return Ok(build_service_status(&workspace_id, false, None, pf));
-                    }
+        let mut guard = MANAGED_CHILDREN.lock().unwrap();
+        if let Some(mp) = guard.get_mut(&workspace_id) {
+            match mp.child.try_wait() {
+                Ok(None) => {
+                    return Ok(build_service_status(&workspace_id, true, Some(mp.pid), pf));
+                }
+                exited => {
+                    // 进程已退出，记录退出码并清理 handle、PID 文件和心跳文件
+                    watchdog_record_exit(&workspace_id, exited.ok().flatten().and_then(|st| st.code()));
+                    guard.remove(&workspace_id);
+                    let _ = fs::remove_file(&pid_file);
+                    remove_heartbeat_file(&workspace_id);
+                    return Ok(build_service_status(&workspace_id, false, None, pf));
                 }
             }
         }
@@ -2150,7 +5715,7 @@ fn openakita_service_status(workspace_id: String) -> Result<ServiceStatus, Strin
     // ── 2. 回退到 PID 文件 ──
     if let Some(data) = read_pid_file(&workspace_id) {
         if is_pid_file_valid(&data) {
-            // PID 文件有效，但如果心跳超过 60 秒没更新，进程可能卡死
+            // PID 文件有效，但如果心跳超过 heartbeat_kill_secs 没更新，进程可能卡死
             // 此时仍报告 running（让前端根据心跳状态决定是否提示用户）
             return Ok(build_service_status(&workspace_id, true, Some(data.pid), pf));
         } else {
@@ -2162,25 +5727,46 @@ fn openakita_service_status(workspace_id: String) -> Result<ServiceStatus, Strin
     Ok(build_service_status(&workspace_id, false, None, pf))
 }
 
+/// 列出所有正在运行的后端（Tauri 管理的子进程 + 有 PID 文件的工作区）。
+/// 逐个走 openakita_service_status，顺带清理已失效的 PID 文件。
+#[tauri::command]
+fn list_running_services() -> Result<Vec<ServiceStatus>, String> {
+    let mut ids: Vec<String> = MANAGED_CHILDREN.lock().unwrap().keys().cloned().collect();
+    for ent in list_service_pids() {
+        if !ids.contains(&ent.workspace_id) {
+            ids.push(ent.workspace_id);
+        }
+    }
+    ids.sort();
+    let mut out = vec![];
+    for id in ids {
+        let status = openakita_service_status(id)?;
+        if status.running {
+            out.push(status);
+        }
+    }
+    Ok(out)
+}
+
 /// 检查进程是否仍在运行（供前端心跳二次确认用）。
 /// 除了检查 PID 存活，还验证进程身份和心跳文件。
-/// 如果心跳超过 60 秒没更新且 HTTP 不可达，自动清理进程和 PID 文件。
+/// 如果心跳超过 heartbeat_kill_secs（默认 60 秒）没更新且 HTTP 不可达，自动清理进程和 PID 文件。
 #[tauri::command]
 fn openakita_check_pid_alive(workspace_id: String) -> Result<bool, String> {
-    // 优先 MANAGED_CHILD（由 Tauri 直接管理的子进程，不需要额外校验身份）
+    // 优先 MANAGED_CHILDREN（由 Tauri 直接管理的子进程，不需要额外校验身份）
     {
-        let mut guard = MANAGED_CHILD.lock().unwrap();
-        if let Some(ref mut mp) = *guard {
-            if mp.workspace_id == workspace_id {
-                let alive = mp.child.try_wait().ok().flatten().is_none();
-                if !alive {
-                    // 进程已退出，清理
-                    *guard = None;
-                    let _ = fs::remove_file(service_pid_file(&workspace_id));
-                    remove_heartbeat_file(&workspace_id);
-                }
-                return Ok(alive);
+        let mut guard = MANAGED_CHILDREN.lock().unwrap();
+        if let Some(mp) = guard.get_mut(&workspace_id) {
+            let exited = mp.child.try_wait().ok().flatten();
+            let alive = exited.is_none();
+            if !alive {
+                // 进程已退出，清理
+                watchdog_record_exit(&workspace_id, exited.and_then(|st| st.code()));
+                guard.remove(&workspace_id);
+                let _ = fs::remove_file(service_pid_file(&workspace_id));
+                remove_heartbeat_file(&workspace_id);
             }
+            return Ok(alive);
         }
     }
     // 回退到 PID 文件：检查 PID 存活 + 验证进程身份
@@ -2198,13 +5784,13 @@ fn openakita_check_pid_alive(workspace_id: String) -> Result<bool, String> {
             remove_heartbeat_file(&workspace_id);
             return Ok(false);
         }
-        // 进程身份已确认，但检查心跳是否严重过期（> 60 秒）
+        // 进程身份已确认，但检查心跳是否严重过期（> heartbeat_kill_secs）
         // 心跳过期意味着进程虽然存活但可能已经卡死
-        if let Some(true) = is_heartbeat_stale(&workspace_id, 60) {
+        if let Some(true) = is_heartbeat_stale(&workspace_id, heartbeat_thresholds().kill_secs) {
             // 心跳严重过期，进程很可能已卡死。
             // 主动尝试清理：先 kill 进程，再清理 PID 和心跳文件。
             let port = read_workspace_api_port(&workspace_id);
-            let _ = graceful_stop_pid(data.pid, port);
+            let _ = graceful_stop_pid(data.pid, port, graceful_stop_timeout_secs(), None);
             let _ = fs::remove_file(service_pid_file(&workspace_id));
             remove_heartbeat_file(&workspace_id);
             return Ok(false);
@@ -2225,6 +5811,17 @@ fn apply_no_window(cmd: &mut Command) {
 #[cfg(not(windows))]
 fn apply_no_window(_cmd: &mut Command) {}
 
+/// 将 Setup Center 中配置的 http_proxy 传给子进程（pip / 后端内 playwright 等下载），
+/// 未配置时保持子进程继承的环境变量不变。
+fn apply_proxy_env(cmd: &mut Command) {
+    if let Some(proxy) = configured_http_proxy() {
+        for key in ["HTTPS_PROXY", "HTTP_PROXY", "https_proxy", "http_proxy"] {
+            cmd.env(key, &proxy);
+        }
+        cmd.env("NO_PROXY", "localhost,127.0.0.1,::1");
+    }
+}
+
 async fn spawn_blocking_result<R: Send + 'static>(
     f: impl FnOnce() -> Result<R, String> + Send + 'static,
 ) -> Result<R, String> {
@@ -2233,28 +5830,354 @@ async fn spawn_blocking_result<R: Send + 'static>(
         .map_err(|e| format!("后台任务失败（join error）: {e}"))?
 }
 
+/// .env 中的一条逻辑记录（多行引号值会合并为一条）
+enum EnvLine {
+    /// 注释、空行或无法解析的行，写回时原样保留
+    Raw(String),
+    /// 键值对。value 为去引号/反转义后的值，raw 为原始文本（可能跨多行），
+    /// comment 为值之后的行内注释（含前导空白，如 `  # keep in sync`）
+    Pair {
+        key: String,
+        value: String,
+        raw: String,
+        comment: Option<String>,
+    },
+}
+
+/// 解析 .env 的值部分（`=` 之后、已去掉前导空白）。
+/// - 双引号：支持 \n \t \r \\ \" 转义，可跨多行
+/// - 单引号：仅 \\ 与 \' 转义，可跨多行
+/// - 无引号：空白后的 `#` 视为行内注释
+///
+/// 返回 (值, 额外消耗的后续行数, 行内注释)。引号未闭合时退回按无引号处理。
+fn parse_env_value(first: &str, following: &[&str]) -> (String, usize, Option<String>) {
+    let quote = match first.chars().next() {
+        Some(q @ ('"' | '\'')) => q,
+        _ => {
+            let (value, comment) = parse_env_value_unquoted(first);
+            return (value, 0, comment);
+        }
+    };
+
+    let mut value = String::new();
+    let mut text = &first[1..];
+    let mut consumed = 0usize;
+    loop {
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            if c == quote {
+                let rest = chars.as_str();
+                let comment = rest
+                    .trim_start()
+                    .starts_with('#')
+                    .then(|| rest.trim_end().to_string());
+                return (value, consumed, comment);
+            }
+            if c != '\\' {
+                value.push(c);
+                continue;
+            }
+            match (quote, chars.next()) {
+                ('"', Some('n')) => value.push('\n'),
+                ('"', Some('t')) => value.push('\t'),
+                ('"', Some('r')) => value.push('\r'),
+                (_, Some(e @ ('\\' | '"' | '\''))) if e == '\\' || e == quote => value.push(e),
+                (_, Some(other)) => {
+                    value.push('\\');
+                    value.push(other);
+                }
+                (_, None) => value.push('\\'),
+            }
+        }
+        // 引号未闭合：值延续到下一行
+        let Some(next) = following.get(consumed) else {
+            let (value, comment) = parse_env_value_unquoted(first);
+            return (value, 0, comment);
+        };
+        value.push('\n');
+        text = next;
+        consumed += 1;
+    }
+}
+
+/// 无引号值：空白后的 `#` 起为行内注释。返回 (值, 行内注释)
+fn parse_env_value_unquoted(text: &str) -> (String, Option<String>) {
+    let mut prev_ws = false;
+    for (i, c) in text.char_indices() {
+        if c == '#' && prev_ws {
+            let value = text[..i].trim_end();
+            return (value.to_string(), Some(text[value.len()..].trim_end().to_string()));
+        }
+        prev_ws = c.is_whitespace();
+    }
+    (text.trim_end().to_string(), None)
+}
+
+/// 将 .env 内容拆分为逻辑记录，供读取与更新共用
+fn parse_env_lines(content: &str) -> Vec<EnvLine> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        let t = line.trim();
+        let Some((k, rest)) = t.split_once('=').filter(|_| !t.starts_with('#')) else {
+            out.push(EnvLine::Raw(line.to_string()));
+            i += 1;
+            continue;
+        };
+        let key = k.trim();
+        if key.is_empty() {
+            out.push(EnvLine::Raw(line.to_string()));
+            i += 1;
+            continue;
+        }
+        let value_text = rest.trim_start();
+        let (value, extra, comment) = if value_text.starts_with('#') && value_text.len() < rest.len() {
+            // `KEY= # todo`：空白之后的 # 是行内注释，值为空
+            (String::new(), 0, Some(rest.trim_end().to_string()))
+        } else {
+            parse_env_value(value_text, &lines[i + 1..])
+        };
+        out.push(EnvLine::Pair {
+            key: key.to_string(),
+            value,
+            raw: lines[i..=i + extra].join("\n"),
+            comment,
+        });
+        i += 1 + extra;
+    }
+    out
+}
+
+/// 写入 .env 时格式化值：含空白、`#` 或引号的值用双引号包裹并转义，其余原样写入
+fn format_env_value(value: &str) -> String {
+    let needs_quotes = value
+        .chars()
+        .any(|c| c.is_whitespace() || matches!(c, '#' | '"' | '\''));
+    if !needs_quotes {
+        return value.to_string();
+    }
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r");
+    format!("\"{escaped}\"")
+}
+
 fn read_env_kv(path: &Path) -> Vec<(String, String)> {
     let Ok(content) = fs::read_to_string(path) else {
         return vec![];
     };
-    let mut out = vec![];
-    for line in content.lines() {
-        let t = line.trim();
-        if t.is_empty() || t.starts_with('#') || !t.contains('=') {
-            continue;
-        }
-        let (k, v) = t.split_once('=').unwrap_or((t, ""));
-        let key = k.trim();
-        if key.is_empty() {
-            continue;
+    parse_env_lines(&content)
+        .into_iter()
+        .filter_map(|l| match l {
+            EnvLine::Pair { key, value, .. } => Some((key, value)),
+            EnvLine::Raw(_) => None,
+        })
+        .collect()
+}
+
+/// 后端启动后立即退出时返回给前端的结构化错误（序列化为 JSON 放在 Err 字符串中）。
+/// `class`: "config-invalid" | "module-missing" | "port-in-use" | "encoding" | "unknown"
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct StartupError {
+    class: String,
+    hint: String,
+    log_tail: String,
+    log_path: String,
+}
+
+/// 缺失的 Python 包 → 提供它的可选模块 id
+const MODULE_IMPORT_OWNERS: &[(&str, &str)] = &[
+    ("sentence_transformers", "vector-memory"),
+    ("chromadb", "vector-memory"),
+    ("torch", "vector-memory"),
+    ("whisper", "whisper"),
+    ("static_ffmpeg", "whisper"),
+    ("zmq", "orchestration"),
+];
+
+/// 自定义启动失败识别规则：日志尾部包含 `contains` 中任一子串（不区分大小写）即归为 `class`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct StartupFailurePattern {
+    class: String,
+    contains: Vec<String>,
+    hint: String,
+}
+
+/// 根据日志尾部识别常见的启动失败原因，返回 (class, 给用户看的提示)。
+/// 先按顺序匹配 `custom`（state.json 的 startupFailurePatterns），再匹配内置规则：
+/// 端口占用、缺失模块、配置校验、编码问题。
+fn classify_startup_failure(log_tail: &str, custom: &[StartupFailurePattern]) -> (String, String) {
+    let lower = log_tail.to_lowercase();
+    for p in custom {
+        if p.contains
+            .iter()
+            .any(|needle| !needle.is_empty() && lower.contains(&needle.to_lowercase()))
+        {
+            return (p.class.clone(), p.hint.clone());
         }
-        out.push((key.to_string(), v.to_string()));
     }
-    out
+    let (class, hint) = classify_builtin_startup_failure(log_tail, &lower);
+    (class.to_string(), hint)
 }
 
+/// 内置识别规则；`lower` 为 `log_tail` 的小写形式
+fn classify_builtin_startup_failure(log_tail: &str, lower: &str) -> (&'static str, String) {
+    if lower.contains("address already in use")
+        || lower.contains("errno 98")
+        || lower.contains("winerror 10048")
+        || lower.contains("only one usage of each socket address")
+    {
+        return ("port-in-use", "API 端口已被其他程序占用，请关闭占用端口的程序或修改工作区 API_PORT".into());
+    }
+    if let Some(pos) = log_tail.rfind("No module named '") {
+        let rest = &log_tail[pos + "No module named '".len()..];
+        let name = rest.split('\'').next().unwrap_or("").to_string();
+        let top = name.split('.').next().unwrap_or("");
+        let hint = match MODULE_IMPORT_OWNERS.iter().find(|(pkg, _)| *pkg == top) {
+            Some((_, module_id)) => {
+                let module_name = module_definitions()
+                    .into_iter()
+                    .find(|(id, ..)| id == module_id)
+                    .map(|(_, n, ..)| n)
+                    .unwrap_or(module_id);
+                format!("缺少 Python 模块 '{name}'，请在「模块管理」中安装「{module_name}」")
+            }
+            None => format!("缺少 Python 模块 '{name}'，安装可能不完整，请尝试重新安装 OpenAkita"),
+        };
+        return ("module-missing", hint);
+    }
+    if log_tail.contains("ValidationError") && lower.contains("pydantic") {
+        return ("config-invalid", "配置校验失败，请检查工作区 .env 与 llm_endpoints.json 中的字段".into());
+    }
+    if log_tail.contains("UnicodeEncodeError") || log_tail.contains("UnicodeDecodeError") {
+        return ("encoding", "字符编码错误，请检查工作区路径或配置文件中是否含有系统编码无法表示的字符".into());
+    }
+    ("unknown", "请查看服务日志了解详情".into())
+}
+
+/// 等待就绪时的轮询间隔
+const START_READY_POLL_MS: u64 = 500;
+
+/// 启动工作区后端。
+/// `auto_port`: 为 true 时，若配置的 API_PORT 被占用且等待释放超时，自动挑选下一个空闲端口，
+/// 写回工作区 .env，并通过 `service-port-changed` 事件通知前端实际使用的端口（也见返回值 apiPort）。
+/// 占用者是本工作区的 OpenAkita 后端时不换端口，直接报错。
+/// `wait_ready_secs`: 大于 0 时，进程拉起后继续等待 HTTP API 就绪（心跳 http_ready + /api/health），
+/// 期间通过 `service-start-progress` 事件报告阶段；超时仍返回 ServiceStatus，`ready` 为 false。
+/// 默认 0：进程存活即返回（旧行为）。
+/// `debug_console`: 为 true 时在可见的控制台中运行后端，便于排查 import 阶段崩溃：
+/// 输出照常写入日志文件，同时在控制台中实时显示：Windows 弹出 PowerShell 窗口跟随日志；
+/// macOS / Linux 在终端（Terminal / x-terminal-emulator）中运行后端并跟随日志。
+/// PID 文件、状态查询、停止与退出清理照常生效；此模式下不由看门狗自动重启。
+/// 默认先补齐工作区脚手架，再运行 openakita_preflight（额外启动一次 Python），存在 severity 为 "error" 的项时不启动，
+/// 以 StartupError（class = "config-invalid"）返回这些问题。
+/// `skip_preflight`: 为 true 时跳过预检。Python 不可用（如打包后端）导致预检本身失败时记录日志后照常启动。
 #[tauri::command]
-fn openakita_service_start(venv_dir: String, workspace_id: String) -> Result<ServiceStatus, String> {
+async fn openakita_service_start(
+    app: tauri::AppHandle,
+    venv_dir: String,
+    workspace_id: String,
+    auto_port: Option<bool>,
+    wait_ready_secs: Option<u64>,
+    debug_console: Option<bool>,
+    skip_preflight: Option<bool>,
+) -> Result<ServiceStatus, String> {
+    spawn_blocking_result(move || {
+        if !skip_preflight.unwrap_or(false) {
+            // 预检把缺少 llm_endpoints.json 视为错误，先创建默认配置，与 service_start 的顺序一致
+            ensure_workspace_scaffold(&workspace_dir(&workspace_id))?;
+            match run_preflight(&venv_dir, &workspace_id) {
+                Ok(findings) => {
+                    let errors: Vec<&PreflightFinding> =
+                        findings.iter().filter(|f| f.severity == "error").collect();
+                    if !errors.is_empty() {
+                        let lines: Vec<String> =
+                            errors.iter().map(|f| format!("- {}: {}", f.key, f.message)).collect();
+                        let err = StartupError {
+                            class: "config-invalid".into(),
+                            hint: format!("启动前配置检查未通过：\n{}", lines.join("\n")),
+                            log_tail: String::new(),
+                            log_path: String::new(),
+                        };
+                        return Err(serde_json::to_string(&err).unwrap_or(err.hint));
+                    }
+                }
+                Err(e) => eprintln!("preflight skipped: {e}"),
+            }
+        }
+        let mut status = service_start(
+            &app,
+            &venv_dir,
+            &workspace_id,
+            auto_port.unwrap_or(false),
+            debug_console.unwrap_or(false),
+        )?;
+        let wait_secs = wait_ready_secs.unwrap_or(0);
+        if wait_secs > 0 {
+            if let Some(pid) = status.pid {
+                let ready = wait_backend_ready(&app, &workspace_id, pid, wait_secs);
+                status = build_service_status(&workspace_id, is_pid_running(pid), Some(pid), status.pid_file);
+                status.ready = Some(ready);
+            }
+        }
+        Ok(status)
+    })
+    .await
+}
+
+/// 轮询心跳 http_ready 与 /api/health，直到后端就绪、进程退出或超时。返回是否就绪。
+/// 心跳阶段变化时发送 `service-start-progress` 事件。
+/// 旧版后端不写心跳，此时仅以 /api/health 为准。
+fn wait_backend_ready(app: &tauri::AppHandle, workspace_id: &str, pid: u32, timeout_secs: u64) -> bool {
+    let port = read_workspace_api_port(workspace_id).unwrap_or_else(default_api_port);
+    let t0 = std::time::Instant::now();
+    let deadline = t0 + Duration::from_secs(timeout_secs);
+    let mut last_phase: Option<String> = None;
+    loop {
+        let hb = read_heartbeat_file(workspace_id);
+        let phase = hb.as_ref().map(|h| h.phase.clone()).unwrap_or_else(|| "starting".into());
+        let http_ready = hb.as_ref().map(|h| h.http_ready).unwrap_or(false);
+        if last_phase.as_deref() != Some(phase.as_str()) {
+            let _ = app.emit("service-start-progress", serde_json::json!({
+                "workspaceId": workspace_id,
+                "phase": phase,
+                "httpReady": http_ready,
+                "elapsedSecs": t0.elapsed().as_secs(),
+            }));
+            last_phase = Some(phase);
+        }
+        // 有心跳时等 http_ready 再探测，避免 import 阶段反复打无响应的端口
+        if (hb.is_none() || http_ready) && probe_http_health(port, 0, 0).ok {
+            let _ = app.emit("service-start-progress", serde_json::json!({
+                "workspaceId": workspace_id,
+                "phase": "ready",
+                "httpReady": true,
+                "elapsedSecs": t0.elapsed().as_secs(),
+            }));
+            return true;
+        }
+        if !is_pid_running(pid) || std::time::Instant::now() >= deadline {
+            return false;
+        }
+        std::thread::sleep(Duration::from_millis(START_READY_POLL_MS));
+    }
+}
+
+/// openakita_service_start 的同步实现（看门狗、开机自启等内部调用方直接使用）
+fn service_start(
+    app: &tauri::AppHandle,
+    venv_dir: &str,
+    workspace_id: &str,
+    auto_port: bool,
+    debug_console: bool,
+) -> Result<ServiceStatus, String> {
+    let workspace_id = workspace_id.to_string();
     fs::create_dir_all(run_dir()).map_err(|e| format!("create run dir failed: {e}"))?;
     let pid_file = service_pid_file(&workspace_id);
     let pf = pid_file.to_string_lossy().to_string();
@@ -2262,30 +6185,30 @@ fn openakita_service_start(venv_dir: String, workspace_id: String) -> Result<Ser
     // ── 0. 启动前清理旧的心跳文件（避免新进程读到旧心跳） ──
     remove_heartbeat_file(&workspace_id);
 
-    // ── 1. 检查是否已在运行（通过 MANAGED_CHILD 或 PID 文件）──
+    // ── 1. 检查是否已在运行（通过 MANAGED_CHILDREN 或 PID 文件）──
     {
-        let mut guard = MANAGED_CHILD.lock().unwrap();
-        if let Some(ref mut mp) = *guard {
-            if mp.workspace_id == workspace_id {
-                match mp.child.try_wait() {
-                    Ok(None) => {
-                        return Ok(build_service_status(&workspace_id, true, Some(mp.pid), pf));
-                    }
-                    _ => { *guard = None; }
+        let mut guard = MANAGED_CHILDREN.lock().unwrap();
+        if let Some(mp) = guard.get_mut(&workspace_id) {
+            match mp.child.try_wait() {
+                Ok(None) => {
+                    return Ok(build_service_status(&workspace_id, true, Some(mp.pid), pf));
                 }
+                _ => { guard.remove(&workspace_id); }
             }
         }
     }
     if let Some(data) = read_pid_file(&workspace_id) {
         if is_pid_file_valid(&data) {
             // 进程已在运行，但检查心跳是否严重过期（可能卡死）
-            if let Some(true) = is_heartbeat_stale(&workspace_id, 60) {
+            if let Some(true) = is_heartbeat_stale(&workspace_id, heartbeat_thresholds().kill_secs) {
                 // 心跳严重过期，进程可能卡死，先尝试清理再启动
                 let port = read_workspace_api_port(&workspace_id);
-                let _ = graceful_stop_pid(data.pid, port);
+                let _ = graceful_stop_pid(data.pid, port, graceful_stop_timeout_secs(), None);
                 let _ = fs::remove_file(&pid_file);
                 remove_heartbeat_file(&workspace_id);
             } else {
+                // 接管外部启动的后端：同样推送心跳变化
+                start_heartbeat_watcher(app, &workspace_id);
                 return Ok(build_service_status(&workspace_id, true, Some(data.pid), pf));
             }
         } else {
@@ -2298,33 +6221,95 @@ fn openakita_service_start(venv_dir: String, workspace_id: String) -> Result<Ser
     if !try_acquire_start_lock(&workspace_id) {
         return Err("另一个启动操作正在进行中，请稍候".to_string());
     }
-    struct LockGuard(String);
-    impl Drop for LockGuard {
-        fn drop(&mut self) { release_start_lock(&self.0); }
-    }
-    let _lock_guard = LockGuard(workspace_id.clone());
+    let _lock_guard = StartLockGuard(workspace_id.clone());
+
+    service_start_locked(app, venv_dir, &workspace_id, auto_port, debug_console)
+}
+
+/// 启动流程中持有启动锁之后的部分（调用方必须已持有 StartLockGuard）。
+/// 由 service_start 与 openakita_service_restart 共用。
+fn service_start_locked(
+    app: &tauri::AppHandle,
+    venv_dir: &str,
+    workspace_id: &str,
+    auto_port: bool,
+    debug_console: bool,
+) -> Result<ServiceStatus, String> {
+    let workspace_id = workspace_id.to_string();
+    let pid_file = service_pid_file(&workspace_id);
+    let pf = pid_file.to_string_lossy().to_string();
 
     let ws_dir = workspace_dir(&workspace_id);
     ensure_workspace_scaffold(&ws_dir)?;
 
+    // ── 2.4 LLM 端点配置预检 ──
+    // 配置损坏时后端会直接拒绝启动，这里提前给出具体的校验错误，而不是只让用户去翻日志。
+    let llm_errors = llm_endpoints_validation_errors(&llm_endpoints_path(&workspace_id));
+    if !llm_errors.is_empty() {
+        return Err(format!(
+            "data/llm_endpoints.json 配置有误，无法启动后端服务：\n- {}",
+            llm_errors.join("\n- ")
+        ));
+    }
+
     // ── 2.5 端口可用性预检 ──
     // 在 spawn 之前检查端口是否被占用（旧进程残留、TIME_WAIT、其他程序等）。
     // Python 端也有重试，但尽早发现可以给用户更明确的提示。
-    let effective_port = read_workspace_api_port(&workspace_id).unwrap_or(18900);
+    let effective_port = read_workspace_api_port(&workspace_id).unwrap_or_else(default_api_port);
     if !check_port_available(effective_port) {
         // 端口被占用，等待最多 10 秒（处理 TIME_WAIT 等场景）
-        if !wait_for_port_free(effective_port, 10_000) {
-            return Err(format!(
-                "端口 {} 已被占用，无法启动后端服务。\n\
-                 可能原因：上次关闭后端口尚未释放、或有其他程序占用该端口。\n\
-                 请稍后重试，或检查是否有其他程序占用端口 {}。",
-                effective_port, effective_port
-            ));
+        if !wait_for_port_free(effective_port, 10_000, Some(&StopProgress { app, workspace_id: &workspace_id })) {
+            // 占用者是 OpenAkita 后端且不属于其他工作区 → 很可能就是本工作区未登记的后端，
+            // 此时换端口只会启动第二个实例，必须让用户先处理
+            let own_backend = find_port_owner_pid(effective_port).filter(|&owner| {
+                is_openakita_process(owner)
+                    && !list_service_pids()
+                        .iter()
+                        .any(|e| e.pid == owner && e.workspace_id != workspace_id)
+            });
+            if let Some(owner) = own_backend {
+                return Err(format!(
+                    "端口 {} 已被本工作区的 OpenAkita 后端占用（PID={}），请先停止该进程后再启动",
+                    effective_port, owner
+                ));
+            }
+            if auto_port {
+                let new_port = find_free_port_in_range(effective_port.saturating_add(1), 100)
+                    .ok_or_else(|| format!("端口 {} 已被占用，且其后 100 个端口中未找到可用端口", effective_port))?;
+                workspace_update_env(
+                    workspace_id.clone(),
+                    vec![EnvEntry {
+                        key: "API_PORT".into(),
+                        value: new_port.to_string(),
+                    }],
+                )?;
+                let _ = app.emit("service-port-changed", serde_json::json!({
+                    "workspaceId": workspace_id,
+                    "previousPort": effective_port,
+                    "port": new_port,
+                }));
+            } else {
+                let owner = match port_owner_info(effective_port) {
+                    Some(o) => format!(
+                        "\n占用进程：PID {} {}\n命令行：{}",
+                        o.pid,
+                        o.exe_name.unwrap_or_default(),
+                        o.command_line.unwrap_or_else(|| "(未知)".into())
+                    ),
+                    None => String::new(),
+                };
+                return Err(format!(
+                    "端口 {} 已被占用，无法启动后端服务。\n\
+                     可能原因：上次关闭后端口尚未释放、或有其他程序占用该端口。\n\
+                     请稍后重试，或检查是否有其他程序占用端口 {}。{}",
+                    effective_port, effective_port, owner
+                ));
+            }
         }
     }
 
     // 优先使用内嵌 PyInstaller 后端，降级到 venv python
-    let (backend_exe, backend_args) = get_backend_executable(&venv_dir);
+    let (backend_exe, backend_args) = get_backend_executable(venv_dir);
     if !backend_exe.exists() {
         return Err(format!("后端可执行文件不存在: {}", backend_exe.to_string_lossy()));
     }
@@ -2355,6 +6340,9 @@ fn openakita_service_start(venv_dir: String, workspace_id: String) -> Result<Ser
         cmd.env(k, v);
     }
     cmd.env("LLM_ENDPOINTS_CONFIG", ws_dir.join("data").join("llm_endpoints.json"));
+    // 控制令牌：后端据此校验 /api/shutdown 请求，同时写入 PID 文件供停止时使用
+    let control_token = generate_control_token();
+    cmd.env(CONTROL_TOKEN_ENV, &control_token);
 
     // 设置可选模块路径（已安装的可选模块 site-packages）
     // 重要：不能使用 PYTHONPATH！Python 启动时 PYTHONPATH 会被插入到 sys.path
@@ -2375,6 +6363,27 @@ fn openakita_service_start(venv_dir: String, workspace_id: String) -> Result<Ser
         cmd.env("PLAYWRIGHT_BROWSERS_PATH", &browsers_dir);
     }
 
+    // 后端按需下载 Playwright 浏览器时使用的镜像；工作区 .env 中已配置的优先
+    if !cmd.get_envs().any(|(k, _)| k == "PLAYWRIGHT_DOWNLOAD_HOST") {
+        cmd.env("PLAYWRIGHT_DOWNLOAD_HOST", playwright_download_host());
+    }
+
+    // 出站代理：后端按需下载 Playwright 浏览器、调用外部 API 时同样走用户配置的代理
+    apply_proxy_env(&mut cmd);
+
+    // 调试模式（macOS / Linux）：在终端中运行，没有 Child 句柄，按 PID 跟踪（同已接管的后端）
+    #[cfg(not(windows))]
+    if debug_console {
+        let pid = spawn_in_terminal(&cmd, &workspace_id, &log_path)?;
+        write_pid_file(&workspace_id, pid, STARTED_BY_TAURI, Some(&control_token))?;
+        ADOPTED_SERVICES.lock().unwrap().insert(workspace_id.clone(), pid);
+        watch_adopted_service(app.clone(), workspace_id.clone(), pid);
+        touch_workspace_last_started(&workspace_id);
+        refresh_backend_version();
+        start_heartbeat_watcher(app, &workspace_id);
+        return Ok(build_service_status(&workspace_id, true, Some(pid), pf));
+    }
+
     // detach + redirect io
     cmd.stdin(std::process::Stdio::null())
         .stdout(std::process::Stdio::from(log_file.try_clone().map_err(|e| format!("clone log failed: {e}"))?))
@@ -2386,31 +6395,55 @@ fn openakita_service_start(venv_dir: String, workspace_id: String) -> Result<Ser
         cmd.creation_flags(0x00000008u32 | 0x00000200u32 | 0x0800_0000u32); // DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP | CREATE_NO_WINDOW
     }
 
+    // 调试模式（Windows）：后端照常写日志，另开一个控制台窗口实时跟随日志输出
+    #[cfg(windows)]
+    if debug_console {
+        open_log_console(&log_path)?;
+    }
+
+    // Unix：后端自成进程组，停止时 killpg 可连同 playwright / Chromium / ffmpeg 等子进程一起结束
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        cmd.process_group(0);
+    }
+
     let child = cmd.spawn().map_err(|e| format!("spawn openakita serve failed: {e}"))?;
     let pid = child.id();
     let started_at = now_epoch_secs();
 
+    // 命令行模式下本进程启动后即退出，不能把后端绑进 kill-on-close 的 Job Object
+    #[cfg(windows)]
+    if read_state_file().kill_backend_with_app.unwrap_or(false) && !is_cli_mode() {
+        if let Err(e) = assign_to_backend_job(&child) {
+            eprintln!("assign backend to job object failed: {e}");
+        }
+    }
+
     // ── 3. 写 JSON PID 文件 ──
-    write_pid_file(&workspace_id, pid, "tauri")?;
+    write_pid_file(&workspace_id, pid, STARTED_BY_TAURI, Some(&control_token))?;
 
-    // ── 4. 存入 MANAGED_CHILD ──
+    // ── 4. 存入 MANAGED_CHILDREN ──
     {
-        let mut guard = MANAGED_CHILD.lock().unwrap();
-        *guard = Some(ManagedProcess {
-            child,
-            workspace_id: workspace_id.clone(),
-            pid,
-            started_at,
-        });
+        let mut guard = MANAGED_CHILDREN.lock().unwrap();
+        guard.insert(
+            workspace_id.clone(),
+            ManagedProcess {
+                child,
+                workspace_id: workspace_id.clone(),
+                pid,
+                started_at,
+            },
+        );
     }
 
     // Confirm the process is still alive shortly after spawning.
     std::thread::sleep(std::time::Duration::from_millis(500));
     if !is_pid_running(pid) {
         {
-            let mut guard = MANAGED_CHILD.lock().unwrap();
-            if let Some(ref mp) = *guard {
-                if mp.pid == pid { *guard = None; }
+            let mut guard = MANAGED_CHILDREN.lock().unwrap();
+            if guard.get(&workspace_id).map(|mp| mp.pid) == Some(pid) {
+                guard.remove(&workspace_id);
             }
         }
         let _ = fs::remove_file(&pid_file);
@@ -2424,58 +6457,567 @@ fn openakita_service_start(venv_dir: String, workspace_id: String) -> Result<Ser
                 }
             })
             .unwrap_or_default();
-        return Err(format!(
-            "openakita serve 似乎启动后立即退出（PID={pid}）。\n请查看服务日志：{}\n\n--- log tail ---\n{}",
-            log_path.to_string_lossy(),
-            tail
-        ));
+        let custom = read_state_file().startup_failure_patterns.unwrap_or_default();
+        let (class, hint) = classify_startup_failure(&tail, &custom);
+        let err = StartupError {
+            class,
+            hint: format!("openakita serve 似乎启动后立即退出（PID={pid}）：{hint}"),
+            log_tail: tail,
+            log_path: log_path.to_string_lossy().to_string(),
+        };
+        return Err(serde_json::to_string(&err).unwrap_or(err.hint));
     }
 
+    touch_workspace_last_started(&workspace_id);
+    refresh_backend_version();
+    if !debug_console {
+        watchdog_register_start(&workspace_id, venv_dir);
+    }
+    start_heartbeat_watcher(app, &workspace_id);
+    spawn_exit_waiter(app.clone(), workspace_id.clone(), pid);
     Ok(build_service_status(&workspace_id, true, Some(pid), pf))
 }
 
-#[tauri::command]
-fn openakita_service_stop(workspace_id: String) -> Result<ServiceStatus, String> {
-    let pid_file = service_pid_file(&workspace_id);
-    let port = read_workspace_api_port(&workspace_id);
-    let effective_port = port.unwrap_or(18900);
+/// 调试模式下等待终端中的启动脚本回报后端 PID 的最长时间
+#[cfg(not(windows))]
+const DEBUG_TERMINAL_PID_TIMEOUT_SECS: u64 = 15;
+
+#[cfg(not(windows))]
+fn sh_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
 
-    // ── 1. MANAGED_CHILD handle ──
+/// 调试模式（Windows）：打开 PowerShell 窗口跟随后端日志。关闭窗口不影响后端。
+#[cfg(windows)]
+fn open_log_console(log_path: &Path) -> Result<(), String> {
+    use std::os::windows::process::CommandExt;
+    let quoted = log_path.to_string_lossy().replace('\'', "''");
+    Command::new("powershell.exe")
+        .args(["-NoProfile", "-NoExit", "-Command"])
+        .arg(format!(
+            "[Console]::OutputEncoding = [Text.Encoding]::UTF8; Get-Content -LiteralPath '{quoted}' -Encoding UTF8 -Tail 0 -Wait"
+        ))
+        .creation_flags(0x00000010u32) // CREATE_NEW_CONSOLE
+        .spawn()
+        .map_err(|e| format!("无法打开控制台窗口: {e}"))?;
+    Ok(())
+}
+
+/// 调试模式：把已配置好的后端命令（工作目录 / 环境变量 / 参数）写成 sh 脚本，在用户终端中运行。
+/// 后端输出写入 `log_path`（与非调试模式一致），终端中 tail -f 实时显示；
+/// 脚本把后端 PID 写入临时文件并等待其退出，退出后保留窗口以便查看输出；返回后端（而非终端）的 PID。
+/// 脚本中含工作区 .env 的 API Key 与控制令牌：以 0700 创建，拿到 PID（或超时）后立即删除。
+#[cfg(not(windows))]
+fn spawn_in_terminal(cmd: &Command, workspace_id: &str, log_path: &Path) -> Result<u32, String> {
+    let script_path = run_dir().join(format!("debug-console-{workspace_id}.command"));
+    let pid_path = run_dir().join(format!("debug-console-{workspace_id}.pid"));
+    let _ = fs::remove_file(&pid_path);
+    let _ = fs::remove_file(&script_path);
+
+    let mut script = String::from("#!/bin/sh\n");
+    if let Some(dir) = cmd.get_current_dir() {
+        script.push_str(&format!("cd {} || exit 1\n", sh_quote(&dir.to_string_lossy())));
+    }
+    for (k, v) in cmd.get_envs() {
+        if let Some(v) = v {
+            script.push_str(&format!(
+                "export {}={}\n",
+                k.to_string_lossy(),
+                sh_quote(&v.to_string_lossy())
+            ));
+        }
+    }
+    let mut line = sh_quote(&cmd.get_program().to_string_lossy());
+    for a in cmd.get_args() {
+        line.push(' ');
+        line.push_str(&sh_quote(&a.to_string_lossy()));
+    }
+    let log = sh_quote(&log_path.to_string_lossy());
+    script.push_str(&format!("tail -n 0 -f {log} &\ntail_pid=$!\n"));
+    script.push_str(&format!("{line} </dev/null >>{log} 2>&1 &\nserve_pid=$!\n"));
+    script.push_str(&format!("echo $serve_pid > {}\n", sh_quote(&pid_path.to_string_lossy())));
+    script.push_str("wait $serve_pid\ncode=$?\nsleep 1\nkill $tail_pid 2>/dev/null\n");
+    script.push_str("echo\necho \"[openakita serve 已退出，退出码 $code] 按回车关闭窗口\"\nread _\n");
     {
-        let mut guard = MANAGED_CHILD.lock().unwrap();
-        if let Some(mut mp) = guard.take() {
-            if mp.workspace_id == workspace_id {
-                let _ = graceful_stop_pid(mp.pid, port);
-                if is_pid_running(mp.pid) {
-                    let _ = mp.child.kill();
-                    let _ = mp.child.wait();
+        use std::os::unix::fs::OpenOptionsExt;
+        let mut f = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o700)
+            .open(&script_path)
+            .map_err(|e| format!("write debug script failed: {e}"))?;
+        f.write_all(script.as_bytes())
+            .map_err(|e| format!("write debug script failed: {e}"))?;
+    }
+
+    #[cfg(target_os = "macos")]
+    let spawned = Command::new("open").arg("-a").arg("Terminal").arg(&script_path).spawn();
+    #[cfg(not(target_os = "macos"))]
+    let spawned = Command::new("x-terminal-emulator")
+        .arg("-e")
+        .arg("sh")
+        .arg(&script_path)
+        .spawn()
+        .or_else(|_| Command::new("xterm").arg("-e").arg("sh").arg(&script_path).spawn());
+    if let Err(e) = spawned {
+        let _ = fs::remove_file(&script_path);
+        return Err(format!("无法打开终端运行后端: {e}"));
+    }
+
+    // sh 已打开脚本后删除不影响其继续执行；脚本写出 PID 时已读过 export 部分
+    let deadline = std::time::Instant::now() + Duration::from_secs(DEBUG_TERMINAL_PID_TIMEOUT_SECS);
+    while std::time::Instant::now() < deadline {
+        if let Some(pid) = fs::read_to_string(&pid_path)
+            .ok()
+            .and_then(|s| s.trim().parse::<u32>().ok())
+        {
+            let _ = fs::remove_file(&pid_path);
+            let _ = fs::remove_file(&script_path);
+            return Ok(pid);
+        }
+        std::thread::sleep(Duration::from_millis(200));
+    }
+    let _ = fs::remove_file(&script_path);
+    Err(format!(
+        "终端已打开，但 {DEBUG_TERMINAL_PID_TIMEOUT_SECS} 秒内未拿到后端 PID，请查看终端窗口中的输出"
+    ))
+}
+
+/// 子进程退出检查间隔
+const EXIT_WAITER_POLL_MS: u64 = 500;
+
+/// 为托管子进程启动退出监视线程：子进程非用户主动停止而退出时，清理 PID / 心跳文件并发送
+/// `backend-exited` 事件（workspaceId / pid / exitCode / signal / uptimeSecs / logTail）。
+/// Child 句柄需留在 MANAGED_CHILDREN 中供停止与状态查询使用，因此这里持锁轮询 try_wait 而不是阻塞 wait。
+/// openakita_service_stop 等主动停止路径会先把条目移出 MANAGED_CHILDREN，线程发现条目不在（或已换成新 PID）即静默结束。
+fn spawn_exit_waiter(app: tauri::AppHandle, workspace_id: String, pid: u32) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(Duration::from_millis(EXIT_WAITER_POLL_MS));
+        let (status, started_at) = {
+            let mut guard = MANAGED_CHILDREN.lock().unwrap();
+            let Some(mp) = guard.get_mut(&workspace_id).filter(|mp| mp.pid == pid) else {
+                return;
+            };
+            match mp.child.try_wait() {
+                Ok(None) => continue,
+                Ok(Some(st)) => {
+                    let started_at = mp.started_at;
+                    guard.remove(&workspace_id);
+                    (Some(st), started_at)
                 }
-                let _ = fs::remove_file(&pid_file);
-                // 等待端口释放（最多 10 秒），确保后续重启不会遇到端口冲突
-                let _ = wait_for_port_free(effective_port, 10_000);
-                remove_heartbeat_file(&workspace_id);
-                return Ok(build_service_status(&workspace_id, false, None, pid_file.to_string_lossy().to_string()));
-            } else {
-                *guard = Some(mp);
+                Err(_) => {
+                    let started_at = mp.started_at;
+                    guard.remove(&workspace_id);
+                    (None, started_at)
+                }
+            }
+        };
+
+        let exit_code = status.and_then(|st| st.code());
+        #[cfg(unix)]
+        let signal = {
+            use std::os::unix::process::ExitStatusExt;
+            status.and_then(|st| st.signal())
+        };
+        #[cfg(not(unix))]
+        let signal: Option<i32> = None;
+
+        watchdog_record_exit(&workspace_id, exit_code);
+        stop_heartbeat_watcher(&workspace_id);
+        if read_pid_file(&workspace_id).is_some_and(|d| d.pid == pid) {
+            let _ = fs::remove_file(service_pid_file(&workspace_id));
+        }
+        remove_heartbeat_file(&workspace_id);
+        let _ = app.emit("backend-exited", serde_json::json!({
+            "workspaceId": workspace_id,
+            "pid": pid,
+            "exitCode": exit_code,
+            "signal": signal,
+            "uptimeSecs": now_epoch_secs().saturating_sub(started_at),
+            "logTail": read_service_log_tail(&workspace_id, WATCHDOG_LOG_TAIL_BYTES),
+        }));
+        return;
+    });
+}
+
+#[tauri::command]
+async fn openakita_service_stop(app: tauri::AppHandle, workspace_id: String) -> Result<ServiceStatus, String> {
+    spawn_blocking_result(move || service_stop(&app, &workspace_id)).await
+}
+
+/// openakita_service_stop 的同步实现（优雅关闭等待 + 端口释放等待，可能持续十余秒，勿在主线程调用）
+fn service_stop(app: &tauri::AppHandle, workspace_id: &str) -> Result<ServiceStatus, String> {
+    let workspace_id = workspace_id.to_string();
+    // 用户主动停止：通知看门狗不要把这次退出当作崩溃
+    watchdog_mark_user_stopped(&workspace_id);
+    stop_heartbeat_watcher(&workspace_id);
+    ADOPTED_SERVICES.lock().unwrap().remove(&workspace_id);
+    let pid_file = service_pid_file(&workspace_id);
+    let port = read_workspace_api_port(&workspace_id);
+    let effective_port = port.unwrap_or_else(default_api_port);
+    let timeout_secs = graceful_stop_timeout_secs();
+    let progress = StopProgress { app, workspace_id: &workspace_id };
+
+    // ── 1. MANAGED_CHILDREN handle ──
+    // 先从表中取出再停止，避免在优雅关闭等待期间长时间持锁阻塞其他工作区的状态查询
+    let managed = MANAGED_CHILDREN.lock().unwrap().remove(&workspace_id);
+    if let Some(mut mp) = managed {
+        let mut killed = graceful_stop_pid(mp.pid, port, timeout_secs, Some(&progress)).unwrap_or_default();
+        if is_pid_running(mp.pid) {
+            let _ = mp.child.kill();
+            let _ = mp.child.wait();
+            if !killed.contains(&mp.pid) {
+                killed.push(mp.pid);
             }
         }
+        let _ = fs::remove_file(&pid_file);
+        // 等待端口释放（最多 10 秒），确保后续重启不会遇到端口冲突
+        let _ = wait_for_port_free(effective_port, 10_000, Some(&progress));
+        remove_heartbeat_file(&workspace_id);
+        let mut status = build_service_status(&workspace_id, false, None, pid_file.to_string_lossy().to_string());
+        status.killed_pids = killed;
+        return Ok(status);
     }
 
     // ── 2. PID 文件回退 ──
     let pid = read_pid_file(&workspace_id).map(|d| d.pid);
+    let mut killed = Vec::new();
     if let Some(pid) = pid {
         // 强制杀干净：如果杀不掉，要显式报错（避免 UI 显示“已停止”但后台仍残留）。
-        graceful_stop_pid(pid, port).map_err(|e| format!("failed to stop service: {e}"))?;
+        killed = graceful_stop_pid(pid, port, timeout_secs, Some(&progress))
+            .map_err(|e| format!("failed to stop service: {e}"))?;
+    }
+    let _ = fs::remove_file(&pid_file);
+    remove_heartbeat_file(&workspace_id);
+    // 等待端口释放（最多 10 秒），确保后续重启不会遇到端口冲突
+    let _ = wait_for_port_free(effective_port, 10_000, Some(&progress));
+    let mut status = build_service_status(&workspace_id, false, None, pid_file.to_string_lossy().to_string());
+    status.killed_pids = killed;
+    Ok(status)
+}
+
+/// 已接管的外部后端（以及在终端中运行的调试模式后端）：workspace_id -> pid。
+/// 没有 Child 句柄，由 watch_adopted_service 按 PID 轮询存活状态。
+static ADOPTED_SERVICES: Lazy<Mutex<std::collections::HashMap<String, u32>>> =
+    Lazy::new(|| Mutex::new(std::collections::HashMap::new()));
+
+/// 已接管后端的存活检查间隔
+const ADOPTED_WATCH_INTERVAL_MS: u64 = 2000;
+
+/// 接管外部（CLI）启动的后端：确认 PID 确为 OpenAkita 进程后，把 PID 文件标记为 tauri-adopted，
+/// 并启动心跳监视与按 PID 的存活监视，使状态查询、心跳事件和托盘退出都按托管进程处理。
+#[tauri::command]
+fn adopt_external_service(app: tauri::AppHandle, workspace_id: String) -> Result<ServiceStatus, String> {
+    let data = read_pid_file(&workspace_id)
+        .ok_or_else(|| format!("工作区 {workspace_id} 没有 PID 文件，无可接管的后端"))?;
+    if data.started_by != STARTED_BY_EXTERNAL {
+        return Err(format!("工作区 {workspace_id} 的后端并非外部启动（started_by = {}）", data.started_by));
+    }
+    if !is_pid_file_valid(&data) || !is_openakita_process(data.pid) {
+        return Err(format!("PID {} 不是运行中的 OpenAkita 进程，已拒绝接管", data.pid));
+    }
+
+    let adopted = PidFileData {
+        started_by: STARTED_BY_TAURI_ADOPTED.to_string(),
+        ..data
+    };
+    let json = serde_json::to_string_pretty(&adopted).map_err(|e| format!("serialize pid: {e}"))?;
+    let pid_file = service_pid_file(&workspace_id);
+    fs::write(&pid_file, json).map_err(|e| format!("write pid file: {e}"))?;
+
+    ADOPTED_SERVICES.lock().unwrap().insert(workspace_id.clone(), adopted.pid);
+    start_heartbeat_watcher(&app, &workspace_id);
+    watch_adopted_service(app, workspace_id.clone(), adopted.pid);
+    Ok(build_service_status(&workspace_id, true, Some(adopted.pid), pid_file.to_string_lossy().to_string()))
+}
+
+/// 按 PID 轮询已接管后端；进程退出后清理 PID / 心跳文件并发送 `backend-exited` 事件。
+/// 接管记录被移除（用户停止、退出清理）或换成其他 PID 时线程结束。
+fn watch_adopted_service(app: tauri::AppHandle, workspace_id: String, pid: u32) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(Duration::from_millis(ADOPTED_WATCH_INTERVAL_MS));
+        if ADOPTED_SERVICES.lock().unwrap().get(&workspace_id) != Some(&pid) {
+            return;
+        }
+        if is_pid_running(pid) {
+            continue;
+        }
+        ADOPTED_SERVICES.lock().unwrap().remove(&workspace_id);
+        stop_heartbeat_watcher(&workspace_id);
+        if read_pid_file(&workspace_id).is_some_and(|d| d.pid == pid) {
+            let _ = fs::remove_file(service_pid_file(&workspace_id));
+        }
+        remove_heartbeat_file(&workspace_id);
+        let _ = app.emit("backend-exited", serde_json::json!({
+            "workspaceId": workspace_id,
+            "pid": pid,
+        }));
+        return;
+    });
+}
+
+/// 原子重启：在同一把启动锁内完成 停止（优雅关闭）→ 等待端口释放 → 清理心跳 → 启动，
+/// 避免前端自行 stop + sleep + start 时与 wait_for_port_free / 心跳清理竞争，或被二次点击重复启动。
+/// 重启期间心跳阶段报告为 "restarting"，直到新后端写入自己的心跳。
+/// 通过 `service-restart-progress` 事件报告阶段：stopping → waiting-port → starting。
+#[tauri::command]
+async fn openakita_service_restart(
+    app: tauri::AppHandle,
+    venv_dir: String,
+    workspace_id: String,
+) -> Result<ServiceStatus, String> {
+    spawn_blocking_result(move || service_restart(&app, &venv_dir, &workspace_id)).await
+}
+
+/// openakita_service_restart 的同步实现（内存守护等内部调用方直接使用）
+fn service_restart(app: &tauri::AppHandle, venv_dir: &str, workspace_id: &str) -> Result<ServiceStatus, String> {
+    let workspace_id = workspace_id.to_string();
+    fs::create_dir_all(run_dir()).map_err(|e| format!("create run dir failed: {e}"))?;
+    if !try_acquire_start_lock(&workspace_id) {
+        return Err("另一个启动操作正在进行中，请稍候".to_string());
+    }
+    let _lock_guard = StartLockGuard(workspace_id.clone());
+    let emit_phase = |phase: &str| {
+        let _ = app.emit("service-restart-progress", serde_json::json!({
+            "workspaceId": workspace_id,
+            "phase": phase,
+        }));
+    };
+
+    emit_phase("stopping");
+    write_heartbeat_marker(&workspace_id, "restarting");
+    service_stop(app, &workspace_id)?;
+
+    emit_phase("waiting-port");
+    let port = read_workspace_api_port(&workspace_id).unwrap_or_else(default_api_port);
+    if !wait_for_port_free(port, 10_000, Some(&StopProgress { app, workspace_id: &workspace_id })) {
+        remove_heartbeat_file(&workspace_id);
+        return Err(format!("重启失败：旧进程已停止，但端口 {port} 在 10 秒内仍未释放"));
+    }
+    // 清理旧心跳，重新标记为 restarting（stop 已删除心跳文件，这里覆盖可能的残留）
+    write_heartbeat_marker(&workspace_id, "restarting");
+
+    emit_phase("starting");
+    let result = service_start_locked(app, venv_dir, &workspace_id, false, false);
+    if result.is_err() {
+        remove_heartbeat_file(&workspace_id);
+    }
+    result
+}
+
+/// 内存守护采样间隔
+const MEMORY_GUARD_INTERVAL_SECS: u64 = 30;
+/// 连续超过阈值的采样次数达到该值时重启后端
+const MEMORY_GUARD_MAX_SAMPLES: u32 = 3;
+
+/// 后台内存守护：max_backend_memory_mb 设置后（默认关闭），每 30 秒采样 Tauri 托管后端的常驻内存，
+/// 连续 3 次超过阈值时优雅重启，并发送 `backend-memory-restart` 事件（workspaceId / peakMemoryMb / thresholdMb / error）。
+/// 心跳处于 starting / initializing / restarting 阶段（加载模型时内存会合理冲高）不计数。
+/// 当前平台无法读取进程内存时不启动。
+fn spawn_memory_guard(app: tauri::AppHandle) {
+    if !PROCESS_RESOURCES_SUPPORTED {
+        return;
+    }
+    std::thread::spawn(move || {
+        // workspace_id -> (pid, 连续超限次数, 期间峰值 MB)
+        let mut over: std::collections::HashMap<String, (u32, u32, f64)> = std::collections::HashMap::new();
+        loop {
+            std::thread::sleep(Duration::from_secs(MEMORY_GUARD_INTERVAL_SECS));
+            let Some(limit_mb) = read_state_file().max_backend_memory_mb.filter(|&mb| mb > 0) else {
+                over.clear();
+                continue;
+            };
+            let children: Vec<(String, u32)> = MANAGED_CHILDREN
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(ws, mp)| (ws.clone(), mp.pid))
+                .collect();
+            over.retain(|ws, _| children.iter().any(|(id, _)| id == ws));
+
+            for (ws, pid) in children {
+                let phase = read_heartbeat_file(&ws).map(|hb| hb.phase).unwrap_or_default();
+                if matches!(phase.as_str(), "starting" | "initializing" | "restarting") {
+                    over.remove(&ws);
+                    continue;
+                }
+                let Some(rss_mb) = sample_process_resources(pid).map(|(_, rss, _)| rss as f64 / (1024.0 * 1024.0)) else {
+                    continue;
+                };
+                if rss_mb <= limit_mb as f64 {
+                    over.remove(&ws);
+                    continue;
+                }
+                let entry = over.entry(ws.clone()).or_insert((pid, 0, 0.0));
+                if entry.0 != pid {
+                    *entry = (pid, 0, 0.0);
+                }
+                entry.1 += 1;
+                entry.2 = entry.2.max(rss_mb);
+                if entry.1 < MEMORY_GUARD_MAX_SAMPLES {
+                    continue;
+                }
+                let peak_mb = entry.2;
+                over.remove(&ws);
+
+                let venv_dir = WATCHDOG_TARGETS
+                    .lock()
+                    .unwrap()
+                    .get(&ws)
+                    .map(|t| t.venv_dir.clone())
+                    .unwrap_or_else(|| openakita_root_dir().join("venv").to_string_lossy().to_string());
+                eprintln!("Memory guard: backend for {ws} uses {peak_mb:.0} MB (> {limit_mb} MB), restarting");
+                let error = service_restart(&app, &venv_dir, &ws).err();
+                let _ = app.emit("backend-memory-restart", serde_json::json!({
+                    "workspaceId": ws,
+                    "peakMemoryMb": peak_mb,
+                    "thresholdMb": limit_mb,
+                    "error": error,
+                }));
+            }
+        }
+    });
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct HealthResult {
+    ok: bool,
+    /// 最后一次尝试的 HTTP 状态码；连接失败时为 None
+    status_code: Option<u16>,
+    /// 最后一次尝试的耗时
+    latency_ms: u64,
+    attempts: u32,
+}
+
+/// 轮询 /api/health：首次尝试失败后最多再重试 `retries` 次，每次间隔 `interval_ms`，单次超时 2 秒。
+/// 返回 2xx 即视为健康。
+fn probe_http_health(port: u16, retries: u8, interval_ms: u64) -> HealthResult {
+    probe_http_health_with_token(port, retries, interval_ms, None)
+}
+
+/// 同 probe_http_health；`token` 非空时携带 Authorization: Bearer 头
+fn probe_http_health_with_token(port: u16, retries: u8, interval_ms: u64, token: Option<&str>) -> HealthResult {
+    let url = format!("http://127.0.0.1:{}/api/health", port);
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(2))
+        .build()
+        .ok();
+    let mut result = HealthResult {
+        ok: false,
+        status_code: None,
+        latency_ms: 0,
+        attempts: 0,
+    };
+    for i in 0..=retries as u32 {
+        if i > 0 && interval_ms > 0 {
+            std::thread::sleep(Duration::from_millis(interval_ms));
+        }
+        let t0 = std::time::Instant::now();
+        let status = client
+            .as_ref()
+            .and_then(|c| {
+                let req = c.get(&url);
+                match token {
+                    Some(t) => req.bearer_auth(t),
+                    None => req,
+                }
+                .send()
+                .ok()
+            })
+            .map(|r| r.status());
+        result.attempts = i + 1;
+        result.latency_ms = t0.elapsed().as_millis() as u64;
+        result.status_code = status.map(|s| s.as_u16());
+        if status.map(|s| s.is_success()).unwrap_or(false) {
+            result.ok = true;
+            break;
+        }
+    }
+    result
+}
+
+/// 供前端"等待后端就绪"时展示延迟与重试次数。端口取自工作区 .env 的 API_PORT。
+#[tauri::command]
+async fn openakita_http_health(
+    workspace_id: String,
+    retries: u8,
+    interval_ms: u64,
+) -> Result<HealthResult, String> {
+    spawn_blocking_result(move || {
+        let port = read_workspace_api_port(&workspace_id).unwrap_or_else(default_api_port);
+        Ok(probe_http_health(port, retries, interval_ms))
+    })
+    .await
+}
+
+/// 去掉 ANSI 转义序列（CSI `ESC [ ... 终止字节`、OSC `ESC ] ... BEL/ESC \`、`ESC ( B` 等带中间字节的序列
+/// 及其他两字节序列），并把 `\r` 覆写的进度条行折叠为最后一次写入的内容，`\r\n` 统一为 `\n`。
+fn clean_log_text(input: &str) -> String {
+    let mut stripped = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            stripped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('[') => {
+                // CSI：参数/中间字节直到 0x40..=0x7E 的终止字节
+                for n in chars.by_ref() {
+                    if ('\x40'..='\x7e').contains(&n) {
+                        break;
+                    }
+                }
+            }
+            Some(']') => {
+                // OSC：以 BEL 或 ST（ESC \）结束
+                while let Some(n) = chars.next() {
+                    if n == '\x07' {
+                        break;
+                    }
+                    if n == '\x1b' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            Some(' '..='/') => {
+                // nF（如 tput sgr0 输出的 ESC ( B）：中间字节 0x20..=0x2F 之后还有一个终止字节
+                for n in chars.by_ref() {
+                    if !(' '..='/').contains(&n) {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
     }
-    let _ = fs::remove_file(&pid_file);
-    remove_heartbeat_file(&workspace_id);
-    // 等待端口释放（最多 10 秒），确保后续重启不会遇到端口冲突
-    let _ = wait_for_port_free(effective_port, 10_000);
-    Ok(build_service_status(&workspace_id, false, None, pid_file.to_string_lossy().to_string()))
+    stripped
+        .split('\n')
+        .map(|line| {
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            line.rsplit('\r').next().unwrap_or(line)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
+/// 读取服务日志。默认返回末尾 `tail_bytes`（默认 40KB，上限 400KB）；
+/// 传入 `from_offset`（上次返回的 nextOffset）时只返回之后追加的内容。
+/// `clean`（默认 true）：去掉 ANSI 颜色码并折叠 `\r` 进度条。
+/// 传入 `level`（"error" | "warning" | "info"）或 `query` 时改为过滤模式：扫描末尾最多 8MB，
+/// 在 `matches` 中返回匹配行及前后 `context_lines`（默认 2）行上下文。
 #[tauri::command]
-fn openakita_service_log(workspace_id: String, tail_bytes: Option<u64>) -> Result<ServiceLogChunk, String> {
+fn openakita_service_log(
+    workspace_id: String,
+    tail_bytes: Option<u64>,
+    from_offset: Option<u64>,
+    clean: Option<bool>,
+    level: Option<String>,
+    query: Option<String>,
+    context_lines: Option<usize>,
+) -> Result<ServiceLogChunk, String> {
     let ws_dir = workspace_dir(&workspace_id);
     let log_path = ws_dir.join("logs").join("openakita-serve.log");
     let path_str = log_path.to_string_lossy().to_string();
@@ -2486,26 +7028,141 @@ fn openakita_service_log(workspace_id: String, tail_bytes: Option<u64>) -> Resul
             path: path_str,
             content: "".into(),
             truncated: false,
+            next_offset: 0,
+            file_len: 0,
+            reset: from_offset.map(|o| o > 0).unwrap_or(false),
+            matches: vec![],
         });
     }
+    if level.is_some() || query.is_some() {
+        return search_log_file(
+            &log_path,
+            level.as_deref(),
+            query.as_deref(),
+            context_lines.unwrap_or(2),
+            clean.unwrap_or(true),
+        );
+    }
 
     let mut f = std::fs::File::open(&log_path).map_err(|e| format!("open log failed: {e}"))?;
     let len = f.metadata().map_err(|e| format!("stat log failed: {e}"))?.len();
-    let start = len.saturating_sub(tail);
-    let truncated = start > 0;
+    // from_offset：只读取上次之后追加的字节（同样受 400KB 上限约束）；
+    // 文件比 offset 短说明被截断/轮转，从新文件开头读并标记 reset
+    let (start, reset) = match from_offset {
+        Some(offset) if offset > len => (0, true),
+        Some(offset) => (offset, false),
+        None => (len.saturating_sub(tail), false),
+    };
+    let end = if from_offset.is_some() { len.min(start + 400_000) } else { len };
+    let truncated = from_offset.is_none() && start > 0;
     f.seek(SeekFrom::Start(start))
         .map_err(|e| format!("seek log failed: {e}"))?;
     let mut buf = Vec::new();
-    f.read_to_end(&mut buf).map_err(|e| format!("read log failed: {e}"))?;
-    let content = String::from_utf8_lossy(&buf).to_string();
+    f.take(end - start)
+        .read_to_end(&mut buf)
+        .map_err(|e| format!("read log failed: {e}"))?;
+    let mut content = String::from_utf8_lossy(&buf).to_string();
+    if clean.unwrap_or(true) {
+        content = clean_log_text(&content);
+    }
 
     Ok(ServiceLogChunk {
         path: path_str,
         content,
         truncated,
+        next_offset: start + buf.len() as u64,
+        file_len: len,
+        reset,
+        matches: vec![],
     })
 }
 
+/// 各工作区日志 follow 线程的停止标记
+static LOG_FOLLOWERS: Lazy<Mutex<std::collections::HashMap<String, std::sync::Arc<AtomicBool>>>> =
+    Lazy::new(|| Mutex::new(std::collections::HashMap::new()));
+
+/// follow 模式的批量间隔：期间新增的完整行合并为一个事件发送
+const LOG_FOLLOW_BATCH_MS: u64 = 250;
+
+/// 实时跟随服务日志：从当前文件末尾开始，每 LOG_FOLLOW_BATCH_MS 把新追加的完整行
+/// 通过 `service-log-line` 事件推送（`{workspaceId, lines, reset}`），替代前端每秒重读尾部。
+/// 文件变短（被截断或轮转）时从头开始读取，并在事件中带上 `reset: true`。
+/// `clean`（默认 true）与 openakita_service_log 相同。
+/// 前端应先用 openakita_service_log 拉取已有内容，再调用本命令。
+#[tauri::command]
+fn openakita_service_log_follow(
+    app: tauri::AppHandle,
+    workspace_id: String,
+    clean: Option<bool>,
+) -> Result<(), String> {
+    let clean = clean.unwrap_or(true);
+    let stop = {
+        let mut followers = LOG_FOLLOWERS.lock().unwrap();
+        if followers.contains_key(&workspace_id) {
+            return Ok(());
+        }
+        let stop = std::sync::Arc::new(AtomicBool::new(false));
+        followers.insert(workspace_id.clone(), stop.clone());
+        stop
+    };
+    let log_path = workspace_dir(&workspace_id).join("logs").join("openakita-serve.log");
+    std::thread::spawn(move || {
+        let mut offset = fs::metadata(&log_path).map(|m| m.len()).unwrap_or(0);
+        let mut pending: Vec<u8> = Vec::new();
+        while !stop.load(Ordering::SeqCst) {
+            std::thread::sleep(Duration::from_millis(LOG_FOLLOW_BATCH_MS));
+            let len = fs::metadata(&log_path).map(|m| m.len()).unwrap_or(0);
+            let mut reset = false;
+            if len < offset {
+                offset = 0;
+                pending.clear();
+                reset = true;
+            }
+            if len > offset {
+                if let Ok(mut f) = fs::File::open(&log_path) {
+                    if f.seek(SeekFrom::Start(offset)).is_ok() {
+                        let mut buf = Vec::new();
+                        if let Ok(n) = f.read_to_end(&mut buf) {
+                            offset += n as u64;
+                            pending.extend_from_slice(&buf);
+                        }
+                    }
+                }
+            }
+            // 只发送完整的行，未以换行结尾的部分留到下一批
+            let lines: Vec<String> = match pending.iter().rposition(|b| *b == b'\n') {
+                Some(last_nl) => {
+                    let complete: Vec<u8> = pending.drain(..=last_nl).collect();
+                    let mut text = String::from_utf8_lossy(&complete).to_string();
+                    if clean {
+                        text = clean_log_text(&text);
+                    }
+                    text.lines()
+                        .map(|l| l.trim_end_matches('\r').to_string())
+                        .collect()
+                }
+                None => vec![],
+            };
+            if !lines.is_empty() || reset {
+                let _ = app.emit("service-log-line", serde_json::json!({
+                    "workspaceId": workspace_id,
+                    "lines": lines,
+                    "reset": reset,
+                }));
+            }
+        }
+    });
+    Ok(())
+}
+
+#[tauri::command]
+fn openakita_service_log_unfollow(workspace_id: String) -> Result<(), String> {
+    if let Some(stop) = LOG_FOLLOWERS.lock().unwrap().remove(&workspace_id) {
+        stop.store(true, Ordering::SeqCst);
+    }
+    Ok(())
+}
+
 #[tauri::command]
 fn autostart_is_enabled(app: tauri::AppHandle) -> Result<bool, String> {
     #[cfg(desktop)]
@@ -2520,25 +7177,112 @@ fn autostart_is_enabled(app: tauri::AppHandle) -> Result<bool, String> {
     }
 }
 
+/// 读取命令行参数值，支持 `--name value` 与 `--name=value`
+fn cli_arg_value(name: &str) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    let prefix = format!("{name}=");
+    for (i, a) in args.iter().enumerate() {
+        if a == name {
+            return args.get(i + 1).filter(|v| !v.starts_with("--")).cloned();
+        }
+        if let Some(v) = a.strip_prefix(&prefix) {
+            return Some(v.to_string());
+        }
+    }
+    None
+}
+
+/// 自启追加参数只允许 `--workspace <已存在的工作区 id>`。其余参数（--status / --headless /
+/// --first-run / --clean-env 等）会让每次登录都执行一次命令行模式或安装流程。
+fn validate_autostart_args(args: &[String], workspaces: &[WorkspaceMeta]) -> Result<(), String> {
+    match args {
+        [] => Ok(()),
+        [flag, id] if flag == "--workspace" => {
+            if workspaces.iter().any(|w| &w.id == id) {
+                Ok(())
+            } else {
+                Err(format!("工作区不存在: {id}"))
+            }
+        }
+        _ => Err(format!("自启参数只支持 --workspace <工作区 id>，收到: {}", args.join(" "))),
+    }
+}
+
+/// 开机自启的完整参数：固定的 --background + 用户配置的 autostart_args（不合法时忽略，如工作区已删除）
+fn autostart_launch_args() -> Vec<String> {
+    let state = read_state_file();
+    let mut args = vec!["--background".to_string()];
+    let extra = state.autostart_args.unwrap_or_default();
+    if validate_autostart_args(&extra, &state.workspaces).is_ok() {
+        args.extend(extra);
+    }
+    args
+}
+
+/// 按给定参数构建自启条目，应用名 / 路径与 tauri_plugin_autostart 的注册方式一致，
+/// 以便覆盖插件在进程启动时按旧参数注册的同名条目。
+#[cfg(desktop)]
+fn build_auto_launch(app: &tauri::AppHandle, args: &[String]) -> Result<auto_launch::AutoLaunch, String> {
+    let mut builder = auto_launch::AutoLaunchBuilder::new();
+    builder.set_app_name(&app.package_info().name);
+    builder.set_args(args);
+    let exe = std::env::current_exe().map_err(|e| format!("current_exe failed: {e}"))?;
+    #[cfg(target_os = "macos")]
+    {
+        builder.set_use_launch_agent(true);
+        let exe = exe.canonicalize().unwrap_or(exe);
+        builder.set_app_path(&exe.display().to_string());
+    }
+    #[cfg(target_os = "linux")]
+    {
+        let path = app
+            .env()
+            .appimage
+            .and_then(|p| p.to_str().map(|s| s.to_string()))
+            .unwrap_or_else(|| exe.display().to_string());
+        builder.set_app_path(&path);
+    }
+    #[cfg(windows)]
+    builder.set_app_path(&exe.display().to_string());
+    builder.build().map_err(|e| format!("autostart build failed: {e}"))
+}
+
 #[tauri::command]
-fn autostart_set_enabled(app: tauri::AppHandle, enabled: bool) -> Result<(), String> {
+fn get_autostart_args() -> Result<Vec<String>, String> {
+    Ok(read_state_file().autostart_args.unwrap_or_default())
+}
+
+/// 开启 / 关闭开机自启。`args` 传入时更新追加参数（如 ["--workspace", "<id>"]），开启时按新参数重新注册。
+#[tauri::command]
+fn autostart_set_enabled(app: tauri::AppHandle, enabled: bool, args: Option<Vec<String>>) -> Result<(), String> {
+    if let Some(ref extra) = args {
+        validate_autostart_args(extra, &read_state_file().workspaces)?;
+    }
     #[cfg(desktop)]
     {
-        let mgr = app.autolaunch();
+        // 同步持久化到 state file，用于下次启动时的自修复检查与插件初始化参数
+        let mut state = read_state_file();
+        if let Some(extra) = args {
+            state.autostart_args = Some(extra);
+        }
+        state.settings.auto_start_backend = Some(enabled);
+        let _ = write_state_file(&state);
+
         if enabled {
-            mgr.enable().map_err(|e| format!("autostart enable failed: {e}"))?;
+            // 插件的管理器持有进程启动时的参数，参数可能已变化，这里按最新参数重新注册
+            build_auto_launch(&app, &autostart_launch_args())?
+                .enable()
+                .map_err(|e| format!("autostart enable failed: {e}"))?;
         } else {
-            mgr.disable().map_err(|e| format!("autostart disable failed: {e}"))?;
+            app.autolaunch()
+                .disable()
+                .map_err(|e| format!("autostart disable failed: {e}"))?;
         }
-        // 同步持久化到 state file，用于下次启动时的自修复检查
-        let mut state = read_state_file();
-        state.auto_start_backend = Some(enabled);
-        let _ = write_state_file(&state);
         return Ok(());
     }
     #[cfg(not(desktop))]
     {
-        let _ = (app, enabled);
+        let _ = (app, enabled, args);
         Ok(())
     }
 }
@@ -2553,43 +7297,355 @@ fn is_backend_auto_starting() -> bool {
 #[tauri::command]
 fn get_auto_start_backend() -> Result<bool, String> {
     let state = read_state_file();
-    Ok(state.auto_start_backend.unwrap_or(false))
+    Ok(state.settings.auto_start_backend.unwrap_or(false))
 }
 
 #[tauri::command]
 fn set_auto_start_backend(enabled: bool) -> Result<(), String> {
     let mut state = read_state_file();
-    state.auto_start_backend = Some(enabled);
+    state.settings.auto_start_backend = Some(enabled);
     write_state_file(&state)
 }
 
 #[tauri::command]
 fn get_auto_update() -> Result<bool, String> {
     let state = read_state_file();
-    Ok(state.auto_update.unwrap_or(true))
+    Ok(state.settings.auto_update.unwrap_or(true))
 }
 
 #[tauri::command]
 fn set_auto_update(enabled: bool) -> Result<(), String> {
     let mut state = read_state_file();
-    state.auto_update = Some(enabled);
+    state.settings.auto_update = Some(enabled);
     write_state_file(&state)
 }
 
-/// 前端心跳检测到后端状态变化时调用，更新托盘 tooltip
-/// status: "alive" | "degraded" | "dead"
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UpdateInfo {
+    available: bool,
+    current_version: String,
+    latest_version: Option<String>,
+    notes: Option<String>,
+    download_url: Option<String>,
+}
+
+/// 按 Setup Center 中配置的代理构建 updater
+#[cfg(desktop)]
+fn build_updater(app: &tauri::AppHandle) -> Result<tauri_plugin_updater::Updater, String> {
+    use tauri_plugin_updater::UpdaterExt;
+    let mut builder = app.updater_builder();
+    if let Some(proxy) = configured_http_proxy() {
+        match tauri::Url::parse(&proxy) {
+            Ok(url) => builder = builder.proxy(url),
+            Err(e) => eprintln!("invalid http_proxy {proxy}: {e}"),
+        }
+    }
+    builder.build().map_err(|e| format!("updater init failed: {e}"))
+}
+
+/// 手动“检查更新”：只查询不安装，与 auto_update 开关无关。走 Setup Center 中配置的代理。
 #[tauri::command]
-fn set_tray_backend_status(app: tauri::AppHandle, status: String) -> Result<(), String> {
-    let tooltip = match status.as_str() {
+async fn check_for_update(app: tauri::AppHandle) -> Result<UpdateInfo, String> {
+    let current_version = app.package_info().version.to_string();
+    #[cfg(desktop)]
+    {
+        let update = build_updater(&app)?
+            .check()
+            .await
+            .map_err(|e| format!("check for update failed: {e}"))?;
+        Ok(match update {
+            Some(u) => UpdateInfo {
+                available: true,
+                current_version,
+                latest_version: Some(u.version.clone()),
+                notes: u.body.clone(),
+                download_url: Some(u.download_url.to_string()),
+            },
+            None => UpdateInfo {
+                available: false,
+                current_version,
+                latest_version: None,
+                notes: None,
+                download_url: None,
+            },
+        })
+    }
+    #[cfg(not(desktop))]
+    {
+        Err(format!("updater is not available on this platform (current version {current_version})"))
+    }
+}
+
+/// 下载进度事件的最小间隔，避免每个数据块都发一次事件
+const UPDATE_PROGRESS_EMIT_MS: u128 = 100;
+
+/// 下载并安装更新。过程中发送：
+/// - `app-update-status` { phase: checking | downloading | stopping-backends | installing | ready-to-restart | up-to-date, version? }
+/// - `app-update-progress` { downloaded, total }（total 为 null 表示服务器未返回长度）
+/// 返回安装的版本号；没有可用更新时返回 None。安装完成后需由前端调用 relaunch 重启。
+#[tauri::command]
+async fn download_and_install_update(app: tauri::AppHandle) -> Result<Option<String>, String> {
+    #[cfg(desktop)]
+    {
+        let emit_status = |phase: &str, version: Option<&str>| {
+            let _ = app.emit("app-update-status", serde_json::json!({ "phase": phase, "version": version }));
+        };
+        emit_status("checking", None);
+        let Some(update) = build_updater(&app)?
+            .check()
+            .await
+            .map_err(|e| format!("check for update failed: {e}"))?
+        else {
+            emit_status("up-to-date", None);
+            return Ok(None);
+        };
+        let version = update.version.clone();
+
+        emit_status("downloading", Some(&version));
+        let progress_app = app.clone();
+        let mut downloaded: u64 = 0;
+        let mut last_emit: Option<std::time::Instant> = None;
+        let bytes = update
+            .download(
+                move |chunk, total| {
+                    downloaded += chunk as u64;
+                    let done = total.map(|t| downloaded >= t).unwrap_or(false);
+                    if done || last_emit.map(|t| t.elapsed().as_millis() >= UPDATE_PROGRESS_EMIT_MS).unwrap_or(true) {
+                        last_emit = Some(std::time::Instant::now());
+                        let _ = progress_app.emit("app-update-progress", serde_json::json!({
+                            "downloaded": downloaded,
+                            "total": total,
+                        }));
+                    }
+                },
+                || {},
+            )
+            .await
+            .map_err(|e| format!("download update failed: {e}"))?;
+
+        // 安装会替换程序文件：先停掉本应用启动的后端，避免文件被占用
+        emit_status("stopping-backends", Some(&version));
+        let stopped = spawn_blocking_result({
+            let app = app.clone();
+            move || stop_tauri_backends(&app)
+        })
+        .await?;
+        if !stopped.is_empty() {
+            eprintln!("Stopped backends before update: {stopped:?}");
+        }
+
+        emit_status("installing", Some(&version));
+        update.install(bytes).map_err(|e| format!("install update failed: {e}"))?;
+        emit_status("ready-to-restart", Some(&version));
+        Ok(Some(version))
+    }
+    #[cfg(not(desktop))]
+    {
+        let _ = app;
+        Err("updater is not available on this platform".to_string())
+    }
+}
+
+/// 用户保存的 pip 镜像偏好（未设置时为 None）
+fn preferred_pip_mirror() -> Option<String> {
+    read_state_file()
+        .pip_mirror
+        .map(|m| m.trim().to_string())
+        .filter(|m| !m.is_empty())
+}
+
+#[tauri::command]
+fn get_pip_mirror() -> Result<Option<String>, String> {
+    Ok(preferred_pip_mirror())
+}
+
+/// 保存 pip 镜像偏好；传入 None 或空字符串表示清除（恢复默认阿里云）
+#[tauri::command]
+fn set_pip_mirror(mirror: Option<String>) -> Result<(), String> {
+    let mirror = mirror.map(|m| m.trim().to_string()).filter(|m| !m.is_empty());
+    if let Some(ref m) = mirror {
+        if !m.starts_with("http://") && !m.starts_with("https://") {
+            return Err(format!("pip mirror must be an http(s) URL: {m}"));
+        }
+    }
+    let mut state = read_state_file();
+    state.pip_mirror = mirror;
+    write_state_file(&state)
+}
+
+/// Playwright 浏览器默认下载镜像（国内 CDN）
+const PLAYWRIGHT_DOWNLOAD_MIRROR: &str = "https://npmmirror.com/mirrors/playwright";
+
+/// 当前生效的 Playwright 浏览器下载地址：用户配置优先，其次默认镜像
+fn playwright_download_host() -> String {
+    read_state_file()
+        .playwright_download_mirror
+        .map(|m| m.trim().trim_end_matches('/').to_string())
+        .filter(|m| !m.is_empty())
+        .unwrap_or_else(|| PLAYWRIGHT_DOWNLOAD_MIRROR.to_string())
+}
+
+#[tauri::command]
+fn get_playwright_download_mirror() -> Result<String, String> {
+    Ok(playwright_download_host())
+}
+
+/// 保存 Playwright 浏览器下载镜像；传入 None 或空字符串表示清除（恢复默认 npmmirror）
+#[tauri::command]
+fn set_playwright_download_mirror(mirror: Option<String>) -> Result<(), String> {
+    let mirror = mirror.map(|m| m.trim().to_string()).filter(|m| !m.is_empty());
+    if let Some(ref m) = mirror {
+        if !m.starts_with("http://") && !m.starts_with("https://") {
+            return Err(format!("playwright download mirror must be an http(s) URL: {m}"));
+        }
+    }
+    let mut state = read_state_file();
+    state.playwright_download_mirror = mirror;
+    write_state_file(&state)
+}
+
+#[tauri::command]
+fn get_auto_restart_backend() -> Result<bool, String> {
+    let state = read_state_file();
+    Ok(state.auto_restart_backend.unwrap_or(false))
+}
+
+#[tauri::command]
+fn set_auto_restart_backend(enabled: bool) -> Result<(), String> {
+    let mut state = read_state_file();
+    state.auto_restart_backend = Some(enabled);
+    write_state_file(&state)
+}
+
+/// state.json 由更新版本创建时返回提示文案（前端挂载时查询，启动事件可能早于前端监听）
+#[tauri::command]
+fn get_config_version_warning() -> Option<String> {
+    NEWER_CONFIG_VERSION.lock().unwrap().map(migrations::newer_version_message)
+}
+
+/// 列出配置迁移前生成的 state.json 备份（用于恢复界面）
+#[tauri::command]
+fn list_state_backups() -> Vec<migrations::BackupInfo> {
+    migrations::list_backups(&openakita_root_dir())
+}
+
+/// 用指定版本的迁移备份覆盖 state.json 并重新执行迁移
+#[tauri::command]
+fn restore_state_backup(version: u32) -> Result<(), String> {
+    migrations::restore_backup(&state_file_path(), &openakita_root_dir(), version)?;
+    reload_cached_settings();
+    Ok(())
+}
+
+#[tauri::command]
+fn get_max_backend_memory_mb() -> Result<Option<u64>, String> {
+    if !PROCESS_RESOURCES_SUPPORTED {
+        return Ok(None);
+    }
+    Ok(read_state_file().max_backend_memory_mb.filter(|&mb| mb > 0))
+}
+
+/// 设置后端内存上限（MB）；传 None 或 0 关闭内存守护。
+/// 当前平台无法读取进程内存（见 PROCESS_RESOURCES_SUPPORTED）时拒绝开启，避免守护静默失效。
+#[tauri::command]
+fn set_max_backend_memory_mb(mb: Option<u64>) -> Result<(), String> {
+    let mb = mb.filter(|&v| v > 0);
+    if mb.is_some() && !PROCESS_RESOURCES_SUPPORTED {
+        return Err("当前平台暂不支持读取进程内存占用，无法启用后端内存上限".into());
+    }
+    let mut state = read_state_file();
+    state.max_backend_memory_mb = mb;
+    write_state_file(&state)
+}
+
+#[tauri::command]
+fn get_heartbeat_thresholds() -> Result<HeartbeatThresholds, String> {
+    Ok(heartbeat_thresholds())
+}
+
+#[tauri::command]
+fn set_heartbeat_thresholds(stale_secs: u64, kill_secs: u64) -> Result<(), String> {
+    if stale_secs == 0 {
+        return Err("heartbeat_stale_secs 必须大于 0".into());
+    }
+    if kill_secs <= stale_secs {
+        return Err(format!("heartbeat_kill_secs（{kill_secs}）必须大于 heartbeat_stale_secs（{stale_secs}）"));
+    }
+    let mut state = read_state_file();
+    state.heartbeat_stale_secs = Some(stale_secs);
+    state.heartbeat_kill_secs = Some(kill_secs);
+    write_state_file(&state)?;
+    *HEARTBEAT_THRESHOLDS.lock().unwrap() = heartbeat_thresholds_from_state(&state);
+    Ok(())
+}
+
+#[tauri::command]
+fn get_graceful_stop_timeout_secs() -> Result<u64, String> {
+    Ok(graceful_stop_timeout_secs())
+}
+
+#[tauri::command]
+fn set_graceful_stop_timeout_secs(secs: u64) -> Result<(), String> {
+    if secs == 0 || secs > MAX_GRACEFUL_STOP_TIMEOUT_SECS {
+        return Err(format!("优雅退出等待时间需在 1~{MAX_GRACEFUL_STOP_TIMEOUT_SECS} 秒之间"));
+    }
+    let mut state = read_state_file();
+    state.graceful_stop_timeout_secs = Some(secs);
+    write_state_file(&state)?;
+    *GRACEFUL_STOP_TIMEOUT_SECS.lock().unwrap() = graceful_stop_timeout_from_state(&state);
+    Ok(())
+}
+
+#[tauri::command]
+fn get_kill_backend_with_app() -> Result<bool, String> {
+    let state = read_state_file();
+    Ok(state.kill_backend_with_app.unwrap_or(false))
+}
+
+/// 仅对之后启动的后端生效
+#[tauri::command]
+fn set_kill_backend_with_app(enabled: bool) -> Result<(), String> {
+    let mut state = read_state_file();
+    state.kill_backend_with_app = Some(enabled);
+    write_state_file(&state)
+}
+
+#[tauri::command]
+fn get_default_api_port() -> Result<u16, String> {
+    Ok(default_api_port())
+}
+
+#[tauri::command]
+fn set_default_api_port(port: u16) -> Result<(), String> {
+    if port == 0 {
+        return Err("port must be between 1 and 65535".into());
+    }
+    let mut state = read_state_file();
+    state.default_api_port = Some(port);
+    write_state_file(&state)?;
+    *DEFAULT_API_PORT_SETTING.lock().unwrap() = default_api_port_from_state(&state);
+    Ok(())
+}
+
+/// 按后端状态更新托盘 tooltip（不发送系统通知）
+fn set_tray_tooltip(app: &tauri::AppHandle, status: &str) {
+    let tooltip = match status {
         "alive" => "OpenAkita - Running",
         "degraded" => "OpenAkita - Backend Unresponsive",
         "dead" => "OpenAkita - Backend Stopped",
         _ => "OpenAkita",
     };
-    // 更新所有 tray icon 的 tooltip
     if let Some(tray) = app.tray_by_id("main_tray") {
         let _ = tray.set_tooltip(Some(tooltip));
     }
+}
+
+/// 前端心跳检测到后端状态变化时调用，更新托盘 tooltip
+/// status: "alive" | "degraded" | "dead"
+#[tauri::command]
+fn set_tray_backend_status(app: tauri::AppHandle, status: String) -> Result<(), String> {
+    set_tray_tooltip(&app, &status);
 
     // 后端死亡时发送系统通知
     if status == "dead" {
@@ -2620,13 +7676,153 @@ fn set_tray_backend_status(app: tauri::AppHandle, status: String) -> Result<(),
         }
         #[cfg(not(windows))]
         {
-            // macOS: use osascript
-            let _ = Command::new("osascript")
-                .args(["-e", "display notification \"Backend service has stopped\" with title \"OpenAkita\""])
-                .spawn();
+            // macOS: use osascript
+            let _ = Command::new("osascript")
+                .args(["-e", "display notification \"Backend service has stopped\" with title \"OpenAkita\""])
+                .spawn();
+        }
+    }
+    Ok(())
+}
+
+/// quit_cleanup 的结果
+struct QuitCleanupReport {
+    /// 仍在运行的进程（描述, PID）
+    residual: Vec<(String, u32)>,
+    /// 被强制结束的 PID（优雅关闭超时后的进程树、孤儿进程）
+    killed: Vec<u32>,
+}
+
+/// 退出前根据所有权标记停止后端：Tauri 启动的全部停掉，external（CLI）启动的保留，再兜底扫描孤儿进程。
+fn quit_cleanup() -> QuitCleanupReport {
+    let mut killed: Vec<u32> = Vec::new();
+    // 0. 解除看门狗，避免退出过程中把后端重新拉起；已接管的后端由下面第 2 步按 PID 文件停止
+    WATCHDOG_TARGETS.lock().unwrap().clear();
+    ADOPTED_SERVICES.lock().unwrap().clear();
+
+    // 0.5 先登记运行期间由 CLI 启动、尚无 PID 文件的后端，避免被下面的孤儿扫描误杀
+    tag_external_backends();
+
+    // 1. 先停 MANAGED_CHILDREN（Tauri 自己启动的所有进程）
+    {
+        let children: Vec<ManagedProcess> = MANAGED_CHILDREN
+            .lock()
+            .unwrap()
+            .drain()
+            .map(|(_, mp)| mp)
+            .collect();
+        for mut mp in children {
+            let port = read_workspace_api_port(&mp.workspace_id);
+            killed.extend(graceful_stop_pid(mp.pid, port, graceful_stop_timeout_secs(), None).unwrap_or_default());
+            if is_pid_running(mp.pid) {
+                let _ = mp.child.kill();
+                let _ = mp.child.wait();
+                killed.push(mp.pid);
+            }
+            let _ = fs::remove_file(service_pid_file(&mp.workspace_id));
+        }
+    }
+
+    // 2. 按 PID 文件逐一处理：tauri 启动及已接管（tauri-adopted）的停掉，external 启动的跳过
+    let entries = list_service_pids();
+    for ent in &entries {
+        if ent.started_by == STARTED_BY_EXTERNAL {
+            // CLI 启动的后端，不停止
+            continue;
+        }
+        let port = read_workspace_api_port(&ent.workspace_id);
+        killed.extend(stop_service_pid_entry(ent, port, graceful_stop_timeout_secs(), None).unwrap_or_default());
+    }
+
+    // 3. 兜底扫描孤儿进程（精确匹配），保留 external 后端
+    let external = external_backend_pids();
+    killed.extend(kill_openakita_orphans(&external));
+
+    std::thread::sleep(std::time::Duration::from_millis(600));
+
+    // 4. 最终确认
+    let still_pid = list_service_pids()
+        .into_iter()
+        .filter(|x| x.started_by != STARTED_BY_EXTERNAL && is_pid_running(x.pid))
+        .collect::<Vec<_>>();
+    let still_orphans = kill_openakita_orphans(&external);
+
+    let mut residual: Vec<(String, u32)> = still_pid
+        .iter()
+        .map(|x| (format!("{} (PID={})", x.workspace_id, x.pid), x.pid))
+        .collect();
+    for p in still_orphans {
+        residual.push((format!("orphan PID={}", p), p));
+    }
+    killed.sort_unstable();
+    killed.dedup();
+    QuitCleanupReport { residual, killed }
+}
+
+/// 停止所有由 Setup Center 启动（含已接管）的后端，跳过 external；返回已停止的 PID。
+/// 与托盘退出不同，这里不做孤儿扫描，也不清空看门狗（逐个按用户主动停止处理）。
+fn stop_tauri_backends(app: &tauri::AppHandle) -> Result<Vec<u32>, String> {
+    let mut targets: Vec<(String, u32)> = MANAGED_CHILDREN
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(ws, mp)| (ws.clone(), mp.pid))
+        .collect();
+    for ent in list_service_pids() {
+        if ent.started_by != STARTED_BY_EXTERNAL
+            && is_pid_running(ent.pid)
+            && !targets.iter().any(|(ws, _)| ws == &ent.workspace_id)
+        {
+            targets.push((ent.workspace_id, ent.pid));
         }
     }
-    Ok(())
+
+    let mut stopped = Vec::new();
+    let mut errors = Vec::new();
+    for (ws, pid) in targets {
+        match service_stop(app, &ws) {
+            Ok(_) if !is_pid_running(pid) => stopped.push(pid),
+            Ok(_) => errors.push(format!("{ws} (PID={pid}) 仍在运行")),
+            Err(e) => errors.push(format!("{ws} (PID={pid}): {e}")),
+        }
+    }
+    if errors.is_empty() {
+        Ok(stopped)
+    } else {
+        Err(format!("部分后端未能停止：{}", errors.join("；")))
+    }
+}
+
+/// 安装更新前调用：停止本应用启动的全部后端（external 保留），避免 Windows 上替换文件时“文件被占用”。
+/// 返回已停止的 PID 列表。
+#[tauri::command]
+async fn prepare_for_update(app: tauri::AppHandle) -> Result<Vec<u32>, String> {
+    spawn_blocking_result(move || stop_tauri_backends(&app)).await
+}
+
+/// 强制退出时无法停止的残留 PID，下次启动由 startup_reconcile 再次尝试清理
+fn residual_pids_file() -> PathBuf {
+    run_dir().join("residual-pids.json")
+}
+
+/// 强制退出：执行与托盘“退出”相同的清理，但无论是否有残留进程都退出应用，
+/// 用于进程确实无法结束（如权限不足）时避免用户被困在“后端仍在运行”的提示里。
+/// 残留 PID 记录到 residual-pids.json。
+#[tauri::command]
+fn force_quit(app: tauri::AppHandle) {
+    let QuitCleanupReport { residual, killed } = quit_cleanup();
+    if !killed.is_empty() {
+        eprintln!("force quit killed processes: {:?}", killed);
+    }
+    if !residual.is_empty() {
+        let pids: Vec<u32> = residual.iter().map(|(_, pid)| *pid).collect();
+        eprintln!("force quit with residual processes: {:?}", pids);
+        let _ = fs::create_dir_all(run_dir());
+        if let Ok(json) = serde_json::to_string(&pids) {
+            let _ = fs::write(residual_pids_file(), json);
+        }
+    }
+    app.exit(0);
 }
 
 fn setup_tray(app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error>> {
@@ -2637,8 +7833,9 @@ fn setup_tray(app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error>> {
     let show = MenuItem::with_id(app, "show", "显示窗口", true, None::<&str>)?;
     let hide = MenuItem::with_id(app, "hide", "隐藏窗口", true, None::<&str>)?;
     let quit = MenuItem::with_id(app, "quit", "退出（Quit）", true, None::<&str>)?;
+    let force_quit_item = MenuItem::with_id(app, "force_quit", "强制退出", true, None::<&str>)?;
 
-    let menu = Menu::with_items(app, &[&open_status, &show, &hide, &quit])?;
+    let menu = Menu::with_items(app, &[&open_status, &show, &hide, &quit, &force_quit_item])?;
 
     TrayIconBuilder::with_id("main_tray")
         .icon(app.default_window_icon().unwrap().clone())
@@ -2647,47 +7844,12 @@ fn setup_tray(app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error>> {
         .show_menu_on_left_click(false)
         .on_menu_event(move |app, event| match event.id.as_ref() {
             "quit" => {
-                // ── 退出前根据所有权标记决定是否停止后端 ──
-
-                // 1. 先停 MANAGED_CHILD（Tauri 自己启动的进程）
-                {
-                    let mut guard = MANAGED_CHILD.lock().unwrap();
-                    if let Some(mut mp) = guard.take() {
-                        let port = read_workspace_api_port(&mp.workspace_id);
-                        let _ = graceful_stop_pid(mp.pid, port);
-                        if is_pid_running(mp.pid) {
-                            let _ = mp.child.kill();
-                            let _ = mp.child.wait();
-                        }
-                        let _ = fs::remove_file(service_pid_file(&mp.workspace_id));
-                    }
-                }
-
-                // 2. 按 PID 文件逐一处理：tauri 启动的停掉，external 启动的跳过
-                let entries = list_service_pids();
-                for ent in &entries {
-                    if ent.started_by == "external" {
-                        // CLI 启动的后端，不停止
-                        continue;
-                    }
-                    let port = read_workspace_api_port(&ent.workspace_id);
-                    let _ = stop_service_pid_entry(ent, port);
-                }
-
-                // 3. 兜底扫描孤儿进程（精确匹配）
-                kill_openakita_orphans();
-
-                std::thread::sleep(std::time::Duration::from_millis(600));
-
-                // 4. 最终确认
-                let still_pid = list_service_pids()
-                    .into_iter()
-                    .filter(|x| x.started_by != "external" && is_pid_running(x.pid))
-                    .collect::<Vec<_>>();
-                let still_orphans = kill_openakita_orphans();
-
-                if still_pid.is_empty() && still_orphans.is_empty() {
+                let QuitCleanupReport { residual, killed } = quit_cleanup();
+                if residual.is_empty() {
                     // 全部清理干净，安全退出
+                    if !killed.is_empty() {
+                        eprintln!("quit killed processes: {:?}", killed);
+                    }
                     app.exit(0);
                 } else {
                     // 仍有残留：阻止退出，提示用户
@@ -2696,21 +7858,21 @@ fn setup_tray(app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error>> {
                         let _ = w.unminimize();
                         let _ = w.set_focus();
                     }
-                    let mut detail = Vec::new();
-                    for x in &still_pid {
-                        detail.push(format!("{} (PID={})", x.workspace_id, x.pid));
-                    }
-                    for p in &still_orphans {
-                        detail.push(format!("orphan PID={}", p));
-                    }
+                    let detail: Vec<String> = residual.iter().map(|(d, _)| d.clone()).collect();
                     let msg = format!(
                         "\u{9000}\u{51fa}\u{5931}\u{8d25}\u{ff1a}\u{540e}\u{53f0}\u{670d}\u{52a1}\u{4ecd}\u{5728}\u{8fd0}\u{884c}\u{3002}\n\n\u{8bf7}\u{5148}\u{5728}\u{201c}\u{72b6}\u{6001}\u{9762}\u{677f}\u{201d}\u{70b9}\u{51fb}\u{201c}\u{505c}\u{6b62}\u{670d}\u{52a1}\u{201d}\u{ff0c}\u{786e}\u{8ba4}\u{72b6}\u{6001}\u{53d8}\u{4e3a}\u{201c}\u{672a}\u{8fd0}\u{884c}\u{201d}\u{540e}\u{518d}\u{9000}\u{51fa}\u{3002}\n\n\u{4ecd}\u{5728}\u{8fd0}\u{884c}\u{7684}\u{8fdb}\u{7a0b}\u{ff1a}{}",
                         detail.join("; ")
                     );
                     let _ = app.emit("open_status", serde_json::json!({}));
-                    let _ = app.emit("quit_failed", serde_json::json!({ "message": msg }));
+                    // 附带扫描结果（含 external 后端），让用户看到具体还有哪些进程存活
+                    let _ = app.emit("quit_failed", serde_json::json!({
+                        "message": msg,
+                        "processes": scan_openakita_orphans(&[]),
+                        "killedPids": killed,
+                    }));
                 }
             }
+            "force_quit" => force_quit(app.clone()),
             "show" => {
                 if let Some(w) = app.get_webview_window("main") {
                     let _ = w.show();
@@ -2791,6 +7953,80 @@ fn workspace_read_file(workspace_id: String, relative_path: String) -> Result<St
     fs::read_to_string(&path).map_err(|e| format!("read failed: {e}"))
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct FileContent {
+    path: String,
+    content: String,
+    exists: bool,
+}
+
+/// 批量读取工作区文件（身份/人格编辑器一次性加载，避免多次 IPC 往返）。
+/// 单个文件不存在时返回 exists=false，不中断整个批次；路径非法仍直接报错。
+#[tauri::command]
+fn workspace_read_files(workspace_id: String, relative_paths: Vec<String>) -> Result<Vec<FileContent>, String> {
+    let mut out = Vec::with_capacity(relative_paths.len());
+    for rel in relative_paths {
+        let path = workspace_file_path(&workspace_id, &rel)?;
+        if !path.is_file() {
+            out.push(FileContent {
+                path: rel,
+                content: String::new(),
+                exists: false,
+            });
+            continue;
+        }
+        let content = fs::read_to_string(&path).map_err(|e| format!("read {rel} failed: {e}"))?;
+        out.push(FileContent {
+            path: rel,
+            content,
+            exists: true,
+        });
+    }
+    Ok(out)
+}
+
+/// 每个工作区一把写锁：串行化并发的 .env 读-改-写，避免后写覆盖先写
+static WORKSPACE_WRITE_LOCKS: Lazy<Mutex<std::collections::HashMap<String, std::sync::Arc<Mutex<()>>>>> =
+    Lazy::new(|| Mutex::new(std::collections::HashMap::new()));
+
+fn workspace_write_lock(workspace_id: &str) -> std::sync::Arc<Mutex<()>> {
+    let mut locks = WORKSPACE_WRITE_LOCKS.lock().unwrap();
+    locks
+        .entry(workspace_id.to_string())
+        .or_insert_with(|| std::sync::Arc::new(Mutex::new(())))
+        .clone()
+}
+
+/// write_file_atomic 使用的临时文件：同目录下的 `<name>.tmp`（同一文件系统内 rename 才是原子的）
+fn atomic_temp_path(path: &Path) -> Result<PathBuf, String> {
+    let file_name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| format!("invalid file path: {}", path.display()))?;
+    Ok(path.with_file_name(format!("{file_name}.tmp")))
+}
+
+/// 原子写文件：先写同目录下的 `<name>.tmp` 并 fsync，再 rename 覆盖原文件。
+/// 进程在写入中途被杀时原文件保持完整；rename 失败（部分文件系统/文件被占用）时回退到 copy + 删除临时文件。
+fn write_file_atomic(path: &Path, content: &[u8]) -> Result<(), String> {
+    let tmp_path = atomic_temp_path(path)?;
+    {
+        let mut f = fs::File::create(&tmp_path)
+            .map_err(|e| format!("create {} failed: {e}", tmp_path.display()))?;
+        f.write_all(content)
+            .map_err(|e| format!("write {} failed: {e}", tmp_path.display()))?;
+        f.sync_all()
+            .map_err(|e| format!("fsync {} failed: {e}", tmp_path.display()))?;
+    }
+    if fs::rename(&tmp_path, path).is_err() {
+        let copied = fs::copy(&tmp_path, path);
+        let _ = fs::remove_file(&tmp_path);
+        copied.map_err(|e| format!("replace {} failed: {e}", path.display()))?;
+    }
+    Ok(())
+}
+
 #[tauri::command]
 fn workspace_write_file(
     workspace_id: String,
@@ -2801,7 +8037,9 @@ fn workspace_write_file(
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent).map_err(|e| format!("create parent dir failed: {e}"))?;
     }
-    fs::write(&path, content).map_err(|e| format!("write failed: {e}"))
+    let lock = workspace_write_lock(&workspace_id);
+    let _guard = lock.lock().unwrap();
+    write_file_atomic(&path, content.as_bytes()).map_err(|e| format!("write failed: {e}"))
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -2811,53 +8049,103 @@ struct EnvEntry {
     value: String,
 }
 
+/// Setup Center 新增键所在分组的标题行
+const ENV_ADDED_SECTION_HEADER: &str = "# added by Setup Center";
+
+/// 按 entries 更新 .env 内容：
+/// - 已存在的键原地替换，保持原有顺序、注释和空行分组，保留值后的行内注释
+/// - 被更新的键若重复出现，以最后一次出现为准（与加载语义一致），删除之前的重复行
+/// - 空值表示删除该键（所有重复行一并删除）
+/// - 新键按传入顺序追加到 `# added by Setup Center` 分组下（分组不存在时自动创建）
 fn update_env_content(existing: &str, entries: &[EnvEntry]) -> String {
-    let mut updates = std::collections::BTreeMap::new();
-    let mut deletes = std::collections::BTreeSet::new();
+    // 保持插入顺序（不能用 BTreeMap，否则新键会被按字母序打乱）
+    let mut updates: Vec<(String, String)> = Vec::new();
+    let mut deletes: Vec<String> = Vec::new();
     for e in entries {
         if e.key.trim().is_empty() {
             continue;
         }
         let k = e.key.trim().to_string();
+        updates.retain(|(uk, _)| *uk != k);
+        deletes.retain(|dk| *dk != k);
         if e.value.trim().is_empty() {
             // 约定：空值表示删除该键（可选字段不填就不落盘）
-            deletes.insert(k);
+            deletes.push(k);
         } else {
-            updates.insert(k, e.value.clone());
+            updates.push((k, e.value.clone()));
         }
     }
     if updates.is_empty() && deletes.is_empty() {
         return existing.to_string();
     }
 
-    let mut out = Vec::new();
-    let mut seen = std::collections::BTreeSet::new();
-
-    for line in existing.lines() {
-        let trimmed = line.trim();
-        if trimmed.starts_with('#') || !trimmed.contains('=') {
-            out.push(line.to_string());
-            continue;
+    let parsed = parse_env_lines(existing);
+    // 每个键最后一次出现的位置
+    let mut last_index = std::collections::HashMap::new();
+    for (idx, entry) in parsed.iter().enumerate() {
+        if let EnvLine::Pair { key, .. } = entry {
+            last_index.insert(key.clone(), idx);
         }
-        let (k, _v) = trimmed.split_once('=').unwrap_or((trimmed, ""));
-        let key = k.trim();
-        if deletes.contains(key) {
-            // 删除该键：跳过该行
-            seen.insert(key.to_string());
+    }
+
+    let mut out: Vec<String> = Vec::new();
+    let mut seen = std::collections::HashSet::new();
+
+    for (idx, entry) in parsed.into_iter().enumerate() {
+        let (key, raw, comment) = match entry {
+            EnvLine::Raw(raw) => {
+                out.push(raw);
+                continue;
+            }
+            EnvLine::Pair { key, raw, comment, .. } => (key, raw, comment),
+        };
+        if deletes.contains(&key) {
+            // 删除该键：跳过该记录（多行值整体跳过）
+            seen.insert(key);
             continue;
         }
-        if let Some(new_val) = updates.get(key) {
-            out.push(format!("{key}={new_val}"));
-            seen.insert(key.to_string());
+        if let Some((_, new_val)) = updates.iter().find(|(uk, _)| *uk == key) {
+            if last_index.get(&key) == Some(&idx) {
+                out.push(format!(
+                    "{key}={}{}",
+                    format_env_value(new_val),
+                    comment.unwrap_or_default()
+                ));
+            }
+            // 非最后一次出现的重复键：丢弃
+            seen.insert(key);
         } else {
-            out.push(line.to_string());
+            out.push(raw);
         }
     }
 
-    // append missing keys
-    for (k, v) in updates {
-        if !seen.contains(&k) {
-            out.push(format!("{k}={v}"));
+    // 新键：追加到 Setup Center 分组末尾
+    let added: Vec<String> = updates
+        .iter()
+        .filter(|(k, _)| !seen.contains(k))
+        .map(|(k, v)| format!("{k}={}", format_env_value(v)))
+        .collect();
+    if !added.is_empty() {
+        match out.iter().position(|l| l.trim() == ENV_ADDED_SECTION_HEADER) {
+            Some(header_idx) => {
+                // 分组范围：标题之后直到第一个空行
+                let insert_at = out[header_idx + 1..]
+                    .iter()
+                    .position(|l| l.trim().is_empty())
+                    .map(|i| header_idx + 1 + i)
+                    .unwrap_or(out.len());
+                out.splice(insert_at..insert_at, added);
+            }
+            None => {
+                while out.last().map(|l| l.trim().is_empty()).unwrap_or(false) {
+                    out.pop();
+                }
+                if !out.is_empty() {
+                    out.push(String::new());
+                }
+                out.push(ENV_ADDED_SECTION_HEADER.to_string());
+                out.extend(added);
+            }
         }
     }
 
@@ -2874,9 +8162,320 @@ fn workspace_update_env(workspace_id: String, entries: Vec<EnvEntry>) -> Result<
     let dir = workspace_dir(&workspace_id);
     ensure_workspace_scaffold(&dir)?;
     let env_path = dir.join(".env");
+    let lock = workspace_write_lock(&workspace_id);
+    let _guard = lock.lock().unwrap();
     let existing = fs::read_to_string(&env_path).unwrap_or_default();
     let updated = update_env_content(&existing, &entries);
-    fs::write(&env_path, updated).map_err(|e| format!("write .env failed: {e}"))
+    write_file_atomic(&env_path, updated.as_bytes()).map_err(|e| format!("write .env failed: {e}"))
+}
+
+/// 读取工作区 .env 并按文件顺序返回键值对（跳过注释/空行）。
+/// 与 openakita_service_start 注入环境变量时使用同一个解析器（read_env_kv），避免前后端解析不一致。
+#[tauri::command]
+fn workspace_read_env(workspace_id: String) -> Result<Vec<EnvEntry>, String> {
+    let env_path = workspace_dir(&workspace_id).join(".env");
+    Ok(read_env_kv(&env_path)
+        .into_iter()
+        .map(|(key, value)| EnvEntry { key, value })
+        .collect())
+}
+
+/// 键名包含 KEY / TOKEN / SECRET / PASSWORD 的视为敏感值
+fn is_secret_env_key(key: &str) -> bool {
+    let upper = key.to_ascii_uppercase();
+    ["KEY", "TOKEN", "SECRET", "PASSWORD"]
+        .iter()
+        .any(|p| upper.contains(p))
+}
+
+/// 敏感值打码：保留前 3 位和后 4 位（如 `sk-****abcd`），过短的值整体打码
+fn mask_secret_value(value: &str) -> String {
+    let chars: Vec<char> = value.chars().collect();
+    if chars.len() <= 8 {
+        return "****".to_string();
+    }
+    let head: String = chars[..3].iter().collect();
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("{head}****{tail}")
+}
+
+/// 读取工作区 .env，敏感键的值打码后返回，避免 API Key 明文进入 webview（截图/日志泄露）。
+/// 写入仍走 workspace_update_env，需要明文时调用 workspace_reveal_env_value。
+#[tauri::command]
+fn workspace_read_env_masked(workspace_id: String) -> Result<Vec<EnvEntry>, String> {
+    Ok(workspace_read_env(workspace_id)?
+        .into_iter()
+        .map(|e| {
+            if is_secret_env_key(&e.key) {
+                EnvEntry {
+                    value: mask_secret_value(&e.value),
+                    key: e.key,
+                }
+            } else {
+                e
+            }
+        })
+        .collect())
+}
+
+/// 显式读取单个 .env 键的明文值（用户点击"显示"时调用）。键不存在返回 None。
+#[tauri::command]
+fn workspace_reveal_env_value(workspace_id: String, key: String) -> Result<Option<String>, String> {
+    Ok(workspace_read_env(workspace_id)?
+        .into_iter()
+        .rev()
+        .find(|e| e.key == key)
+        .map(|e| e.value))
+}
+
+fn llm_endpoints_path(workspace_id: &str) -> PathBuf {
+    workspace_dir(workspace_id).join("data").join("llm_endpoints.json")
+}
+
+/// 读取 llm_endpoints.json 顶层对象；文件不存在时返回空对象
+fn read_llm_endpoints_doc(path: &Path) -> Result<serde_json::Map<String, serde_json::Value>, String> {
+    let raw = match fs::read_to_string(path) {
+        Ok(s) => s,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(serde_json::Map::new()),
+        Err(e) => return Err(format!("read llm_endpoints.json failed: {e}")),
+    };
+    if raw.trim().is_empty() {
+        return Ok(serde_json::Map::new());
+    }
+    match serde_json::from_str::<serde_json::Value>(&raw) {
+        Ok(serde_json::Value::Object(map)) => Ok(map),
+        Ok(_) => Err("llm_endpoints.json 顶层必须是 JSON 对象".into()),
+        Err(e) => Err(format!("parse llm_endpoints.json failed: {e}")),
+    }
+}
+
+fn llm_endpoints_array(doc: &serde_json::Map<String, serde_json::Value>) -> Vec<serde_json::Value> {
+    doc.get("endpoints")
+        .and_then(|v| v.as_array())
+        .cloned()
+        .unwrap_or_default()
+}
+
+fn llm_endpoint_name(ep: &serde_json::Value) -> Option<&str> {
+    ep.get("name").and_then(|v| v.as_str())
+}
+
+/// 校验端点必填字段：name / base_url / api_type 均为非空字符串
+fn validate_llm_endpoint(ep: &serde_json::Value) -> Result<(), String> {
+    if !ep.is_object() {
+        return Err("endpoint 必须是 JSON 对象".into());
+    }
+    for field in ["name", "base_url", "api_type"] {
+        let ok = ep
+            .get(field)
+            .and_then(|v| v.as_str())
+            .map(|s| !s.trim().is_empty())
+            .unwrap_or(false);
+        if !ok {
+            return Err(format!("endpoint 缺少必填字段: {field}"));
+        }
+    }
+    Ok(())
+}
+
+/// 在工作区写锁内读取 → 修改 endpoints 数组 → 原子写回，返回修改后的数组。
+/// 顶层其他字段（settings / compiler_endpoints / _doc 等）原样保留。
+fn modify_llm_endpoints(
+    workspace_id: &str,
+    f: impl FnOnce(&mut Vec<serde_json::Value>) -> Result<(), String>,
+) -> Result<Vec<serde_json::Value>, String> {
+    ensure_workspace_scaffold(&workspace_dir(workspace_id))?;
+    let path = llm_endpoints_path(workspace_id);
+    let lock = workspace_write_lock(workspace_id);
+    let _guard = lock.lock().unwrap();
+    let mut doc = read_llm_endpoints_doc(&path)?;
+    let mut endpoints = llm_endpoints_array(&doc);
+    f(&mut endpoints)?;
+    doc.insert("endpoints".into(), serde_json::Value::Array(endpoints.clone()));
+    let mut out = serde_json::to_string_pretty(&serde_json::Value::Object(doc))
+        .map_err(|e| format!("serialize llm_endpoints.json failed: {e}"))?;
+    out.push('\n');
+    write_file_atomic(&path, out.as_bytes())
+        .map_err(|e| format!("write llm_endpoints.json failed: {e}"))?;
+    Ok(endpoints)
+}
+
+/// 按 data/llm_endpoints.json.example 的结构校验配置文件，返回可读的错误列表（为空表示通过）。
+/// 文件不存在时视为通过（ensure_workspace_scaffold 会补齐默认模板）。
+fn llm_endpoints_validation_errors(path: &Path) -> Vec<String> {
+    let raw = match fs::read_to_string(path) {
+        Ok(s) => s,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return vec![],
+        Err(e) => return vec![format!("无法读取文件: {e}")],
+    };
+    let doc: serde_json::Value = match serde_json::from_str(&raw) {
+        Ok(v) => v,
+        Err(e) => {
+            return vec![format!(
+                "JSON 解析失败（第 {} 行第 {} 列）: {e}",
+                e.line(),
+                e.column()
+            )]
+        }
+    };
+    let Some(doc) = doc.as_object() else {
+        return vec!["顶层必须是 JSON 对象".into()];
+    };
+
+    let mut errors = vec![];
+    if let Some(settings) = doc.get("settings") {
+        if !settings.is_object() {
+            errors.push("settings 必须是对象".into());
+        }
+    }
+    for list in ["endpoints", "compiler_endpoints", "stt_endpoints"] {
+        let Some(value) = doc.get(list) else {
+            continue;
+        };
+        let Some(items) = value.as_array() else {
+            errors.push(format!("{list} 必须是数组"));
+            continue;
+        };
+        let mut seen = std::collections::HashSet::new();
+        for (i, ep) in items.iter().enumerate() {
+            let at = match llm_endpoint_name(ep) {
+                Some(name) => format!("{list}[{i}] ({name})"),
+                None => format!("{list}[{i}]"),
+            };
+            if let Err(e) = validate_llm_endpoint(ep) {
+                errors.push(format!("{at}: {e}"));
+            }
+            if let Some(url) = ep.get("base_url").and_then(|v| v.as_str()) {
+                let url = url.trim();
+                if !url.is_empty() && !url.starts_with("http://") && !url.starts_with("https://") {
+                    errors.push(format!("{at}: base_url 必须以 http:// 或 https:// 开头: {url}"));
+                }
+            }
+            if let Some(model) = ep.get("model") {
+                if !model.is_string() {
+                    errors.push(format!("{at}: model 必须是字符串"));
+                }
+            }
+            for field in ["models", "capabilities"] {
+                if let Some(v) = ep.get(field) {
+                    let ok = v
+                        .as_array()
+                        .map(|a| a.iter().all(|x| x.is_string()))
+                        .unwrap_or(false);
+                    if !ok {
+                        errors.push(format!("{at}: {field} 必须是字符串数组"));
+                    }
+                }
+            }
+            if let Some(name) = llm_endpoint_name(ep) {
+                if !seen.insert(name.to_string()) {
+                    errors.push(format!("{at}: 端点名称重复: {name}"));
+                }
+            }
+        }
+    }
+    errors
+}
+
+/// 校验工作区的 data/llm_endpoints.json（不运行 Python），返回问题列表（为空表示通过）：
+/// 结构错误（同启动预检），以及 api_key 为空、api_key_env 指向的变量
+/// 在工作区 .env 中未配置或为空的端点（仅提示，不阻止启动）。
+/// 文件无法读取或 JSON 无法解析时返回 Err（解析失败带行列号），以便与“有问题项”区分。
+#[tauri::command]
+fn validate_llm_endpoints(workspace_id: String) -> Result<Vec<String>, String> {
+    let path = llm_endpoints_path(&workspace_id);
+    let raw = match fs::read_to_string(&path) {
+        Ok(s) => s,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(format!("read llm_endpoints.json failed: {e}")),
+    };
+    let doc: serde_json::Value = serde_json::from_str(&raw).map_err(|e| {
+        format!("llm_endpoints.json JSON 解析失败：第 {} 行第 {} 列: {e}", e.line(), e.column())
+    })?;
+    let mut problems = llm_endpoints_validation_errors(&path);
+    let env: std::collections::HashMap<String, String> =
+        read_env_kv(&workspace_dir(&workspace_id).join(".env")).into_iter().collect();
+    for list in ["endpoints", "compiler_endpoints", "stt_endpoints"] {
+        let Some(items) = doc.get(list).and_then(|v| v.as_array()) else {
+            continue;
+        };
+        for (i, ep) in items.iter().enumerate() {
+            let at = match llm_endpoint_name(ep) {
+                Some(name) => format!("{list}[{i}] ({name})"),
+                None => format!("{list}[{i}]"),
+            };
+            if let Some(key) = ep.get("api_key").and_then(|v| v.as_str()) {
+                if key.trim().is_empty() {
+                    problems.push(format!("{at}: api_key 为空"));
+                }
+            }
+            if let Some(var) = ep.get("api_key_env").and_then(|v| v.as_str()) {
+                let var = var.trim();
+                if var.is_empty() {
+                    problems.push(format!("{at}: api_key_env 为空"));
+                } else if env.get(var).map(|v| v.trim().is_empty()).unwrap_or(true) {
+                    problems.push(format!("{at}: api_key_env 指向的 {var} 在 .env 中未配置或为空"));
+                }
+            }
+        }
+    }
+    Ok(problems)
+}
+
+/// 同 validate_llm_endpoints，按工作区命名的别名
+#[tauri::command]
+fn workspace_validate_llm_endpoints(workspace_id: String) -> Result<Vec<String>, String> {
+    validate_llm_endpoints(workspace_id)
+}
+
+#[tauri::command]
+fn llm_endpoints_list(workspace_id: String) -> Result<Vec<serde_json::Value>, String> {
+    let doc = read_llm_endpoints_doc(&llm_endpoints_path(&workspace_id))?;
+    Ok(llm_endpoints_array(&doc))
+}
+
+/// 按 name 新增或更新端点。更新时逐字段合并，保留 Setup Center 不认识的字段。
+#[tauri::command]
+fn llm_endpoints_upsert(
+    workspace_id: String,
+    endpoint_json: String,
+) -> Result<Vec<serde_json::Value>, String> {
+    let incoming: serde_json::Value =
+        serde_json::from_str(&endpoint_json).map_err(|e| format!("parse endpoint failed: {e}"))?;
+    validate_llm_endpoint(&incoming)?;
+    let name = llm_endpoint_name(&incoming).unwrap_or_default().to_string();
+    modify_llm_endpoints(&workspace_id, |endpoints| {
+        match endpoints
+            .iter_mut()
+            .find(|ep| llm_endpoint_name(ep) == Some(name.as_str()))
+        {
+            Some(existing) => {
+                let mut merged = existing.as_object().cloned().unwrap_or_default();
+                if let serde_json::Value::Object(fields) = incoming {
+                    for (k, v) in fields {
+                        merged.insert(k, v);
+                    }
+                }
+                let merged = serde_json::Value::Object(merged);
+                validate_llm_endpoint(&merged)?;
+                *existing = merged;
+            }
+            None => endpoints.push(incoming),
+        }
+        Ok(())
+    })
+}
+
+#[tauri::command]
+fn llm_endpoints_delete(workspace_id: String, name: String) -> Result<Vec<serde_json::Value>, String> {
+    modify_llm_endpoints(&workspace_id, |endpoints| {
+        let before = endpoints.len();
+        endpoints.retain(|ep| llm_endpoint_name(ep) != Some(name.as_str()));
+        if endpoints.len() == before {
+            return Err(format!("endpoint not found: {name}"));
+        }
+        Ok(())
+    })
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -3115,6 +8714,101 @@ fn find_python_executable(root: &Path) -> Option<PathBuf> {
     None
 }
 
+/// 用户在 Setup Center 中配置的代理（未配置时为 None）
+fn configured_http_proxy() -> Option<String> {
+    read_state_file()
+        .http_proxy
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty())
+}
+
+/// 系统环境变量中的代理（HTTPS_PROXY / HTTP_PROXY / ALL_PROXY，大小写均可）
+fn env_http_proxy() -> Option<String> {
+    ["HTTPS_PROXY", "https_proxy", "HTTP_PROXY", "http_proxy", "ALL_PROXY", "all_proxy"]
+        .iter()
+        .filter_map(|k| std::env::var(k).ok())
+        .map(|v| v.trim().to_string())
+        .find(|v| !v.is_empty())
+}
+
+/// 带超时和代理配置的 blocking ClientBuilder，调用方可继续追加 user_agent 等设置。
+/// 配置了 http_proxy 时对所有请求使用该代理（本机地址除外）；否则沿用 reqwest 默认行为，
+/// 即读取 HTTP_PROXY / HTTPS_PROXY / NO_PROXY 环境变量。
+fn http_client_builder(timeout: Duration) -> reqwest::blocking::ClientBuilder {
+    let mut builder = reqwest::blocking::Client::builder().timeout(timeout);
+    if let Some(proxy) = configured_http_proxy() {
+        match reqwest::Proxy::all(&proxy) {
+            Ok(p) => {
+                builder = builder.proxy(p.no_proxy(reqwest::NoProxy::from_string("localhost,127.0.0.1,::1")));
+            }
+            Err(e) => eprintln!("invalid http_proxy {proxy}: {e}"),
+        }
+    }
+    builder
+}
+
+/// 异步版本的 http_client_builder（供 async 命令使用）
+fn async_http_client_builder() -> reqwest::ClientBuilder {
+    let mut builder = reqwest::Client::builder();
+    if let Some(proxy) = configured_http_proxy() {
+        match reqwest::Proxy::all(&proxy) {
+            Ok(p) => {
+                builder = builder.proxy(p.no_proxy(reqwest::NoProxy::from_string("localhost,127.0.0.1,::1")));
+            }
+            Err(e) => eprintln!("invalid http_proxy {proxy}: {e}"),
+        }
+    }
+    builder
+}
+
+/// 带超时、代理和默认 User-Agent 的 blocking HTTP 客户端
+fn build_http_client(timeout: Duration) -> Result<reqwest::blocking::Client, String> {
+    http_client_builder(timeout)
+        .user_agent("openakita-setup-center")
+        .build()
+        .map_err(|e| format!("http client build failed: {e}"))
+}
+
+/// 当前生效的代理：Setup Center 配置优先，其次环境变量
+#[tauri::command]
+fn get_http_proxy() -> Result<Option<String>, String> {
+    Ok(configured_http_proxy().or_else(env_http_proxy))
+}
+
+/// 设置出站代理；传入 None 或空字符串表示清除（回退到环境变量）
+#[tauri::command]
+fn set_http_proxy(proxy: Option<String>) -> Result<(), String> {
+    let proxy = proxy.map(|p| p.trim().to_string()).filter(|p| !p.is_empty());
+    if let Some(ref p) = proxy {
+        reqwest::Proxy::all(p).map_err(|e| format!("invalid proxy URL {p}: {e}"))?;
+    }
+    let mut state = read_state_file();
+    state.http_proxy = proxy;
+    write_state_file(&state)
+}
+
+/// 自定义的启动失败识别规则（见 classify_startup_failure）
+#[tauri::command]
+fn get_startup_failure_patterns() -> Result<Vec<StartupFailurePattern>, String> {
+    Ok(read_state_file().startup_failure_patterns.unwrap_or_default())
+}
+
+/// 设置自定义的启动失败识别规则；传入空列表表示只用内置规则
+#[tauri::command]
+fn set_startup_failure_patterns(patterns: Vec<StartupFailurePattern>) -> Result<(), String> {
+    for p in &patterns {
+        if p.class.trim().is_empty() {
+            return Err("startup failure pattern: class 不能为空".into());
+        }
+        if p.contains.iter().all(|n| n.trim().is_empty()) {
+            return Err(format!("startup failure pattern {}: contains 至少需要一个非空子串", p.class));
+        }
+    }
+    let mut state = read_state_file();
+    state.startup_failure_patterns = (!patterns.is_empty()).then_some(patterns);
+    write_state_file(&state)
+}
+
 /// 带重试的 HTTP GET，依次尝试原始 URL 和镜像 URL
 fn get_with_mirrors(client: &reqwest::blocking::Client, urls: &[&str]) -> Result<reqwest::blocking::Response, String> {
     let mut last_err = String::new();
@@ -3130,6 +8824,84 @@ fn get_with_mirrors(client: &reqwest::blocking::Client, urls: &[&str]) -> Result
     Err(last_err)
 }
 
+/// OpenAkita 在 GitHub 上的仓库（owner/repo）
+const OPENAKITA_GITHUB_REPO: &str = "openakita/openakita";
+
+/// 获取 OpenAkita 某个版本（默认最新）的 GitHub Release 说明（Markdown），
+/// 供更新提示界面在用户确认更新前展示。与 get_with_mirrors 一样依次尝试代理镜像和直连 API，
+/// 但会单独识别 GitHub API 限流（403/429 + x-ratelimit-remaining=0），给出明确提示。
+#[tauri::command]
+async fn fetch_release_notes(version: Option<String>) -> Result<String, String> {
+    spawn_blocking_result(move || {
+        let (path, label) = match version.as_deref().map(str::trim).filter(|v| !v.is_empty()) {
+            Some(v) => {
+                let tag = if v.starts_with('v') { v.to_string() } else { format!("v{v}") };
+                (format!("releases/tags/{tag}"), tag)
+            }
+            None => ("releases/latest".to_string(), "latest".to_string()),
+        };
+        let api = format!("https://api.github.com/repos/{OPENAKITA_GITHUB_REPO}/{path}");
+        let urls = [format!("https://ghp.ci/{api}"), api];
+
+        let client = build_http_client(Duration::from_secs(15))?;
+
+        let mut last_err = String::new();
+        let mut rate_limit_reset: Option<u64> = None;
+        for url in &urls {
+            let resp = match client
+                .get(url)
+                .header("Accept", "application/vnd.github+json")
+                .send()
+            {
+                Ok(r) => r,
+                Err(e) => {
+                    last_err = format!("{e}");
+                    continue;
+                }
+            };
+            let status = resp.status();
+            if status.is_success() {
+                let body: serde_json::Value = resp
+                    .json()
+                    .map_err(|e| format!("parse github release failed: {e}"))?;
+                return Ok(body
+                    .get("body")
+                    .and_then(|b| b.as_str())
+                    .unwrap_or_default()
+                    .to_string());
+            }
+            let header = |name: &str| {
+                resp.headers()
+                    .get(name)
+                    .and_then(|v| v.to_str().ok())
+                    .map(|v| v.to_string())
+            };
+            let exhausted = header("x-ratelimit-remaining").as_deref() == Some("0");
+            if status.as_u16() == 429 || (status.as_u16() == 403 && exhausted) {
+                rate_limit_reset = header("x-ratelimit-reset")
+                    .and_then(|v| v.parse::<u64>().ok())
+                    .or(Some(0));
+                last_err = format!("rate limited ({status})");
+            } else if status.as_u16() == 404 {
+                last_err = format!("release {label} not found");
+            } else {
+                last_err = format!("HTTP {status}");
+            }
+        }
+
+        if let Some(reset) = rate_limit_reset {
+            let wait_min = reset.saturating_sub(now_epoch_secs()).div_ceil(60);
+            return Err(if wait_min > 0 {
+                format!("GitHub API 请求次数已达上限（限流），请约 {wait_min} 分钟后再试")
+            } else {
+                "GitHub API 请求次数已达上限（限流），请稍后再试".to_string()
+            });
+        }
+        Err(format!("fetch release notes failed (all mirrors): {last_err}"))
+    })
+    .await
+}
+
 /// 向 onboarding 日志文件追加一行（仅用于内部进度，忽略错误）
 fn append_to_onboarding_log(log_path: Option<&Path>, line: &str) {
     let Some(path) = log_path else { return };
@@ -3153,10 +8925,9 @@ fn install_embedded_python_sync(
     let triple = target_triple_hint()?;
     let log_path = log_path.as_deref();
 
-    let client = reqwest::blocking::Client::builder()
+    let client = http_client_builder(Duration::from_secs(120))
         .user_agent("openakita-setup-center")
         .connect_timeout(Duration::from_secs(10))
-        .timeout(Duration::from_secs(120))
         .build()
         .map_err(|e| format!("http client build failed: {e}"))?;
 
@@ -3215,10 +8986,9 @@ fn install_embedded_python_sync(
     // 安装包为 python-build-standalone 的 install_only 归档，典型 20–50 MB，慢网下可能较久
     if !archive_path.exists() {
         append_to_onboarding_log(log_path, "[嵌入式 Python] 开始下载安装包（约 20–50 MB）...");
-        let download_client = reqwest::blocking::Client::builder()
+        let download_client = http_client_builder(Duration::from_secs(3600))
             .user_agent("openakita-setup-center")
             .connect_timeout(Duration::from_secs(15))
-            .timeout(Duration::from_secs(3600))
             .build()
             .map_err(|e| format!("download client build failed: {e}"))?;
         let dl_mirror_ghp = format!("https://ghp.ci/{}", &asset.browser_download_url);
@@ -3416,6 +9186,15 @@ fn venv_pythonw_path(venv_dir: &str) -> PathBuf {
     }
 }
 
+/// package_spec 是否已指定版本：PEP 440 版本约束（==、>=、~= 等）、直接引用（pkg @ url）或本地 wheel 文件
+fn package_spec_has_version(spec: &str) -> bool {
+    let spec = spec.trim();
+    spec.ends_with(".whl")
+        || ["==", ">=", "<=", "~=", "!=", "<", ">", "@"]
+            .iter()
+            .any(|op| spec.contains(op))
+}
+
 #[tauri::command]
 async fn pip_install(
     app: tauri::AppHandle,
@@ -3550,7 +9329,8 @@ async fn pip_install(
             Ok(status)
         }
 
-        // 国内镜像兜底：前端未传 index_url 时默认使用阿里云
+        // 国内镜像兜底：前端未传 index_url 时先用用户保存的偏好，再默认使用阿里云
+        let index_url = index_url.or_else(preferred_pip_mirror);
         let effective_index = index_url.as_deref()
             .unwrap_or("https://mirrors.aliyun.com/pypi/simple/");
         let effective_host = effective_index
@@ -3561,6 +9341,7 @@ async fn pip_install(
         emit_stage("升级 pip（best-effort）", 40);
         let mut up = Command::new(&py);
         apply_no_window(&mut up);
+        apply_proxy_env(&mut up);
         up.env("PYTHONUTF8", "1");
         up.env("PYTHONIOENCODING", "utf-8");
         up.args(["-m", "pip", "install", "-U", "pip", "setuptools", "wheel"]);
@@ -3573,9 +9354,15 @@ async fn pip_install(
         emit_stage("安装 openakita（pip）", 70);
         let mut c = Command::new(&py);
         apply_no_window(&mut c);
+        apply_proxy_env(&mut c);
         c.env("PYTHONUTF8", "1");
         c.env("PYTHONIOENCODING", "utf-8");
-        c.args(["-m", "pip", "install", "-U", &package_spec]);
+        // 指定了版本（如 openakita==1.2.3）时不加 -U，让 pip 精确安装所请求的版本（支持回退/锁定）
+        c.args(["-m", "pip", "install"]);
+        if !package_spec_has_version(&package_spec) {
+            c.arg("-U");
+        }
+        c.arg(&package_spec);
         c.args(["-i", effective_index]);
         if !effective_host.is_empty() {
             c.args(["--trusted-host", effective_host]);
@@ -3747,15 +9534,9 @@ async fn openakita_list_models(
 async fn openakita_version(venv_dir: String) -> Result<String, String> {
     spawn_blocking_result(move || {
         // 1. 尝试从打包后端读取 _bundled_version.txt（最快且无需 Python）
-        let bundled = bundled_backend_dir();
-        let version_file = bundled.join("_internal").join("openakita").join("_bundled_version.txt");
-        if version_file.exists() {
-            if let Ok(v) = fs::read_to_string(&version_file) {
-                let v = v.trim().to_string();
-                if !v.is_empty() {
-                    return Ok(v);
-                }
-            }
+        if let Some(v) = read_bundled_version() {
+            *BACKEND_VERSION.lock().unwrap() = Some(v.clone());
+            return Ok(v);
         }
 
         // 2. 使用 resolve_python 查找可用 Python 并获取版本
@@ -3777,11 +9558,41 @@ async fn openakita_version(venv_dir: String) -> Result<String, String> {
             let stdout = String::from_utf8_lossy(&out.stdout).to_string();
             return Err(format!("python failed: {}\nstdout:\n{}\nstderr:\n{}", out.status, stdout, stderr));
         }
-        Ok(String::from_utf8_lossy(&out.stdout).trim().to_string())
+        let version = String::from_utf8_lossy(&out.stdout).trim().to_string();
+        if !version.is_empty() {
+            *BACKEND_VERSION.lock().unwrap() = Some(version.clone());
+        }
+        Ok(version)
     })
     .await
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct PreflightFinding {
+    /// "error" | "warning"
+    severity: String,
+    key: String,
+    message: String,
+}
+
+fn run_preflight(venv_dir: &str, workspace_id: &str) -> Result<Vec<PreflightFinding>, String> {
+    let wd_str = workspace_dir(workspace_id).to_string_lossy().to_string();
+    let out = run_python_module_json(
+        venv_dir,
+        "openakita.setup_center.bridge",
+        &["preflight", "--workspace-dir", &wd_str],
+        &[],
+    )?;
+    serde_json::from_str(&out).map_err(|e| format!("parse preflight output failed: {e}"))
+}
+
+/// 通过 Python bridge 的 preflight 子命令做启动前配置检查（缺少 API Key、未知模型、无效端点等），不联网
+#[tauri::command]
+async fn openakita_preflight(venv_dir: String, workspace_id: String) -> Result<Vec<PreflightFinding>, String> {
+    spawn_blocking_result(move || run_preflight(&venv_dir, &workspace_id)).await
+}
+
 /// Health check LLM endpoints via Python bridge.
 /// Returns JSON array of health results.
 #[tauri::command]
@@ -3934,89 +9745,315 @@ async fn openakita_get_skill_config(
     .await
 }
 
+/// PEP 440 版本排序键：字段按声明顺序比较（epoch → release → pre → post → dev）
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Pep440Key {
+    epoch: u64,
+    release: Vec<u64>,
+    pre: (i8, u64),
+    post: i64,
+    dev: i64,
+}
+
+/// 解析 PEP 440 版本号，返回 (排序键, 是否为预发布/开发版)。无法解析的版本返回 None。
+/// 支持 epoch（1!）、预发布（a/b/rc 及 alpha/beta/c/pre/preview 别名）、post（.post1 / -1）、dev 段，忽略 +local。
+fn parse_pep440(version: &str) -> Option<(Pep440Key, bool)> {
+    let lower = version.trim().to_ascii_lowercase();
+    let s = lower.strip_prefix('v').unwrap_or(&lower);
+    // 本地版本标签（+xxx）不参与排序
+    let s = s.split('+').next().unwrap_or("");
+    let (epoch, s) = match s.split_once('!') {
+        Some((e, rest)) => (e.parse::<u64>().ok()?, rest),
+        None => (0, s),
+    };
+
+    fn take_digits(s: &str) -> (Option<u64>, &str) {
+        let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        if end == 0 {
+            (None, s)
+        } else {
+            (s[..end].parse().ok(), &s[end..])
+        }
+    }
+    fn skip_sep(s: &str) -> &str {
+        s.strip_prefix(['.', '_', '-']).unwrap_or(s)
+    }
+
+    let mut release = vec![];
+    let mut rest = s;
+    loop {
+        let (n, r) = take_digits(rest);
+        release.push(n?);
+        rest = r;
+        match rest.strip_prefix('.') {
+            Some(r) if r.starts_with(|c: char| c.is_ascii_digit()) => rest = r,
+            _ => break,
+        }
+    }
+    while release.len() > 1 && release.last() == Some(&0) {
+        release.pop();
+    }
+
+    let mut pre = None;
+    let mut post = None;
+    let mut dev = None;
+
+    let r = skip_sep(rest);
+    // 长的别名放前面，避免 "alpha" 被 "a" 先匹配
+    for (tag, phase) in [
+        ("alpha", 0),
+        ("beta", 1),
+        ("preview", 2),
+        ("pre", 2),
+        ("rc", 2),
+        ("a", 0),
+        ("b", 1),
+        ("c", 2),
+    ] {
+        if let Some(after) = r.strip_prefix(tag) {
+            let (n, after) = take_digits(skip_sep(after));
+            pre = Some((phase, n.unwrap_or(0)));
+            rest = after;
+            break;
+        }
+    }
+
+    // 隐式 post：1.0-1
+    if let Some(after) = rest
+        .strip_prefix('-')
+        .filter(|a| a.starts_with(|c: char| c.is_ascii_digit()))
+    {
+        let (n, after) = take_digits(after);
+        post = n;
+        rest = after;
+    } else {
+        let r = skip_sep(rest);
+        for tag in ["post", "rev", "r"] {
+            if let Some(after) = r.strip_prefix(tag) {
+                let (n, after) = take_digits(skip_sep(after));
+                post = Some(n.unwrap_or(0));
+                rest = after;
+                break;
+            }
+        }
+    }
+
+    let r = skip_sep(rest);
+    if let Some(after) = r.strip_prefix("dev") {
+        let (n, after) = take_digits(skip_sep(after));
+        dev = Some(n.unwrap_or(0));
+        rest = after;
+    }
+
+    if !rest.is_empty() {
+        return None;
+    }
+
+    let is_prerelease = pre.is_some() || dev.is_some();
+    let pre_key = match (pre, post, dev) {
+        (Some(p), _, _) => p,
+        // 仅有 dev 的版本（1.0.dev1）排在所有预发布之前
+        (None, None, Some(_)) => (-1, 0),
+        (None, _, _) => (3, 0),
+    };
+    Some((
+        Pep440Key {
+            epoch,
+            release,
+            pre: pre_key,
+            post: post.map(|n| n as i64).unwrap_or(-1),
+            dev: dev.map(|n| n as i64).unwrap_or(i64::MAX),
+        },
+        is_prerelease,
+    ))
+}
+
 /// Fetch available versions of a package from PyPI JSON API.
 /// Returns JSON array of version strings, newest first.
 #[tauri::command]
-async fn fetch_pypi_versions(package: String, index_url: Option<String>) -> Result<String, String> {
+async fn fetch_pypi_versions(
+    package: String,
+    index_url: Option<String>,
+    include_prerelease: Option<bool>,
+) -> Result<String, String> {
     spawn_blocking_result(move || {
-        // 构建候选 URL 列表，多源回退
-        // 注意：并非所有 PyPI 镜像都支持 /pypi/<pkg>/json API（阿里云不支持）
-        // 因此即使用户指定了 index_url，也要带上已验证可用的回退源
-        let mut urls: Vec<String> = Vec::new();
-        if let Some(ref idx) = index_url {
-            let root = idx
-                .trim_end_matches('/')
-                .trim_end_matches("/simple")
-                .trim_end_matches("/simple/");
-            urls.push(format!("{}/pypi/{}/json", root, package));
-        }
-        // 清华（已验证支持 JSON API）和官方 PyPI 作为回退
-        let tuna_url = format!("https://pypi.tuna.tsinghua.edu.cn/pypi/{}/json", package);
-        let pypi_url = format!("https://pypi.org/pypi/{}/json", package);
-        if !urls.iter().any(|u| u.contains("tuna.tsinghua")) {
-            urls.push(tuna_url);
-        }
-        if !urls.iter().any(|u| u.contains("pypi.org")) {
-            urls.push(pypi_url);
-        }
-
-        let client = reqwest::blocking::Client::builder()
-            .timeout(std::time::Duration::from_secs(10))
-            .user_agent("openakita-setup-center")
-            .build()
-            .map_err(|e| format!("HTTP client error: {e}"))?;
+        let versions = pypi_versions_sync(&package, index_url.as_deref(), include_prerelease.unwrap_or(false))?;
+        Ok(serde_json::to_string(&versions).unwrap_or_else(|_| "[]".into()))
+    })
+    .await
+}
 
-        // 多源自动回退
-        let mut last_err = String::new();
-        let mut resp_ok = None;
-        for url in &urls {
-            match client.get(url).send() {
-                Ok(r) => match r.error_for_status() {
-                    Ok(r) => { resp_ok = Some(r); break; }
-                    Err(e) => { last_err = format!("fetch PyPI versions failed ({}): {}", url, e); }
-                },
+/// 查询 PyPI JSON API，返回按 PEP 440 从新到旧排序的版本号（多源回退，见 fetch_pypi_versions）
+fn pypi_versions_sync(package: &str, index_url: Option<&str>, include_prerelease: bool) -> Result<Vec<String>, String> {
+    // 构建候选 URL 列表，多源回退
+    // 注意：并非所有 PyPI 镜像都支持 /pypi/<pkg>/json API（阿里云不支持）
+    // 因此即使用户指定了 index_url，也要带上已验证可用的回退源
+    let mut urls: Vec<String> = Vec::new();
+    if let Some(idx) = index_url {
+        let root = idx
+            .trim_end_matches('/')
+            .trim_end_matches("/simple")
+            .trim_end_matches("/simple/");
+        urls.push(format!("{}/pypi/{}/json", root, package));
+    }
+    // 清华（已验证支持 JSON API）和官方 PyPI 作为回退
+    let tuna_url = format!("https://pypi.tuna.tsinghua.edu.cn/pypi/{}/json", package);
+    let pypi_url = format!("https://pypi.org/pypi/{}/json", package);
+    if !urls.iter().any(|u| u.contains("tuna.tsinghua")) {
+        urls.push(tuna_url);
+    }
+    if !urls.iter().any(|u| u.contains("pypi.org")) {
+        urls.push(pypi_url);
+    }
+
+    let client = build_http_client(Duration::from_secs(10))?;
+
+    // 多源自动回退
+    let mut last_err = String::new();
+    let mut resp_ok = None;
+    for url in &urls {
+        match client.get(url).send() {
+            Ok(r) => match r.error_for_status() {
+                Ok(r) => { resp_ok = Some(r); break; }
                 Err(e) => { last_err = format!("fetch PyPI versions failed ({}): {}", url, e); }
-            }
+            },
+            Err(e) => { last_err = format!("fetch PyPI versions failed ({}): {}", url, e); }
         }
-        let resp = resp_ok.ok_or(last_err)?;
+    }
+    let resp = resp_ok.ok_or(last_err)?;
 
-        let body: serde_json::Value = resp
-            .json()
-            .map_err(|e| format!("parse PyPI JSON failed: {e}"))?;
-
-        // PyPI JSON API: { "releases": { "1.0.0": [...], "1.2.3": [...], ... } }
-        let releases = body
-            .get("releases")
-            .and_then(|v| v.as_object())
-            .ok_or_else(|| "unexpected PyPI JSON format: missing 'releases'".to_string())?;
-
-        let mut versions: Vec<String> = releases
-            .keys()
-            .filter(|v| {
-                // Skip pre-release / dev versions with letters like "a", "b", "rc", "dev"
-                // unless the version contains only dots and digits
-                let v_lower = v.to_lowercase();
-                !v_lower.contains("dev") && !v_lower.contains("alpha")
-            })
-            .cloned()
-            .collect();
+    let body: serde_json::Value = resp
+        .json()
+        .map_err(|e| format!("parse PyPI JSON failed: {e}"))?;
+
+    // PyPI JSON API: { "releases": { "1.0.0": [...], "1.2.3": [...], ... } }
+    let releases = body
+        .get("releases")
+        .and_then(|v| v.as_object())
+        .ok_or_else(|| "unexpected PyPI JSON format: missing 'releases'".to_string())?;
+
+    // 按 PEP 440 解析并从新到旧排序；默认隐藏预发布/开发版（a/b/rc/dev），
+    // include_prerelease=true 时供高级用户选择 rc 等版本。无法解析的版本号直接跳过。
+    let mut parsed: Vec<(Pep440Key, &String)> = releases
+        .keys()
+        .filter_map(|v| parse_pep440(v).map(|(key, pre)| (key, pre, v)))
+        .filter(|(_, pre, _)| include_prerelease || !*pre)
+        .map(|(key, _, v)| (key, v))
+        .collect();
+    parsed.sort_by(|a, b| b.0.cmp(&a.0));
+    Ok(parsed.into_iter().map(|(_, v)| v.clone()).collect())
+}
 
-        // Sort by semver-ish descending (newest first).
-        // Use a simple tuple-based comparison: split on '.', parse each part.
-        versions.sort_by(|a, b| {
-            let parse = |s: &str| -> Vec<i64> {
-                s.split('.')
-                    .map(|p| {
-                        // strip pre-release suffixes for sorting: "1a0" -> 1
-                        let numeric: String = p.chars().take_while(|c| c.is_ascii_digit()).collect();
-                        numeric.parse::<i64>().unwrap_or(0)
-                    })
-                    .collect()
-            };
-            parse(b).cmp(&parse(a))
-        });
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ModuleUpdateInfo {
+    module_id: String,
+    package: String,
+    /// 模块 site-packages 中 dist-info 记录的版本；未找到时为 None
+    installed: Option<String>,
+    /// PyPI 上落在 MODULE_VERSION_PINS 允许范围内的最新正式版（即 update_module 能升到的版本）；
+    /// 查询失败或范围内没有版本时为 None
+    latest: Option<String>,
+    update_available: bool,
+}
+
+/// 读取 site-packages 下 `<name>-<version>.dist-info` 中的版本号
+fn installed_dist_version(site_packages: &Path, package: &str) -> Option<String> {
+    let wanted = normalize_dist_name(package);
+    fs::read_dir(site_packages).ok()?.flatten().find_map(|entry| {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let stem = name.strip_suffix(".dist-info")?;
+        let (dist, version) = stem.rsplit_once('-')?;
+        (normalize_dist_name(dist) == wanted).then(|| version.to_string())
+    })
+}
 
-        Ok(serde_json::to_string(&versions).unwrap_or_else(|_| "[]".into()))
+/// `latest` 是否比 `installed` 新（比较时忽略 release 末尾的 0，即 1.0 == 1.0.0）
+fn pep440_is_newer(latest: &str, installed: &str) -> bool {
+    match (pep440_cmp_key(latest), pep440_cmp_key(installed)) {
+        (Some(l), Some(i)) => l > i,
+        _ => false,
+    }
+}
+
+/// 用于比较的 PEP 440 排序键（去掉 release 末尾的 0）
+fn pep440_cmp_key(v: &str) -> Option<Pep440Key> {
+    parse_pep440(v).map(|(mut k, _)| {
+        while k.release.len() > 1 && k.release.last() == Some(&0) {
+            k.release.pop();
+        }
+        k
+    })
+}
+
+/// 版本是否满足逗号分隔的约束（支持 >= <= > < == !=）。版本号无法解析时视为不满足。
+fn pep440_satisfies(version: &str, range: &str) -> bool {
+    let Some(v) = pep440_cmp_key(version) else {
+        return false;
+    };
+    range.split(',').map(str::trim).filter(|c| !c.is_empty()).all(|clause| {
+        let (op, rhs) = [">=", "<=", "==", "!=", ">", "<"]
+            .iter()
+            .find_map(|op| clause.strip_prefix(op).map(|rest| (*op, rest.trim())))
+            .unwrap_or(("", clause));
+        let Some(bound) = pep440_cmp_key(rhs) else {
+            return false;
+        };
+        match op {
+            ">=" => v >= bound,
+            "<=" => v <= bound,
+            "==" => v == bound,
+            "!=" => v != bound,
+            ">" => v > bound,
+            "<" => v < bound,
+            _ => false,
+        }
+    })
+}
+
+/// 从按新到旧排序的版本列表中取允许范围内的最新版本；没有范围约束时取最新版本
+fn latest_allowed_version(versions: Vec<String>, range: Option<&str>) -> Option<String> {
+    versions
+        .into_iter()
+        .find(|v| range.is_none_or(|r| pep440_satisfies(v, r)))
+}
+
+/// 检查已安装模块中各 pip 包的版本，与 PyPI 上允许范围内的最新正式版比较。只检查已安装的模块。
+#[tauri::command]
+async fn check_module_updates(mirror: Option<String>) -> Result<Vec<ModuleUpdateInfo>, String> {
+    spawn_blocking_result(move || {
+        let index_url = mirror.or_else(preferred_pip_mirror);
+        let mut out = Vec::new();
+        for (id, _, _, packages, _, _) in module_definitions() {
+            let site_packages = modules_dir().join(id).join("site-packages");
+            if !site_packages.exists() {
+                continue;
+            }
+            for req in packages {
+                let package = package_spec_name(req);
+                let installed = installed_dist_version(&site_packages, package);
+                let range = module_version_pin(id, package).map(|(_, range)| range);
+                let latest = match pypi_versions_sync(package, index_url.as_deref(), false) {
+                    Ok(versions) => latest_allowed_version(versions, range),
+                    Err(e) => {
+                        eprintln!("check_module_updates: {package}: {e}");
+                        None
+                    }
+                };
+                let update_available = match (&installed, &latest) {
+                    (Some(i), Some(l)) => pep440_is_newer(l, i),
+                    _ => false,
+                };
+                out.push(ModuleUpdateInfo {
+                    module_id: id.to_string(),
+                    package: package.to_string(),
+                    installed,
+                    latest,
+                    update_available,
+                });
+            }
+        }
+        Ok(out)
     })
     .await
 }
@@ -4026,8 +10063,7 @@ async fn fetch_pypi_versions(package: String, index_url: Option<String>) -> Resu
 #[tauri::command]
 async fn http_get_json(url: String) -> Result<String, String> {
     spawn_blocking_result(move || {
-        let client = reqwest::blocking::Client::builder()
-            .timeout(std::time::Duration::from_secs(15))
+        let client = http_client_builder(Duration::from_secs(15))
             .user_agent("openakita-desktop/1.0")
             .build()
             .map_err(|e| format!("HTTP client error: {e}"))?;
@@ -4063,8 +10099,7 @@ async fn http_proxy_request(
 ) -> Result<String, String> {
     spawn_blocking_result(move || {
         let timeout = timeout_secs.unwrap_or(30);
-        let client = reqwest::blocking::Client::builder()
-            .timeout(std::time::Duration::from_secs(timeout))
+        let client = http_client_builder(Duration::from_secs(timeout))
             .user_agent("openakita-desktop/1.0")
             .build()
             .map_err(|e| format!("HTTP client error: {e}"))?;
@@ -4136,10 +10171,62 @@ async fn read_file_base64(path: String) -> Result<String, String> {
     Ok(format!("data:{};base64,{}", mime, b64))
 }
 
+/// Validators of a partial download, stored next to the `.part` file as `<dest>.part.meta`.
+/// A resume sends them as `If-Range`, so the server only answers 206 if the file is unchanged.
+#[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct PartialDownloadMeta {
+    url: String,
+    #[serde(default)]
+    etag: Option<String>,
+    #[serde(default)]
+    last_modified: Option<String>,
+}
+
+impl PartialDownloadMeta {
+    fn from_response(url: &str, resp: &reqwest::Response) -> Self {
+        let header = |name: reqwest::header::HeaderName| {
+            resp.headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.to_string())
+        };
+        Self {
+            url: url.to_string(),
+            etag: header(reqwest::header::ETAG),
+            last_modified: header(reqwest::header::LAST_MODIFIED),
+        }
+    }
+
+    /// If-Range value: a strong ETag, else Last-Modified (weak ETags are not allowed in If-Range).
+    fn if_range(&self) -> Option<&str> {
+        self.etag
+            .as_deref()
+            .filter(|e| !e.starts_with("W/"))
+            .or(self.last_modified.as_deref())
+    }
+}
+
+/// Start offset of a 206 response (`Content-Range: bytes <start>-<end>/<total>`).
+fn content_range_start(value: &str) -> Option<u64> {
+    value.trim().strip_prefix("bytes ")?.split('-').next()?.trim().parse().ok()
+}
+
 /// Download a file from a URL and save it to the user's Downloads folder.
+/// The body is streamed to `<dest>.part` chunk by chunk and renamed on completion; progress is reported via
+/// `download-progress` events `{ downloadId, bytes, total }` (total from Content-Length, may be null).
+/// If a `.part` file from an interrupted attempt exists, the download resumes with `Range` + `If-Range`
+/// (the ETag / Last-Modified recorded in `<dest>.part.meta`); a `.part` without validators, or a server
+/// answering 200 (file changed or no range support), restarts from scratch. Network interruptions keep
+/// the `.part` file for the next attempt, other errors remove it.
 /// Returns the saved file path on success.
 #[tauri::command]
-async fn download_file(url: String, filename: String) -> Result<String, String> {
+async fn download_file(
+    app: tauri::AppHandle,
+    url: String,
+    filename: String,
+    download_id: Option<String>,
+) -> Result<String, String> {
     // Determine downloads directory
     let downloads_dir = dirs_next::download_dir()
         .or_else(|| dirs_next::home_dir().map(|h| h.join("Downloads")))
@@ -4164,23 +10251,139 @@ async fn download_file(url: String, filename: String) -> Result<String, String>
         dest = downloads_dir.join(format!("{stem} ({counter}){ext}"));
         counter += 1;
     }
+    let part = {
+        let mut name = dest.file_name().unwrap_or_default().to_os_string();
+        name.push(".part");
+        dest.with_file_name(name)
+    };
+    let meta_path = {
+        let mut name = part.file_name().unwrap_or_default().to_os_string();
+        name.push(".meta");
+        part.with_file_name(name)
+    };
+    let discard_part = || {
+        let _ = std::fs::remove_file(&part);
+        let _ = std::fs::remove_file(&meta_path);
+    };
 
     // Download
-    let client = reqwest::Client::new();
-    let resp = client
-        .get(&url)
+    let download_id = download_id.unwrap_or_else(|| filename.clone());
+    let client = async_http_client_builder()
+        .build()
+        .map_err(|e| format!("HTTP client error: {e}"))?;
+    let existing = std::fs::metadata(&part).map(|m| m.len()).unwrap_or(0);
+    // 只有记录了校验信息（且是同一 URL）的 .part 才能续传，否则无法确认服务器上的文件没变
+    let if_range = (existing > 0)
+        .then(|| std::fs::read_to_string(&meta_path).ok())
+        .flatten()
+        .and_then(|s| serde_json::from_str::<PartialDownloadMeta>(&s).ok())
+        .filter(|m| m.url == url)
+        .and_then(|m| m.if_range().map(|v| v.to_string()));
+    if existing > 0 && if_range.is_none() {
+        discard_part();
+    }
+    let mut req = client.get(&url);
+    if let Some(v) = &if_range {
+        req = req
+            .header(reqwest::header::RANGE, format!("bytes={existing}-"))
+            .header(reqwest::header::IF_RANGE, v.as_str());
+    }
+    let mut resp = req
         .send()
         .await
         .map_err(|e| format!("Download request failed: {e}"))?;
+
+    // 206 且起始位置吻合 = 文件未变，追加写入；200 = 文件已变或服务器不支持 Range，从头下载
+    let resuming = if_range.is_some()
+        && resp.status() == reqwest::StatusCode::PARTIAL_CONTENT
+        && resp
+            .headers()
+            .get(reqwest::header::CONTENT_RANGE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(content_range_start)
+            == Some(existing);
+    if if_range.is_some()
+        && !resuming
+        && (resp.status() == reqwest::StatusCode::PARTIAL_CONTENT
+            || resp.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE)
+    {
+        // .part 已无效（续传位置不符或超出文件长度），丢弃后重新请求完整文件
+        discard_part();
+        resp = client
+            .get(&url)
+            .send()
+            .await
+            .map_err(|e| format!("Download request failed: {e}"))?;
+    }
     if !resp.status().is_success() {
+        discard_part();
         return Err(format!("Download failed with status {}", resp.status()));
     }
-    let bytes = resp
-        .bytes()
-        .await
-        .map_err(|e| format!("Failed to read response body: {e}"))?;
-    std::fs::write(&dest, &bytes)
-        .map_err(|e| format!("Failed to write file: {e}"))?;
+    if !resuming {
+        // 新的下载：记录本次响应的校验信息，供中断后续传；服务器没有提供时无法续传
+        let meta = PartialDownloadMeta::from_response(&url, &resp);
+        let saved = meta.if_range().is_some()
+            && serde_json::to_string(&meta)
+                .ok()
+                .is_some_and(|json| std::fs::write(&meta_path, json).is_ok());
+        if !saved {
+            let _ = std::fs::remove_file(&meta_path);
+        }
+    }
+    let offset = if resuming { existing } else { 0 };
+    let total = resp.content_length().map(|len| len + offset);
+
+    let emit_progress = |bytes: u64| {
+        let _ = app.emit("download-progress", serde_json::json!({
+            "downloadId": download_id,
+            "bytes": bytes,
+            "total": total,
+        }));
+    };
+
+    let open_result = if resuming {
+        OpenOptions::new().append(true).open(&part)
+    } else {
+        std::fs::File::create(&part)
+    };
+    let mut file = match open_result {
+        Ok(f) => f,
+        Err(e) => {
+            discard_part();
+            return Err(format!("Failed to create file: {e}"));
+        }
+    };
+    let mut written: u64 = offset;
+    let mut last_emit = std::time::Instant::now();
+    emit_progress(written);
+    loop {
+        let chunk = match resp.chunk().await {
+            Ok(Some(c)) => c,
+            Ok(None) => break,
+            // 网络中断：保留 .part，下次调用时续传
+            Err(e) => return Err(format!("Failed to read response body (partial download kept for resume): {e}")),
+        };
+        if let Err(e) = file.write_all(&chunk) {
+            drop(file);
+            discard_part();
+            return Err(format!("Failed to write file: {e}"));
+        }
+        written += chunk.len() as u64;
+        // 节流：最多每 200ms 发一次进度事件
+        if last_emit.elapsed() >= Duration::from_millis(200) {
+            emit_progress(written);
+            last_emit = std::time::Instant::now();
+        }
+    }
+    if let Err(e) = file.flush() {
+        drop(file);
+        discard_part();
+        return Err(format!("Failed to write file: {e}"));
+    }
+    drop(file);
+    std::fs::rename(&part, &dest).map_err(|e| format!("Failed to finalize download: {e}"))?;
+    let _ = std::fs::remove_file(&meta_path);
+    emit_progress(written);
 
     Ok(dest.to_string_lossy().to_string())
 }
@@ -4249,6 +10452,54 @@ fn open_file_with_default(path: String) -> Result<(), String> {
     Ok(())
 }
 
+/// Reveal a file (e.g. the serve log returned by `openakita_service_log`) in the OS
+/// file manager with it selected. Unlike `show_item_in_folder`, the path must be a file.
+#[tauri::command]
+fn reveal_file(path: String) -> Result<(), String> {
+    if !std::path::Path::new(&path).is_file() {
+        return Err(format!("File does not exist: {path}"));
+    }
+    show_item_in_folder(path)
+}
+
+/// Open a directory in the OS file manager (Explorer / Finder / xdg-open).
+#[tauri::command]
+fn open_path_in_explorer(path: String) -> Result<(), String> {
+    let p = std::path::Path::new(&path);
+    if !p.is_dir() {
+        return Err(format!("Directory does not exist: {path}"));
+    }
+    #[cfg(target_os = "windows")]
+    {
+        let mut c = std::process::Command::new("explorer");
+        c.arg(&path);
+        apply_no_window(&mut c);
+        c.spawn().map_err(|e| format!("Failed to open folder: {e}"))?;
+    }
+    #[cfg(target_os = "macos")]
+    {
+        std::process::Command::new("open")
+            .arg(&path)
+            .spawn()
+            .map_err(|e| format!("Failed to open folder: {e}"))?;
+    }
+    #[cfg(target_os = "linux")]
+    {
+        std::process::Command::new("xdg-open")
+            .arg(&path)
+            .spawn()
+            .map_err(|e| format!("Failed to open folder: {e}"))?;
+    }
+    Ok(())
+}
+
+/// Open a workspace's directory in the OS file manager.
+#[tauri::command]
+fn open_workspace_folder(workspace_id: String) -> Result<(), String> {
+    let dir = workspace_dir(&workspace_id);
+    open_path_in_explorer(dir.to_string_lossy().to_string())
+}
+
 /// Open an external URL in the OS default browser.
 #[tauri::command]
 fn open_external_url(url: String) -> Result<(), String> {
@@ -4280,21 +10531,251 @@ fn open_external_url(url: String) -> Result<(), String> {
 // CLI 命令注册（跨平台）
 // ═══════════════════════════════════════════════════════════════════════
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(rename_all = "camelCase")]
-struct CliConfig {
-    commands: Vec<String>,
-    add_to_path: bool,
-    bin_dir: String,
-    installed_at: String,
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct CliConfig {
+    commands: Vec<String>,
+    add_to_path: bool,
+    bin_dir: String,
+    installed_at: String,
+    /// 注册时指定的 venv（wrapper 调用其 python）；None 表示按 cli_backend_exe_path 自动解析
+    #[serde(default)]
+    venv_dir: Option<String>,
+    /// PATH 写入范围："user" | "system"；未添加到 PATH 或旧版本配置时为 None
+    #[serde(default)]
+    path_scope: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct CliStatus {
+    registered_commands: Vec<String>,
+    in_path: bool,
+    bin_dir: String,
+    path_scope: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct CliVerify {
+    command: String,
+    wrapper_path: String,
+    wrapper_exists: bool,
+    /// 在干净环境中执行 `<command> --version` 是否输出了 OpenAkita 版本信息
+    runs: bool,
+    version_output: Option<String>,
+    /// 新开终端中 which / where 找到的路径（Windows 按注册表 PATH，macOS / Linux 启动登录 shell）
+    resolved_path: Option<String>,
+    /// resolved_path 是否就是本应用生成的 wrapper（否则被其他同名命令遮蔽）
+    resolves_to_wrapper: bool,
+    /// 本进程启动时继承的 PATH 中能否找到；新终端能找到而这里找不到，说明已打开的终端需要重开
+    in_current_session: bool,
+    hint: Option<String>,
+}
+
+/// 新开终端时的 PATH：系统 PATH + 用户 PATH（展开 %VAR%）
+#[cfg(target_os = "windows")]
+fn windows_fresh_path() -> String {
+    use winreg::enums::*;
+    use winreg::RegKey;
+
+    let expand = |s: &str| -> String {
+        let mut out = String::new();
+        let mut rest = s;
+        while let Some(start) = rest.find('%') {
+            out.push_str(&rest[..start]);
+            let after = &rest[start + 1..];
+            match after.find('%') {
+                Some(end) => {
+                    let name = &after[..end];
+                    match std::env::var(name) {
+                        Ok(v) => out.push_str(&v),
+                        Err(_) => out.push_str(&rest[start..start + end + 2]),
+                    }
+                    rest = &after[end + 1..];
+                }
+                None => {
+                    out.push_str(&rest[start..]);
+                    rest = "";
+                }
+            }
+        }
+        out.push_str(rest);
+        out
+    };
+
+    let mut parts: Vec<String> = Vec::new();
+    for (hive_predef, subkey_path) in [
+        (HKEY_LOCAL_MACHINE, r"SYSTEM\CurrentControlSet\Control\Session Manager\Environment"),
+        (HKEY_CURRENT_USER, "Environment"),
+    ] {
+        if let Ok(key) = RegKey::predef(hive_predef).open_subkey_with_flags(subkey_path, KEY_READ) {
+            let value: String = key.get_value("Path").unwrap_or_default();
+            if !value.is_empty() {
+                parts.push(expand(&value));
+            }
+        }
+    }
+    parts.join(";")
+}
+
+/// 在“新终端”环境中查找命令：Windows 用注册表 PATH 执行 where，macOS / Linux 启动登录交互 shell 执行 command -v
+fn cli_resolve_in_fresh_shell(command: &str) -> Option<PathBuf> {
+    #[cfg(target_os = "windows")]
+    let output = {
+        let mut c = Command::new("where");
+        c.arg(command);
+        c.env("PATH", windows_fresh_path());
+        apply_no_window(&mut c);
+        command_output_with_timeout(c, Duration::from_secs(10)).ok()?
+    };
+    #[cfg(not(target_os = "windows"))]
+    let output = {
+        let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".into());
+        let mut c = Command::new(&shell);
+        // 登录 + 交互 shell 才会读取 .zshrc / .bashrc / .bash_profile 中的 openakita block
+        c.args(["-l", "-i", "-c", &format!("command -v {}", command)]);
+        c.env_clear();
+        for key in ["HOME", "USER", "LOGNAME", "SHELL", "LANG"] {
+            if let Ok(v) = std::env::var(key) {
+                c.env(key, v);
+            }
+        }
+        c.env("PATH", "/usr/local/bin:/usr/bin:/bin:/usr/sbin:/sbin");
+        c.env("TERM", "dumb");
+        command_output_with_timeout(c, Duration::from_secs(10)).ok()?
+    };
+    if !output.status.success() {
+        return None;
+    }
+    // 交互 shell 可能先打印欢迎信息，取最后一个像路径的行
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::trim)
+        .filter(|l| Path::new(l).is_absolute())
+        .map(PathBuf::from)
+        .next_back()
+}
+
+/// 本进程继承的 PATH 中能否找到命令
+fn cli_in_current_path(command: &str) -> bool {
+    let Some(path) = std::env::var_os("PATH") else {
+        return false;
+    };
+    std::env::split_paths(&path).any(|dir| wrapper_script_path(&dir, command).exists())
+}
+
+/// 验证 CLI 命令是否真的可用：直接执行 wrapper `--version`，并检查新开的终端能否通过 PATH 找到它。
+/// 用于解释“已添加到 PATH 但当前终端仍提示找不到命令”——已打开的终端需要重新打开。
+#[tauri::command]
+async fn cli_verify(command: String) -> Result<CliVerify, String> {
+    if command.is_empty() || !command.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(format!("命令名 '{}' 包含非法字符", command));
+    }
+    spawn_blocking_result(move || {
+        let bin_dir = read_cli_config()
+            .map(|c| PathBuf::from(c.bin_dir))
+            .unwrap_or_else(cli_bin_dir);
+        let wrapper = wrapper_script_path(&bin_dir, &command);
+        let wrapper_exists = wrapper.exists();
+
+        let mut version_output = None;
+        let mut runs = false;
+        if wrapper_exists {
+            #[cfg(target_os = "windows")]
+            let mut c = {
+                let mut c = Command::new("cmd");
+                c.arg("/C").arg(&wrapper).arg("--version");
+                c
+            };
+            #[cfg(not(target_os = "windows"))]
+            let mut c = {
+                let mut c = Command::new(&wrapper);
+                c.arg("--version");
+                c
+            };
+            // 干净环境：不继承本进程的 PYTHONPATH / VIRTUAL_ENV 等，避免“只在这里能跑”
+            c.env_clear();
+            #[cfg(target_os = "windows")]
+            for key in ["SystemRoot", "SystemDrive", "USERPROFILE", "APPDATA", "LOCALAPPDATA", "TEMP", "TMP", "PATHEXT", "COMSPEC"] {
+                if let Ok(v) = std::env::var(key) {
+                    c.env(key, v);
+                }
+            }
+            #[cfg(not(target_os = "windows"))]
+            for key in ["HOME", "USER", "LANG"] {
+                if let Ok(v) = std::env::var(key) {
+                    c.env(key, v);
+                }
+            }
+            c.env("PYTHONIOENCODING", "utf-8");
+            apply_no_window(&mut c);
+            match command_output_with_timeout(c, Duration::from_secs(30)) {
+                Ok(out) => {
+                    let text = format!(
+                        "{}{}",
+                        String::from_utf8_lossy(&out.stdout),
+                        String::from_utf8_lossy(&out.stderr)
+                    );
+                    runs = out.status.success() && text.contains("OpenAkita");
+                    let text = text.trim();
+                    version_output = Some(text.chars().take(500).collect());
+                }
+                Err(e) => version_output = Some(e),
+            }
+        }
+
+        let resolved_path = cli_resolve_in_fresh_shell(&command);
+        let resolves_to_wrapper = resolved_path.as_ref().is_some_and(|p| {
+            match (fs::canonicalize(p), fs::canonicalize(&wrapper)) {
+                (Ok(a), Ok(b)) => a == b,
+                _ => p == &wrapper,
+            }
+        });
+        let in_current_session = cli_in_current_path(&command);
+
+        let hint = if !wrapper_exists {
+            Some(format!("命令 {} 尚未注册", command))
+        } else if !runs {
+            Some("wrapper 执行失败，后端可能已移动或重装，可尝试重新注册 CLI".to_string())
+        } else if resolved_path.is_none() {
+            Some(format!("新终端中找不到 {}，请确认注册时已勾选“添加到 PATH”", command))
+        } else if !resolves_to_wrapper {
+            Some(format!(
+                "新终端中的 {} 指向 {}，被其他同名命令遮蔽",
+                command,
+                resolved_path.as_ref().map(|p| p.display().to_string()).unwrap_or_default()
+            ))
+        } else if !in_current_session {
+            Some("已添加到 PATH；已打开的终端需要关闭后重新打开才能使用".to_string())
+        } else {
+            None
+        };
+
+        Ok(CliVerify {
+            command,
+            wrapper_path: wrapper.to_string_lossy().to_string(),
+            wrapper_exists,
+            runs,
+            version_output,
+            resolved_path: resolved_path.map(|p| p.to_string_lossy().to_string()),
+            resolves_to_wrapper,
+            in_current_session,
+            hint,
+        })
+    })
+    .await
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(rename_all = "camelCase")]
-struct CliStatus {
-    registered_commands: Vec<String>,
-    in_path: bool,
-    bin_dir: String,
+/// 校验 register_cli 的 PATH 范围参数，默认 "user"。
+/// macOS / Linux 只写当前用户的 shell 配置，不支持 "system"。
+fn cli_path_scope(scope: Option<&str>) -> Result<&'static str, String> {
+    match scope.map(str::trim).unwrap_or("") {
+        "" | "user" => Ok("user"),
+        "system" if cfg!(target_os = "windows") => Ok("system"),
+        "system" => Err("macOS / Linux 仅支持添加到当前用户的 PATH（scope = \"user\"）".into()),
+        other => Err(format!("未知的 PATH 范围: {}（可选 user / system）", other)),
+    }
 }
 
 /// 获取 CLI bin 目录路径
@@ -4357,31 +10838,49 @@ fn write_cli_config(config: &CliConfig) -> Result<(), String> {
     Ok(())
 }
 
-/// 生成 wrapper 脚本内容
+/// 生成 wrapper 脚本内容。`backend_exe` 为 cli_backend_exe_path 解析出的实际路径：
+/// 打包的 openakita-server 直接调用；venv 的 python 解释器（开发环境）以 `-m openakita.main` 调用。
 fn generate_wrapper_content(backend_exe: &Path) -> String {
+    let exe_path = backend_exe.to_string_lossy();
+    let is_python = backend_exe
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_ascii_lowercase().starts_with("python"))
+        .unwrap_or(false);
+    let module_args = if is_python { " -m openakita.main" } else { "" };
     #[cfg(target_os = "windows")]
     {
-        let _ = backend_exe; // Windows 使用相对路径，不需要绝对路径
-        format!("@echo off\r\n\"%~dp0..\\resources\\openakita-server\\openakita-server.exe\" %*\r\n")
+        format!("@echo off\r\nREM OpenAkita CLI wrapper - managed by OpenAkita Desktop\r\n\"{exe_path}\"{module_args} %*\r\n")
     }
     #[cfg(not(target_os = "windows"))]
     {
-        let exe_path = backend_exe.to_string_lossy();
+        // 双引号内 \ " $ ` 仍会被 shell 解释，需转义（普通路径生成的内容与旧版一致）
+        let exe_path = exe_path
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('$', "\\$")
+            .replace('`', "\\`");
         format!(
-            "#!/bin/sh\n# OpenAkita CLI wrapper - managed by OpenAkita Desktop\nexec \"{}\" \"$@\"\n",
-            exe_path
+            "#!/bin/sh\n# OpenAkita CLI wrapper - managed by OpenAkita Desktop\nexec \"{exe_path}\"{module_args} \"$@\"\n"
         )
     }
 }
 
+/// wrapper 脚本路径（Windows 为 <name>.cmd）
+fn wrapper_script_path(bin_dir: &Path, cmd_name: &str) -> PathBuf {
+    #[cfg(target_os = "windows")]
+    {
+        bin_dir.join(format!("{}.cmd", cmd_name))
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        bin_dir.join(cmd_name)
+    }
+}
+
 /// 创建 wrapper 脚本文件
 fn create_wrapper_script(bin_dir: &Path, cmd_name: &str, backend_exe: &Path) -> Result<(), String> {
     let content = generate_wrapper_content(backend_exe);
-
-    #[cfg(target_os = "windows")]
-    let file_path = bin_dir.join(format!("{}.cmd", cmd_name));
-    #[cfg(not(target_os = "windows"))]
-    let file_path = bin_dir.join(cmd_name);
+    let file_path = wrapper_script_path(bin_dir, cmd_name);
 
     std::fs::write(&file_path, &content)
         .map_err(|e| format!("写入 {} 失败: {e}", file_path.display()))?;
@@ -4400,39 +10899,56 @@ fn create_wrapper_script(bin_dir: &Path, cmd_name: &str, backend_exe: &Path) ->
 
 /// 删除 wrapper 脚本文件
 fn remove_wrapper_script(bin_dir: &Path, cmd_name: &str) {
-    #[cfg(target_os = "windows")]
-    let file_path = bin_dir.join(format!("{}.cmd", cmd_name));
-    #[cfg(not(target_os = "windows"))]
-    let file_path = bin_dir.join(cmd_name);
+    let _ = std::fs::remove_file(wrapper_script_path(bin_dir, cmd_name));
+}
+
+/// PATH 条目是否形如 `*/openakita*/bin`：父目录名（忽略前导 `.`，大小写不敏感）以 openakita 开头
+fn is_openakita_bin_entry(entry: &str) -> bool {
+    let path = Path::new(entry.trim().trim_matches('"'));
+    let is_bin = path
+        .file_name()
+        .is_some_and(|n| n.to_string_lossy().eq_ignore_ascii_case("bin"));
+    let parent_matches = path.parent().and_then(|p| p.file_name()).is_some_and(|n| {
+        n.to_string_lossy()
+            .to_lowercase()
+            .trim_start_matches('.')
+            .starts_with("openakita")
+    });
+    is_bin && parent_matches
+}
 
-    let _ = std::fs::remove_file(&file_path);
+/// 已失效的 openakita bin 条目：目录不存在。含 `%VAR%` 的条目无法可靠展开，不计入。
+fn is_stale_openakita_bin_entry(entry: &str) -> bool {
+    !entry.contains('%')
+        && is_openakita_bin_entry(entry)
+        && !Path::new(entry.trim().trim_matches('"')).exists()
 }
 
 // ── PATH 操作：Windows ──
 
+/// `scope`："system" 写 HKLM（需要管理员权限，无权限时直接报错，不会降级），"user" 写 HKCU
 #[cfg(target_os = "windows")]
-fn windows_add_to_path(bin_dir: &Path) -> Result<(), String> {
+fn windows_add_to_path(bin_dir: &Path, scope: &str) -> Result<(), String> {
     use winreg::enums::*;
     use winreg::RegKey;
 
     let bin_str = bin_dir.to_string_lossy().to_string();
 
-    // 尝试 HKLM (perMachine), 如果权限不够降级到 HKCU (currentUser)
-    let (hive, subkey) = {
+    let (hive, subkey) = if scope == "system" {
         let hklm = RegKey::predef(HKEY_LOCAL_MACHINE);
-        let sys_env = hklm.open_subkey_with_flags(
-            r"SYSTEM\CurrentControlSet\Control\Session Manager\Environment",
-            KEY_READ | KEY_WRITE,
-        );
-        if let Ok(key) = sys_env {
-            (key, "system")
-        } else {
-            let hkcu = RegKey::predef(HKEY_CURRENT_USER);
-            let user_env = hkcu
-                .open_subkey_with_flags("Environment", KEY_READ | KEY_WRITE)
-                .map_err(|e| format!("无法打开用户环境变量注册表: {e}"))?;
-            (user_env, "user")
-        }
+        let sys_env = hklm
+            .open_subkey_with_flags(
+                r"SYSTEM\CurrentControlSet\Control\Session Manager\Environment",
+                KEY_READ | KEY_WRITE,
+            )
+            .map_err(|e| format!("写入系统 PATH 需要管理员权限，请以管理员身份运行，或改为添加到用户 PATH: {e}"))?;
+        (sys_env, "system")
+    } else {
+        let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+        let user_env = hkcu
+            .open_subkey_with_flags("Environment", KEY_READ | KEY_WRITE)
+            .map_err(|e| format!("无法打开用户环境变量注册表: {e}"))?;
+        (user_env, "user")
     };
 
     // 读取当前 PATH
@@ -4519,6 +11035,60 @@ fn windows_is_in_path(bin_dir: &Path) -> bool {
     false
 }
 
+#[cfg(target_os = "windows")]
+fn windows_stale_path_entries() -> Vec<String> {
+    use winreg::enums::*;
+    use winreg::RegKey;
+
+    let mut out: Vec<String> = Vec::new();
+    for (hive_predef, subkey_path) in [
+        (HKEY_LOCAL_MACHINE, r"SYSTEM\CurrentControlSet\Control\Session Manager\Environment"),
+        (HKEY_CURRENT_USER, "Environment"),
+    ] {
+        let hive = RegKey::predef(hive_predef);
+        if let Ok(key) = hive.open_subkey_with_flags(subkey_path, KEY_READ) {
+            let current_path: String = key.get_value("Path").unwrap_or_default();
+            for p in current_path.split(';').filter(|p| is_stale_openakita_bin_entry(p)) {
+                if !out.iter().any(|o| o.eq_ignore_ascii_case(p)) {
+                    out.push(p.to_string());
+                }
+            }
+        }
+    }
+    out
+}
+
+#[cfg(target_os = "windows")]
+fn windows_clean_stale_path() -> Result<usize, String> {
+    use winreg::enums::*;
+    use winreg::RegKey;
+
+    let mut removed = 0;
+    for (hive_predef, subkey_path) in [
+        (HKEY_LOCAL_MACHINE, r"SYSTEM\CurrentControlSet\Control\Session Manager\Environment"),
+        (HKEY_CURRENT_USER, "Environment"),
+    ] {
+        let hive = RegKey::predef(hive_predef);
+        // 无管理员权限时 HKLM 打不开写权限，跳过即可
+        if let Ok(key) = hive.open_subkey_with_flags(subkey_path, KEY_READ | KEY_WRITE) {
+            let current_path: String = key.get_value("Path").unwrap_or_default();
+            let (stale, keep): (Vec<&str>, Vec<&str>) =
+                current_path.split(';').partition(|p| is_stale_openakita_bin_entry(p));
+            if stale.is_empty() {
+                continue;
+            }
+            key.set_value("Path", &keep.join(";"))
+                .map_err(|e| format!("写入 PATH 注册表失败 ({}): {e}", subkey_path))?;
+            removed += stale.len();
+        }
+    }
+
+    if removed > 0 {
+        windows_broadcast_env_change();
+    }
+    Ok(removed)
+}
+
 #[cfg(target_os = "windows")]
 fn windows_broadcast_env_change() {
     use std::ffi::CString;
@@ -4703,6 +11273,95 @@ fn unix_is_in_path(bin_dir: &Path) -> bool {
     false
 }
 
+/// 取出 marker block 中 `export PATH="<dir>:$PATH"` 行的 <dir>
+#[cfg(not(target_os = "windows"))]
+fn unix_block_path_entry(line: &str) -> Option<&str> {
+    line.trim().strip_prefix("export PATH=\"")?.strip_suffix(":$PATH\"")
+}
+
+#[cfg(not(target_os = "windows"))]
+fn unix_stale_path_entries() -> Vec<String> {
+    let marker_start = "# >>> openakita cli >>>";
+    let marker_end = "# <<< openakita cli <<<";
+    let home = match home_dir() {
+        Some(h) => h,
+        None => return vec![],
+    };
+
+    let mut out: Vec<String> = Vec::new();
+    for profile in get_shell_profiles(&home) {
+        let content = std::fs::read_to_string(&profile).unwrap_or_default();
+        let mut in_block = false;
+        for line in content.lines() {
+            if line.contains(marker_start) {
+                in_block = true;
+            } else if line.contains(marker_end) {
+                in_block = false;
+            } else if in_block {
+                if let Some(dir) = unix_block_path_entry(line) {
+                    if is_stale_openakita_bin_entry(dir) && !out.iter().any(|o| o == dir) {
+                        out.push(dir.to_string());
+                    }
+                }
+            }
+        }
+    }
+    out
+}
+
+/// 删除 shell profile 中指向已不存在目录的 openakita marker block。
+/// 返回删除的 block 数（同一目录出现在多个 profile 中时各计一次）。
+#[cfg(not(target_os = "windows"))]
+fn unix_clean_stale_path() -> Result<usize, String> {
+    let marker_start = "# >>> openakita cli >>>";
+    let marker_end = "# <<< openakita cli <<<";
+    let home = home_dir().ok_or("无法获取 HOME 目录")?;
+
+    let mut removed = 0;
+    for profile in get_shell_profiles(&home) {
+        let Ok(existing) = std::fs::read_to_string(&profile) else {
+            continue;
+        };
+        if !existing.contains(marker_start) {
+            continue;
+        }
+        let mut new_lines: Vec<&str> = Vec::new();
+        let mut block: Option<Vec<&str>> = None;
+        let mut dropped = 0;
+        for line in existing.lines() {
+            if line.contains(marker_start) {
+                block = Some(vec![line]);
+            } else if let Some(mut lines) = block.take() {
+                lines.push(line);
+                if !line.contains(marker_end) {
+                    block = Some(lines);
+                } else if lines.iter().any(|l| unix_block_path_entry(l).is_some_and(is_stale_openakita_bin_entry)) {
+                    dropped += 1;
+                } else {
+                    new_lines.extend(lines);
+                }
+            } else {
+                new_lines.push(line);
+            }
+        }
+        // 未闭合的 block 原样保留
+        if let Some(lines) = block {
+            new_lines.extend(lines);
+        }
+        if dropped == 0 {
+            continue;
+        }
+        let mut content = new_lines.join("\n");
+        if existing.ends_with('\n') {
+            content.push('\n');
+        }
+        std::fs::write(&profile, content)
+            .map_err(|e| format!("写入 {} 失败: {e}", profile.display()))?;
+        removed += dropped;
+    }
+    Ok(removed)
+}
+
 #[cfg(not(target_os = "windows"))]
 fn get_shell_profiles(home: &Path) -> Vec<PathBuf> {
     let mut profiles = Vec::new();
@@ -4721,13 +11380,83 @@ fn get_shell_profiles(home: &Path) -> Vec<PathBuf> {
     profiles
 }
 
+/// wrapper 要调用的程序：指定了 venv 时用其 python，否则按 cli_backend_exe_path 解析
+fn cli_wrapper_target(venv_dir: Option<&str>) -> Result<PathBuf, String> {
+    match venv_dir {
+        Some(dir) => {
+            let py = venv_python_path(dir);
+            if !py.exists() {
+                return Err(format!("venv 中未找到 Python: {}", py.display()));
+            }
+            Ok(py)
+        }
+        None => cli_backend_exe_path(),
+    }
+}
+
+/// 按 cli.json 重新生成所有 wrapper 脚本（不修改 PATH）。
+/// `force` 为 false 时只重写缺失或内容已过期的脚本。未注册 CLI 时返回 Ok 并说明。
+fn refresh_cli_wrappers(force: bool) -> Result<String, String> {
+    let Some(config) = read_cli_config() else {
+        return Ok("未注册 CLI 命令，无需刷新".into());
+    };
+    let bin_dir = PathBuf::from(&config.bin_dir);
+    std::fs::create_dir_all(&bin_dir)
+        .map_err(|e| format!("创建 bin 目录失败: {e}"))?;
+    let backend_exe = cli_wrapper_target(config.venv_dir.as_deref())?;
+    let expected = generate_wrapper_content(&backend_exe);
+
+    let mut refreshed = Vec::new();
+    for cmd_name in &config.commands {
+        let current = std::fs::read_to_string(wrapper_script_path(&bin_dir, cmd_name)).ok();
+        if force || current.as_deref() != Some(expected.as_str()) {
+            create_wrapper_script(&bin_dir, cmd_name, &backend_exe)?;
+            refreshed.push(cmd_name.clone());
+        }
+    }
+    if refreshed.is_empty() {
+        Ok("CLI 命令均为最新".into())
+    } else {
+        Ok(format!("已刷新 CLI 命令: {}", refreshed.join(", ")))
+    }
+}
+
+/// 应用版本变化（升级 / 安装路径变化）后刷新 CLI wrapper，并记录本次版本
+fn refresh_cli_after_upgrade() {
+    let mut state = read_state_file();
+    let current = env!("CARGO_PKG_VERSION");
+    if state.settings.last_installed_version.as_deref() == Some(current) {
+        return;
+    }
+    match refresh_cli_wrappers(false) {
+        Ok(msg) => eprintln!("CLI refresh after upgrade: {msg}"),
+        Err(e) => eprintln!("CLI refresh after upgrade failed: {e}"),
+    }
+    state.settings.last_installed_version = Some(current.to_string());
+    let _ = write_state_file(&state);
+}
+
 // ── Tauri 命令 ──
 
 #[tauri::command]
-fn register_cli(commands: Vec<String>, add_to_path: bool) -> Result<String, String> {
+fn refresh_cli(force: bool) -> Result<String, String> {
+    refresh_cli_wrappers(force)
+}
+
+/// `venv_dir`：可选，指定后 wrapper 直接调用该 venv 的 python（`-m openakita.main`），
+/// 而不是优先使用打包的 openakita-server（开发环境 / 源码安装）
+/// `scope`：PATH 写入范围 "user"（默认）或 "system"（仅 Windows，需要管理员权限）
+#[tauri::command]
+fn register_cli(
+    commands: Vec<String>,
+    add_to_path: bool,
+    venv_dir: Option<String>,
+    scope: Option<String>,
+) -> Result<String, String> {
     if commands.is_empty() {
         return Err("至少需要选择一个命令名称".into());
     }
+    let scope = cli_path_scope(scope.as_deref())?;
 
     // 验证命令名仅包含合法字符
     for cmd in &commands {
@@ -4741,7 +11470,8 @@ fn register_cli(commands: Vec<String>, add_to_path: bool) -> Result<String, Stri
         .map_err(|e| format!("创建 bin 目录失败: {e}"))?;
 
     // 获取后端可执行文件路径
-    let backend_exe = cli_backend_exe_path()?;
+    let venv_dir = venv_dir.filter(|d| !d.trim().is_empty());
+    let backend_exe = cli_wrapper_target(venv_dir.as_deref())?;
 
     // 生成 wrapper 脚本
     for cmd_name in &commands {
@@ -4751,7 +11481,7 @@ fn register_cli(commands: Vec<String>, add_to_path: bool) -> Result<String, Stri
     // PATH 注入
     if add_to_path {
         #[cfg(target_os = "windows")]
-        windows_add_to_path(&bin_dir)?;
+        windows_add_to_path(&bin_dir, scope)?;
 
         #[cfg(not(target_os = "windows"))]
         unix_add_to_path(&bin_dir)?;
@@ -4762,6 +11492,8 @@ fn register_cli(commands: Vec<String>, add_to_path: bool) -> Result<String, Stri
         commands: commands.clone(),
         add_to_path,
         bin_dir: bin_dir.to_string_lossy().to_string(),
+        venv_dir,
+        path_scope: add_to_path.then(|| scope.to_string()),
         installed_at: {
             let now = std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
@@ -4775,7 +11507,11 @@ fn register_cli(commands: Vec<String>, add_to_path: bool) -> Result<String, Stri
     Ok(format!(
         "CLI 命令已注册: {}{}",
         commands.join(", "),
-        if add_to_path { " (已添加到 PATH)" } else { "" }
+        match (add_to_path, scope) {
+            (false, _) => "",
+            (true, "system") => " (已添加到系统 PATH)",
+            (true, _) => " (已添加到用户 PATH)",
+        }
     ))
 }
 
@@ -4805,9 +11541,46 @@ fn unregister_cli() -> Result<String, String> {
     let config_path = openakita_root_dir().join("cli.json");
     let _ = std::fs::remove_file(&config_path);
 
+    // 旧安装位置留下的 bin 目录不在 cli.json 中，这里只提示，由用户决定是否清理
+    let stale = cli_scan_stale_path_entries();
+    if !stale.is_empty() {
+        return Ok(format!(
+            "CLI 命令已注销；PATH 中还有 {} 个已失效的 OpenAkita bin 目录: {}",
+            stale.len(),
+            stale.join(", ")
+        ));
+    }
+
     Ok("CLI 命令已注销".into())
 }
 
+/// 扫描 PATH 中已不存在的 `*/openakita*/bin` 条目（应用移动或重装到新位置后遗留）。
+/// Windows 读取系统 + 用户 PATH 注册表；macOS / Linux 读取 shell profile 中的 openakita block。
+#[tauri::command]
+fn cli_scan_stale_path_entries() -> Vec<String> {
+    #[cfg(target_os = "windows")]
+    {
+        windows_stale_path_entries()
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        unix_stale_path_entries()
+    }
+}
+
+/// 从 PATH 中移除 cli_scan_stale_path_entries 找到的失效条目，返回移除数量
+#[tauri::command]
+fn cli_clean_stale_path() -> Result<usize, String> {
+    #[cfg(target_os = "windows")]
+    {
+        windows_clean_stale_path()
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        unix_clean_stale_path()
+    }
+}
+
 #[tauri::command]
 fn get_cli_status() -> Result<CliStatus, String> {
     let bin_dir = cli_bin_dir();
@@ -4838,12 +11611,521 @@ fn get_cli_status() -> Result<CliStatus, String> {
             registered_commands: existing_commands,
             in_path,
             bin_dir: config.bin_dir,
+            path_scope: config.path_scope,
         })
     } else {
         Ok(CliStatus {
             registered_commands: vec![],
             in_path: false,
             bin_dir: bin_dir.to_string_lossy().to_string(),
+            path_scope: None,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pid_file_round_trips_control_token() {
+        let data = PidFileData {
+            pid: 4321,
+            started_by: STARTED_BY_TAURI.into(),
+            started_at: 1_700_000_000,
+            control_token: Some("abc123".into()),
+        };
+        let json = serde_json::to_string_pretty(&data).unwrap();
+        assert!(json.contains("\"control_token\": \"abc123\""));
+        let parsed = parse_pid_file(&json).unwrap();
+        assert_eq!(parsed.pid, 4321);
+        assert_eq!(parsed.control_token.as_deref(), Some("abc123"));
+    }
+
+    #[test]
+    fn pid_file_without_token_omits_field() {
+        let data = PidFileData {
+            pid: 1,
+            started_by: STARTED_BY_EXTERNAL.into(),
+            started_at: 0,
+            control_token: None,
+        };
+        let json = serde_json::to_string(&data).unwrap();
+        assert!(!json.contains("control_token"));
+        assert_eq!(parse_pid_file(&json).unwrap().control_token, None);
+    }
+
+    #[test]
+    fn legacy_pid_files_have_no_token() {
+        let legacy_json = parse_pid_file(r#"{"pid": 99, "started_by": "tauri", "started_at": 5}"#).unwrap();
+        assert_eq!(legacy_json.pid, 99);
+        assert_eq!(legacy_json.control_token, None);
+
+        let plain = parse_pid_file("  1234\n").unwrap();
+        assert_eq!(plain.pid, 1234);
+        assert_eq!(plain.started_by, STARTED_BY_TAURI);
+        assert_eq!(plain.control_token, None);
+
+        assert!(parse_pid_file("0").is_none());
+        assert!(parse_pid_file("garbage").is_none());
+    }
+
+    #[test]
+    fn control_tokens_are_random_hex() {
+        let a = generate_control_token();
+        let b = generate_control_token();
+        assert_eq!(a.len(), 32);
+        assert!(a.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn module_updates_stay_within_pin_range() {
+        let versions: Vec<String> = ["0.6.3", "0.6.0", "0.5.23", "0.5.20", "0.4.24"]
+            .iter()
+            .map(|v| v.to_string())
+            .collect();
+        assert_eq!(
+            latest_allowed_version(versions.clone(), Some(">=0.4.0,<0.6")).as_deref(),
+            Some("0.5.23")
+        );
+        assert_eq!(latest_allowed_version(versions.clone(), None).as_deref(), Some("0.6.3"));
+        assert_eq!(latest_allowed_version(versions, Some(">=1.0")), None);
+        assert!(!pep440_is_newer("0.5.23", "0.5.23"));
+    }
+
+    #[test]
+    fn pep440_versions_sort_in_spec_order() {
+        let key = |v: &str| parse_pep440(v).unwrap().0;
+        let ascending = [
+            "1.0.dev1",
+            "1.0a1.dev1",
+            "1.0a1",
+            "1.0a2",
+            "1.0b1",
+            "1.0rc1",
+            "1.0",
+            "1.0.post1.dev1",
+            "1.0.post1",
+            "1.0.1",
+            "1.10",
+            "1!0.1",
+        ];
+        for pair in ascending.windows(2) {
+            assert!(key(pair[0]) < key(pair[1]), "{} < {}", pair[0], pair[1]);
+        }
+        // 别名与等价写法
+        assert_eq!(key("1.0alpha1"), key("1.0a1"));
+        assert_eq!(key("1.0c1"), key("1.0rc1"));
+        assert_eq!(key("1.0-1"), key("1.0.post1"));
+        assert_eq!(key("v1.0.0"), key("1.0"));
+        assert_eq!(key("1.0+local.7"), key("1.0"));
+
+        assert!(parse_pep440("1.0rc1").unwrap().1);
+        assert!(parse_pep440("1.0.dev1").unwrap().1);
+        assert!(!parse_pep440("1.0.post1").unwrap().1);
+        assert!(parse_pep440("latest").is_none());
+        assert!(parse_pep440("1.0foo").is_none());
+    }
+
+    #[test]
+    fn pep440_satisfies_range_clauses() {
+        assert!(pep440_satisfies("0.5.23", ">=0.4.0,<0.6"));
+        assert!(!pep440_satisfies("0.6.0", ">=0.4.0,<0.6"));
+        assert!(pep440_satisfies("1.0.0", "==1.0"));
+        assert!(!pep440_satisfies("1.0", "!=1.0.0"));
+        assert!(!pep440_satisfies("not-a-version", ">=0"));
+        assert!(pep440_is_newer("1.0.post1", "1.0"));
+        assert!(!pep440_is_newer("1.0rc1", "1.0"));
+    }
+
+    #[test]
+    fn classify_startup_failure_recognizes_sample_logs() {
+        let port_log = "INFO:     Started server process [4242]\nERROR:    [Errno 98] error while attempting to bind on address ('127.0.0.1', 18900): address already in use\n";
+        assert_eq!(classify_startup_failure(port_log, &[]).0, "port-in-use");
+        let win_port_log = "OSError: [WinError 10048] 通常每个套接字地址(协议/网络地址/端口)只允许使用一次。\n";
+        assert_eq!(classify_startup_failure(win_port_log, &[]).0, "port-in-use");
+
+        let module_log = "Traceback (most recent call last):\n  File \"openakita/memory/vector.py\", line 3, in <module>\n    import chromadb\nModuleNotFoundError: No module named 'chromadb'\n";
+        let (class, hint) = classify_startup_failure(module_log, &[]);
+        assert_eq!(class, "module-missing");
+        assert!(hint.contains("'chromadb'"));
+        let unknown_module = "ModuleNotFoundError: No module named 'foo.bar'\n";
+        assert!(classify_startup_failure(unknown_module, &[]).1.contains("重新安装"));
+
+        let config_log = "pydantic_core._pydantic_core.ValidationError: 1 validation error for Settings\napi_port\n  Input should be a valid integer\n";
+        assert_eq!(classify_startup_failure(config_log, &[]).0, "config-invalid");
+        let encoding_log = "UnicodeEncodeError: 'gbk' codec can't encode character '\\u2713' in position 0\n";
+        assert_eq!(classify_startup_failure(encoding_log, &[]).0, "encoding");
+        assert_eq!(classify_startup_failure("Killed\n", &[]).0, "unknown");
+    }
+
+    #[test]
+    fn classify_startup_failure_prefers_custom_patterns() {
+        let custom = vec![
+            StartupFailurePattern {
+                class: "disk-full".into(),
+                contains: vec!["No space left on device".into()],
+                hint: "磁盘空间不足".into(),
+            },
+            StartupFailurePattern {
+                class: "port-conflict".into(),
+                contains: vec!["".into(), "ERRNO 98".into()],
+                hint: "端口被占用（自定义）".into(),
+            },
+        ];
+        let disk_log = "OSError: [Errno 28] No space left on device: 'data/memory.db'\n";
+        assert_eq!(
+            classify_startup_failure(disk_log, &custom),
+            ("disk-full".to_string(), "磁盘空间不足".to_string())
+        );
+        // 自定义规则先于内置规则匹配，且不区分大小写；空子串不会匹配任何日志
+        assert_eq!(classify_startup_failure("[Errno 98] address already in use", &custom).0, "port-conflict");
+        assert_eq!(classify_startup_failure("UnicodeDecodeError: ...", &custom).0, "encoding");
+    }
+
+    #[test]
+    fn clean_log_text_collapses_pip_progress_bars() {
+        // pip 24 的 rich 进度条：隐藏光标、\r 覆写同一行、256 色 / 真彩色 SGR
+        let captured = "Collecting chromadb\r\n  Downloading chromadb-0.5.23-py3-none-any.whl (628 kB)\r\n\x1b[?25l     \x1b[90m━━━━━━━━━━\x1b[0m \x1b[32m0.0/628.0 kB\x1b[0m \x1b[31m?\x1b[0m eta \x1b[36m-:--:--\x1b[0m\r     \x1b[38;2;114;156;31m━━━━━━━━━━\x1b[0m \x1b[32m628.0/628.0 kB\x1b[0m \x1b[31m4.1 MB/s\x1b[0m eta \x1b[36m0:00:00\x1b[0m\r\n\x1b[?25hInstalling collected packages: chromadb\r\nSuccessfully installed chromadb-0.5.23\r\n";
+        assert_eq!(
+            clean_log_text(captured),
+            "Collecting chromadb\n  Downloading chromadb-0.5.23-py3-none-any.whl (628 kB)\n     ━━━━━━━━━━ 628.0/628.0 kB 4.1 MB/s eta 0:00:00\nInstalling collected packages: chromadb\nSuccessfully installed chromadb-0.5.23\n"
+        );
+    }
+
+    #[test]
+    fn clean_log_text_strips_rich_links_and_titles() {
+        // rich 的 OSC 8 超链接（ST 结尾）、窗口标题（BEL 结尾）、tput sgr0 的 ESC ( B
+        let captured = "\x1b]0;openakita\x07\x1b[1;31mERROR\x1b(B\x1b[m see \x1b]8;id=1;https://pip.pypa.io\x1b\\pip docs\x1b]8;;\x1b\\ for help";
+        assert_eq!(clean_log_text(captured), "ERROR see pip docs for help");
+        assert_eq!(clean_log_text("plain\nlines\n"), "plain\nlines\n");
+    }
+
+    #[test]
+    fn traceback_lines_inherit_error_level() {
+        let log = [
+            "2026-10-16 10:00:00 INFO     openakita.main: starting",
+            "2026-10-16 10:00:01 ERROR    openakita.core.agent: tool call failed",
+            "Traceback (most recent call last):",
+            "  File \"/app/openakita/core/agent.py\", line 42, in run",
+            "    raise ValueError(\"bad input\")",
+            "ValueError: bad input",
+            "",
+            "During handling of the above exception, another exception occurred:",
+            "pydantic_core._pydantic_core.ValidationError: 1 validation error for Settings",
+            "2026-10-16 10:00:02 INFO     openakita.main: recovered",
+            "plain stdout line",
+        ];
+        let levels = effective_log_levels(log);
+        assert_eq!(levels[0], Some(1));
+        assert!(levels[1..9].iter().all(|lv| *lv == Some(3)), "{levels:?}");
+        assert_eq!(levels[9], Some(1));
+        // 非续行的无级别输出不继承
+        assert_eq!(levels[10], None);
+        assert_eq!(effective_log_levels(["  indented without a leveled line before"]), vec![None]);
+    }
+
+    #[test]
+    fn command_line_scan_spawns_one_child_process() {
+        use std::cell::Cell;
+        // 15 个 python 进程，原生读取全部失败（如受保护进程）：整轮扫描只允许一次批量 PowerShell
+        let pids: Vec<u32> = (1000..1015).collect();
+        let spawned = Cell::new(0);
+        let batch = || {
+            spawned.set(spawned.get() + 1);
+            Some(pids.iter().map(|&p| (p, format!("python -m openakita.main serve #{p}"))).collect())
+        };
+        let per_pid = |_| {
+            spawned.set(spawned.get() + 1);
+            None
+        };
+        let map = resolve_command_lines(&pids, |_| None, batch, per_pid);
+        assert_eq!(spawned.get(), 1);
+        assert_eq!(map.len(), pids.len());
+
+        // 原生读取全部成功时不启动任何子进程
+        spawned.set(0);
+        let map = resolve_command_lines(
+            &pids,
+            |p| Some(format!("python #{p}")),
+            || {
+                spawned.set(spawned.get() + 1);
+                None
+            },
+            per_pid,
+        );
+        assert_eq!((spawned.get(), map.len()), (0, pids.len()));
+
+        // 批量查询失败才逐个回退
+        spawned.set(0);
+        let map = resolve_command_lines(
+            &pids[..3],
+            |_| None,
+            || {
+                spawned.set(spawned.get() + 1);
+                None
+            },
+            |p| {
+                spawned.set(spawned.get() + 1);
+                Some(format!("python #{p}"))
+            },
+        );
+        assert_eq!((spawned.get(), map.len()), (4, 3));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn venv_wrapper_runs_python_module() {
+        use std::os::unix::fs::PermissionsExt;
+        // 目录名含空格和 $，检验 wrapper 中的引号/转义
+        let root = std::env::temp_dir().join(format!("openakita wrapper $HOME {}", std::process::id()));
+        let venv_bin = root.join("venv").join("bin");
+        let bin_dir = root.join("bin");
+        fs::create_dir_all(&venv_bin).unwrap();
+        fs::create_dir_all(&bin_dir).unwrap();
+        // 假的 venv python：逐行打印收到的参数
+        let python = venv_bin.join("python3");
+        fs::write(&python, "#!/bin/sh\nfor a in \"$@\"; do echo \"$a\"; done\n").unwrap();
+        fs::set_permissions(&python, fs::Permissions::from_mode(0o755)).unwrap();
+
+        create_wrapper_script(&bin_dir, "openakita", &python).unwrap();
+        let out = Command::new(wrapper_script_path(&bin_dir, "openakita"))
+            .args(["serve", "--workspace", "my ws"])
+            .output()
+            .unwrap();
+        assert!(out.status.success(), "{out:?}");
+        assert_eq!(
+            String::from_utf8_lossy(&out.stdout),
+            "-m\nopenakita.main\nserve\n--workspace\nmy ws\n"
+        );
+
+        // 打包的 openakita-server 直接调用，不带 -m
+        assert!(!generate_wrapper_content(Path::new("/opt/openakita/openakita-server")).contains(" -m "));
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    fn env_entries(pairs: &[(&str, &str)]) -> Vec<EnvEntry> {
+        pairs
+            .iter()
+            .map(|(k, v)| EnvEntry { key: k.to_string(), value: v.to_string() })
+            .collect()
+    }
+
+    #[test]
+    fn update_env_keeps_comment_sections_and_groups_new_keys() {
+        let original = "# ── LLM ──\nOPENAI_API_KEY=sk-old\nANTHROPIC_API_KEY=ak  # primary\n\n# ── Server ──\nAPI_PORT=18900\n";
+        // 新键按传入顺序（而非字母序）追加到 Setup Center 分组
+        let updated = update_env_content(original, &env_entries(&[("ZETA", "1"), ("API_PORT", "19000"), ("ALPHA", "2")]));
+        assert_eq!(
+            updated,
+            "# ── LLM ──\nOPENAI_API_KEY=sk-old\nANTHROPIC_API_KEY=ak  # primary\n\n# ── Server ──\nAPI_PORT=19000\n\n# added by Setup Center\nZETA=1\nALPHA=2\n"
+        );
+        // 再次新增时并入已有分组（分组后面用户自己的内容保持不动）
+        let with_user_tail = format!("{updated}\n# mine\nX=1\n");
+        assert_eq!(
+            update_env_content(&with_user_tail, &env_entries(&[("BETA", "3")])),
+            format!("{}BETA=3\n\n# mine\nX=1\n", updated)
+        );
+        // 没有变化时原样返回
+        assert_eq!(update_env_content(original, &[]), original);
+    }
+
+    #[test]
+    fn update_env_keeps_inline_comments() {
+        let original = "API_PORT=18900  # keep in sync with nginx\nNAME=\"my bot\" # display name\nPLAIN=a#b\n";
+        let updated = update_env_content(
+            original,
+            &env_entries(&[("API_PORT", "19000"), ("NAME", "new bot"), ("PLAIN", "c")]),
+        );
+        assert_eq!(
+            updated,
+            "API_PORT=19000  # keep in sync with nginx\nNAME=\"new bot\" # display name\nPLAIN=c\n"
+        );
+        // 写回后再解析，值与注释分离正确
+        let kv: Vec<(String, String)> = parse_env_lines(&updated)
+            .into_iter()
+            .filter_map(|l| match l {
+                EnvLine::Pair { key, value, .. } => Some((key, value)),
+                EnvLine::Raw(_) => None,
+            })
+            .collect();
+        assert_eq!(kv[0], ("API_PORT".to_string(), "19000".to_string()));
+        assert_eq!(kv[1], ("NAME".to_string(), "new bot".to_string()));
+    }
+
+    #[test]
+    fn env_values_drop_quotes_and_comment_only_values() {
+        let kv: Vec<(String, String)> = parse_env_lines("A=\"\"\nB= # todo\nC=#x\nD='q' # note\n")
+            .into_iter()
+            .filter_map(|l| match l {
+                EnvLine::Pair { key, value, .. } => Some((key, value)),
+                EnvLine::Raw(_) => None,
+            })
+            .collect();
+        let expected = [("A", ""), ("B", ""), ("C", "#x"), ("D", "q")];
+        assert_eq!(kv, expected.map(|(k, v)| (k.to_string(), v.to_string())));
+    }
+
+    #[test]
+    fn update_env_collapses_duplicate_keys_to_last_occurrence() {
+        let original = "A=1\nB=2\n\n# section\nA=3  # later wins\nC=4\nC=5\n";
+        assert_eq!(
+            update_env_content(original, &env_entries(&[("A", "9")])),
+            "B=2\n\n# section\nA=9  # later wins\nC=4\nC=5\n"
+        );
+        // 空值删除该键的全部重复行，空行分组保留
+        assert_eq!(
+            update_env_content(original, &env_entries(&[("A", "")])),
+            "B=2\n\n# section\nC=4\nC=5\n"
+        );
+        // 多行引号值整体替换
+        assert_eq!(
+            update_env_content("CERT=\"line1\nline2\"\nX=1\n", &env_entries(&[("CERT", "new")])),
+            "CERT=new\nX=1\n"
+        );
+    }
+
+    #[test]
+    fn atomic_write_uses_sibling_temp_file() {
+        let dir = std::env::temp_dir().join(format!("openakita-atomic-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let env_path = dir.join(".env");
+        assert_eq!(atomic_temp_path(&env_path).unwrap(), dir.join(".env.tmp"));
+
+        // 模拟上次写入中途被杀：原文件完整，残留半截临时文件
+        fs::write(&env_path, "API_PORT=18900\n").unwrap();
+        fs::write(dir.join(".env.tmp"), "API_PO").unwrap();
+        assert_eq!(read_env_kv(&env_path), vec![("API_PORT".to_string(), "18900".to_string())]);
+
+        write_file_atomic(&env_path, b"API_PORT=19000\n").unwrap();
+        assert_eq!(fs::read_to_string(&env_path).unwrap(), "API_PORT=19000\n");
+        assert!(!dir.join(".env.tmp").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn concurrent_env_updates_serialize_per_workspace() {
+        let ws = format!("lock-test-{}", std::process::id());
+        assert!(std::sync::Arc::ptr_eq(&workspace_write_lock(&ws), &workspace_write_lock(&ws)));
+        assert!(!std::sync::Arc::ptr_eq(&workspace_write_lock(&ws), &workspace_write_lock("other")));
+
+        let dir = std::env::temp_dir().join(format!("openakita-envlock-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let env_path = dir.join(".env");
+        fs::write(&env_path, "# base\n").unwrap();
+        // 与 workspace_update_env 相同的加锁读-改-写；不串行化时后写会覆盖先写的键
+        let handles: Vec<_> = (0..8)
+            .map(|n| {
+                let (ws, env_path) = (ws.clone(), env_path.clone());
+                std::thread::spawn(move || {
+                    let lock = workspace_write_lock(&ws);
+                    let _guard = lock.lock().unwrap();
+                    let existing = fs::read_to_string(&env_path).unwrap();
+                    std::thread::sleep(Duration::from_millis(5));
+                    let updated = update_env_content(&existing, &env_entries(&[(&format!("KEY_{n}"), "1")]));
+                    write_file_atomic(&env_path, updated.as_bytes()).unwrap();
+                })
+            })
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+        let keys: std::collections::HashSet<String> = read_env_kv(&env_path).into_iter().map(|(k, _)| k).collect();
+        assert_eq!(keys.len(), 8);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn autostart_args_allow_only_existing_workspace() {
+        let workspaces = vec![WorkspaceMeta {
+            id: "default".into(),
+            name: "Default".into(),
+            created_at: None,
+            last_started_at: None,
+        }];
+        let args = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert!(validate_autostart_args(&[], &workspaces).is_ok());
+        assert!(validate_autostart_args(&args(&["--workspace", "default"]), &workspaces).is_ok());
+        assert!(validate_autostart_args(&args(&["--workspace", "missing"]), &workspaces).is_err());
+        for flag in ["--status", "--headless", "--background", "--first-run", "--clean-env"] {
+            assert!(validate_autostart_args(&args(&[flag]), &workspaces).is_err(), "{flag}");
+        }
+        assert!(validate_autostart_args(&args(&["--workspace", "default", "--status"]), &workspaces).is_err());
+    }
+
+    #[test]
+    fn descendants_post_order_skips_reused_parent_pids() {
+        // 10 -> 11 -> 12；20 的登记父 PID 是 10，但创建时间早于 10（PID 被复用）
+        let edges = [(11, 10), (12, 11), (20, 10), (21, 20)];
+        let started = |pid: u32| match pid {
+            10 => Some(1_000),
+            11 => Some(1_005),
+            12 => Some(1_006),
+            20 => Some(500),
+            21 => Some(600),
+            _ => None,
+        };
+        let all = descendants_post_order(10, &edges, &|_, _| true);
+        assert_eq!(all, vec![12, 11, 21, 20]);
+        let real = descendants_post_order(10, &edges, &|c, p| !started_before_parent(started(c), started(p)));
+        assert_eq!(real, vec![12, 11]);
+        // 时间未知时按父子关系处理；同一秒内创建不算早于父进程
+        assert!(!started_before_parent(None, Some(1)));
+        assert!(!started_before_parent(Some(1), None));
+        assert!(!started_before_parent(Some(7), Some(7)));
+    }
+
+    #[test]
+    fn known_template_hashes_exclude_user_content() {
+        // 用户清空的文件不能当作旧模板被覆盖
+        assert!(!is_known_template_hash("personas/user_custom.md", &template_content_hash("")));
+        assert!(!is_known_template_hash("personas/user_custom.md", &template_content_hash("我的自定义人格\n")));
+        assert!(!is_known_template_hash("personas/default.md", &template_content_hash("")));
+    }
+
+    #[test]
+    fn partial_download_if_range_prefers_strong_etag() {
+        let meta = |etag: Option<&str>, lm: Option<&str>| PartialDownloadMeta {
+            url: "https://example.com/a.zip".into(),
+            etag: etag.map(String::from),
+            last_modified: lm.map(String::from),
+        };
+        let lm = "Wed, 21 Oct 2026 07:28:00 GMT";
+        assert_eq!(meta(Some("\"abc\""), Some(lm)).if_range(), Some("\"abc\""));
+        assert_eq!(meta(Some("W/\"abc\""), Some(lm)).if_range(), Some(lm));
+        assert_eq!(meta(Some("W/\"abc\""), None).if_range(), None);
+        assert_eq!(meta(None, None).if_range(), None);
+
+        assert_eq!(content_range_start("bytes 1024-2047/4096"), Some(1024));
+        assert_eq!(content_range_start("bytes 0-99/*"), Some(0));
+        assert_eq!(content_range_start("bytes */4096"), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn sysconf_units_are_sane() {
+        assert!(unix::clock_ticks_per_sec() > 0);
+        assert!(unix::page_size().is_power_of_two());
+        let (cpu_secs, rss, threads) = sample_process_resources(std::process::id()).unwrap();
+        assert!(cpu_secs >= 0.0 && rss > 0 && threads >= 1);
+    }
+
+    #[test]
+    fn backend_owner_managed_is_tauri() {
+        assert_eq!(backend_owner(true, None, 1_000), Some(STARTED_BY_TAURI));
+        assert_eq!(backend_owner(true, Some(500), 1_000), Some(STARTED_BY_TAURI));
+    }
+
+    #[test]
+    fn backend_owner_started_during_session_is_external() {
+        assert_eq!(backend_owner(false, Some(1_001), 1_000), Some(STARTED_BY_EXTERNAL));
+    }
+
+    #[test]
+    fn backend_owner_leaves_previous_session_orphans_untagged() {
+        assert_eq!(backend_owner(false, Some(999), 1_000), None);
+        assert_eq!(backend_owner(false, Some(1_000), 1_000), None);
+        assert_eq!(backend_owner(false, None, 1_000), None);
+    }
+}
@@ -3,12 +3,17 @@
 //! 每次发版如果配置结构发生变化，在此添加迁移函数。
 //! 应用启动时自动执行，链式升级：v1 → v2 → v3 → ... → 当前版本。
 
+use serde::Serialize;
 use serde_json::Value;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 /// 当前配置文件版本。每次添加迁移时递增此值。
-pub const CURRENT_CONFIG_VERSION: u32 = 1;
+pub const CURRENT_CONFIG_VERSION: u32 = 3;
+
+/// 迁移前备份文件名前缀，完整文件名为 `state.json.backup-v<迁移前版本>`
+const BACKUP_PREFIX: &str = "state.json.backup-v";
 
 type MigrationFn = fn(state: &mut Value, root: &Path) -> Result<(), String>;
 
@@ -16,8 +21,8 @@ type MigrationFn = fn(state: &mut Value, root: &Path) -> Result<(), String>;
 /// 元组格式: (目标版本号, 迁移函数)
 fn get_migrations() -> Vec<(u32, MigrationFn)> {
     vec![
-        // 示例（下一个版本需要迁移时取消注释并实现）：
-        // (2, migrate_v1_to_v2),
+        (2, migrate_v1_to_v2),
+        (3, migrate_v2_to_v3),
     ]
 }
 
@@ -26,6 +31,7 @@ fn get_migrations() -> Vec<(u32, MigrationFn)> {
 /// - 迁移前自动备份 state.json
 /// - 迁移是单向的（不支持降级）
 /// - 如果没有需要执行的迁移，直接返回 Ok
+/// - 配置由更新版本写入（configVersion 高于 CURRENT_CONFIG_VERSION）时返回错误，且不改动文件
 pub fn run_migrations(state_path: &Path, root: &Path) -> Result<(), String> {
     if !state_path.exists() {
         return Ok(());
@@ -41,6 +47,10 @@ pub fn run_migrations(state_path: &Path, root: &Path) -> Result<(), String> {
         .and_then(|v| v.as_u64())
         .unwrap_or(1) as u32;
 
+    if current_version > CURRENT_CONFIG_VERSION {
+        return Err(newer_version_message(current_version));
+    }
+
     if current_version >= CURRENT_CONFIG_VERSION {
         // 确保 configVersion 字段存在
         if state.get("configVersion").is_none() {
@@ -54,10 +64,7 @@ pub fn run_migrations(state_path: &Path, root: &Path) -> Result<(), String> {
     }
 
     // 备份当前 state.json
-    let backup_name = format!(
-        "state.json.backup-v{}",
-        current_version
-    );
+    let backup_name = format!("{BACKUP_PREFIX}{current_version}");
     let backup_path = root.join(&backup_name);
     if let Err(e) = fs::copy(state_path, &backup_path) {
         eprintln!("Warning: could not backup state.json: {e}");
@@ -86,17 +93,238 @@ pub fn run_migrations(state_path: &Path, root: &Path) -> Result<(), String> {
     Ok(())
 }
 
+/// state.json 的 configVersion 高于本版本支持的版本时返回该版本号（用户从新版降级安装旧版）。
+/// 文件不存在或无法解析时返回 None。
+pub fn newer_config_version(state_path: &Path) -> Option<u32> {
+    let content = fs::read_to_string(state_path).ok()?;
+    let state: Value = serde_json::from_str(&content).ok()?;
+    let version = state.get("configVersion").and_then(|v| v.as_u64())? as u32;
+    (version > CURRENT_CONFIG_VERSION).then_some(version)
+}
+
+pub fn newer_version_message(version: u32) -> String {
+    format!(
+        "配置文件由更新版本的 OpenAkita 创建（configVersion {version}，当前版本支持 {CURRENT_CONFIG_VERSION}），\
+         为避免损坏配置，本版本不会修改 state.json。请升级到最新版本。"
+    )
+}
+
+/// 一份迁移前备份（供恢复界面列出）
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupInfo {
+    /// 备份时的配置版本（即迁移前版本）
+    pub version: u32,
+    pub path: String,
+    /// 备份文件修改时间（unix epoch 秒）
+    pub modified_at: u64,
+    pub size: u64,
+}
+
+fn backup_path(root: &Path, version: u32) -> PathBuf {
+    root.join(format!("{BACKUP_PREFIX}{version}"))
+}
+
+/// 列出 root 下所有 `state.json.backup-v<N>`，按版本从新到旧排序。
+pub fn list_backups(root: &Path) -> Vec<BackupInfo> {
+    let mut out = Vec::new();
+    let Ok(rd) = fs::read_dir(root) else {
+        return out;
+    };
+    for entry in rd.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let Some(version) = name.strip_prefix(BACKUP_PREFIX).and_then(|v| v.parse::<u32>().ok()) else {
+            continue;
+        };
+        let Ok(meta) = entry.metadata() else { continue };
+        if !meta.is_file() {
+            continue;
+        }
+        let modified_at = meta
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        out.push(BackupInfo {
+            version,
+            path: entry.path().to_string_lossy().to_string(),
+            modified_at,
+            size: meta.len(),
+        });
+    }
+    out.sort_by_key(|b| std::cmp::Reverse(b.version));
+    out
+}
+
+/// 用 `state.json.backup-v<version>` 覆盖 state.json，然后重新执行迁移链。
+/// 备份不存在或不是合法 JSON 时返回错误，state.json 保持不变。
+pub fn restore_backup(state_path: &Path, root: &Path, version: u32) -> Result<(), String> {
+    let backup = backup_path(root, version);
+    if !backup.is_file() {
+        return Err(format!("backup not found: {}", backup.display()));
+    }
+    let content = fs::read_to_string(&backup)
+        .map_err(|e| format!("read backup failed: {e}"))?;
+    serde_json::from_str::<Value>(&content)
+        .map_err(|e| format!("backup is not valid JSON: {e}"))?;
+    fs::copy(&backup, state_path)
+        .map_err(|e| format!("restore state.json failed: {e}"))?;
+    eprintln!("Config restored from {}", backup.display());
+    run_migrations(state_path, root)
+}
+
 // ═══════════════════════════════════════════════════════════════════════
 // 迁移函数区域 — 每个版本的迁移函数放在下面
 // ═══════════════════════════════════════════════════════════════════════
 
-// 示例迁移函数（留作参考，下一次需要迁移时照此模式添加）：
-//
-// fn migrate_v1_to_v2(state: &mut Value, root: &Path) -> Result<(), String> {
-//     // 例如：重命名字段、添加新字段、迁移工作区配置等
-//     if let Some(obj) = state.as_object_mut() {
-//         // 添加新字段的默认值
-//         obj.entry("newField").or_insert(serde_json::json!("default"));
-//     }
-//     Ok(())
-// }
+/// v1 → v2：为每个工作区补充 createdAt / lastStartedAt 字段。
+/// createdAt 取工作区目录的修改时间（无法获取时取当前时间），lastStartedAt 留空。
+fn migrate_v1_to_v2(state: &mut Value, root: &Path) -> Result<(), String> {
+    let Some(workspaces) = state.get_mut("workspaces").and_then(|v| v.as_array_mut()) else {
+        return Ok(());
+    };
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    for ws in workspaces.iter_mut() {
+        let Some(obj) = ws.as_object_mut() else { continue };
+        if obj.get("createdAt").map(|v| v.is_null()).unwrap_or(true) {
+            let id = obj.get("id").and_then(|v| v.as_str()).unwrap_or("");
+            let created_at = fs::metadata(root.join("workspaces").join(id))
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs())
+                .unwrap_or(now);
+            obj.insert("createdAt".into(), serde_json::json!(created_at));
+        }
+        obj.entry("lastStartedAt").or_insert(Value::Null);
+    }
+    Ok(())
+}
+
+/// 从顶层移入 settings 对象的字段（v3）
+const SETTINGS_KEYS: &[&str] = &["autoStartBackend", "autoUpdate", "installMode", "lastInstalledVersion"];
+
+/// v2 → v3：把零散的顶层开关 autoStartBackend / autoUpdate / installMode / lastInstalledVersion
+/// 收拢到 settings 对象下。settings 中已有的值优先，顶层旧字段一律移除。
+fn migrate_v2_to_v3(state: &mut Value, _root: &Path) -> Result<(), String> {
+    let Some(obj) = state.as_object_mut() else {
+        return Ok(());
+    };
+    let mut settings = match obj.remove("settings") {
+        Some(Value::Object(m)) => m,
+        _ => serde_json::Map::new(),
+    };
+    for key in SETTINGS_KEYS {
+        if let Some(v) = obj.remove(*key) {
+            if !v.is_null() && settings.get(*key).map(|s| s.is_null()).unwrap_or(true) {
+                settings.insert((*key).to_string(), v);
+            }
+        }
+    }
+    obj.insert("settings".into(), Value::Object(settings));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// 每个测试独立的临时目录
+    fn temp_root(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("openakita-migrations-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn v2_to_v3_moves_top_level_settings() {
+        let mut state = json!({
+            "configVersion": 2,
+            "currentWorkspaceId": "default",
+            "workspaces": [{ "id": "default", "name": "默认工作区", "createdAt": 1700000000, "lastStartedAt": null }],
+            "autoStartBackend": true,
+            "autoUpdate": false,
+            "installMode": null,
+            "lastInstalledVersion": "1.2.0",
+            "settings": { "autoUpdate": true },
+            "pipMirror": "https://pypi.tuna.tsinghua.edu.cn/simple"
+        });
+        migrate_v2_to_v3(&mut state, Path::new("")).unwrap();
+
+        for key in SETTINGS_KEYS {
+            assert!(state.get(*key).is_none(), "top-level {key} should be removed");
+        }
+        // settings 中已有的值优先；null 的旧字段不写入
+        assert_eq!(
+            state["settings"],
+            json!({ "autoUpdate": true, "autoStartBackend": true, "lastInstalledVersion": "1.2.0" })
+        );
+        assert_eq!(state["currentWorkspaceId"], "default");
+        assert_eq!(state["pipMirror"], "https://pypi.tuna.tsinghua.edu.cn/simple");
+    }
+
+    #[test]
+    fn v2_to_v3_replaces_non_object_settings() {
+        let mut state = json!({ "configVersion": 2, "settings": "broken", "installMode": "venv" });
+        migrate_v2_to_v3(&mut state, Path::new("")).unwrap();
+        assert_eq!(state["settings"], json!({ "installMode": "venv" }));
+    }
+
+    #[test]
+    fn v1_state_file_migrates_to_current_version() {
+        let root = temp_root("v1");
+        fs::create_dir_all(root.join("workspaces").join("default")).unwrap();
+        let state_path = root.join("state.json");
+        let v1 = json!({
+            "currentWorkspaceId": "default",
+            "workspaces": [{ "id": "default", "name": "默认工作区" }],
+            "autoStartBackend": true,
+            "lastInstalledVersion": "1.0.0"
+        });
+        fs::write(&state_path, serde_json::to_string_pretty(&v1).unwrap()).unwrap();
+
+        run_migrations(&state_path, &root).unwrap();
+
+        let migrated: Value = serde_json::from_str(&fs::read_to_string(&state_path).unwrap()).unwrap();
+        assert_eq!(migrated["configVersion"], json!(CURRENT_CONFIG_VERSION));
+        let ws = &migrated["workspaces"][0];
+        assert!(ws["createdAt"].as_u64().unwrap() > 0);
+        assert!(ws["lastStartedAt"].is_null());
+        assert_eq!(
+            migrated["settings"],
+            json!({ "autoStartBackend": true, "lastInstalledVersion": "1.0.0" })
+        );
+        assert!(migrated.get("autoStartBackend").is_none());
+
+        // 迁移前的原文件备份为 state.json.backup-v1，可列出
+        let backup: Value = serde_json::from_str(&fs::read_to_string(backup_path(&root, 1)).unwrap()).unwrap();
+        assert_eq!(backup, v1);
+        assert_eq!(list_backups(&root).iter().map(|b| b.version).collect::<Vec<_>>(), vec![1]);
+
+        // 再次执行不再产生变化
+        run_migrations(&state_path, &root).unwrap();
+        let again: Value = serde_json::from_str(&fs::read_to_string(&state_path).unwrap()).unwrap();
+        assert_eq!(again, migrated);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn newer_config_version_is_left_untouched() {
+        let root = temp_root("newer");
+        let state_path = root.join("state.json");
+        let content = format!("{{\"configVersion\": {}, \"futureField\": 1}}", CURRENT_CONFIG_VERSION + 1);
+        fs::write(&state_path, &content).unwrap();
+
+        assert!(run_migrations(&state_path, &root).is_err());
+        assert_eq!(fs::read_to_string(&state_path).unwrap(), content);
+        assert_eq!(newer_config_version(&state_path), Some(CURRENT_CONFIG_VERSION + 1));
+        assert!(list_backups(&root).is_empty());
+        let _ = fs::remove_dir_all(&root);
+    }
+}
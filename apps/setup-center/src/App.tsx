@@ -963,7 +963,7 @@ export function App() {
 
   // ── Onboarding Wizard (首次安装引导) ──
   type OnboardingStep = "ob-welcome" | "ob-llm" | "ob-im" | "ob-modules" | "ob-cli" | "ob-progress" | "ob-done";
-  type ModuleInfo = { id: string; name: string; description: string; installed: boolean; bundled: boolean; sizeMb: number; category: string };
+  type ModuleInfo = { id: string; name: string; description: string; installed: boolean; bundled: boolean; sizeMb: number; category: string; installedVersions: Record<string, string> };
   const [obStep, setObStep] = useState<OnboardingStep>("ob-welcome");
   const [obModules, setObModules] = useState<ModuleInfo[]>([]);
   const [obSelectedModules, setObSelectedModules] = useState<Set<string>>(new Set());
@@ -1332,13 +1332,23 @@ export function App() {
             // 此处轮询等待直到服务就绪或确认启动失败。
             if (!alreadyConnected && !cancelled) {
               let handled = false;
+              let spawnDone = false;       // Rust 线程已完成（进程已 spawn 或失败），由 auto-start-result 事件置位
+              let spawnFailed = false;
+              let unlistenAutoStart: (() => void) | null = null;
+              try {
+                // 先订阅再查询，避免线程在两者之间结束导致漏掉事件
+                unlistenAutoStart = await listen<{ outcome: string }>("auto-start-result", (ev) => {
+                  spawnDone = true;
+                  spawnFailed = ev.payload?.outcome === "failed";
+                });
+              } catch { /* 事件不可用时按超时兜底 */ }
               try {
                 const autoStarting = await invoke<boolean>("is_backend_auto_starting");
+                if (!autoStarting) spawnDone = true;
                 if (autoStarting) {
                   handled = true;
                   setBusy(t("topbar.autoStarting"));
                   let serviceReady = false;
-                  let spawnDone = false;       // Rust 线程已完成（进程已 spawn 或失败）
                   let postSpawnWait = 0;       // spawn 完成后的额外等待次数
 
                   for (let attempt = 0; attempt < 90 && !cancelled; attempt++) {
@@ -1348,13 +1358,7 @@ export function App() {
                       serviceReady = await connectToRunningService(localUrl);
                       if (serviceReady) break;
                     } catch { /* still starting */ }
-                    // 检查 Rust 端 spawn 是否完成
-                    if (!spawnDone) {
-                      try {
-                        const still = await invoke<boolean>("is_backend_auto_starting");
-                        if (!still) spawnDone = true;
-                      } catch { spawnDone = true; }
-                    }
+                    if (spawnFailed) break;
                     // spawn 完成后：进程已启动但 HTTP 可能尚未就绪，
                     // 额外等待最多 60 秒（30 次 × 2s）让 FastAPI+uvicorn 初始化
                     if (spawnDone) {
@@ -1380,6 +1384,7 @@ export function App() {
                   }
                 }
               } catch { /* is_backend_auto_starting 不可用，忽略 */ }
+              unlistenAutoStart?.();
               // 没有自动启动 → 显式标记服务未运行（解除 serviceStatus===null 的锁定）
               if (!handled && !cancelled) {
                 setServiceStatus({ running: false, pid: null, pidFile: "" });
@@ -1556,7 +1561,7 @@ export function App() {
         const status = String(p.status || "");
         const moduleId = String(p.moduleId || "");
         if (msg) {
-          const prefix = status === "retrying" ? "🔄" : status === "error" ? "❌" : status === "done" ? "✅" : status === "warning" ? "⚠️" : status === "restart-hint" ? "🔁" : "📦";
+          const prefix = status === "retrying" ? "🔄" : status === "error" ? "❌" : status === "done" ? "✅" : status === "warning" ? "⚠️" : status === "restart-hint" ? "🔁" : status === "cancelled" ? "⏹️" : "📦";
           setObDetailLog(prev => [...prev, `[${new Date().toLocaleTimeString()}] ${prefix} [${moduleId}] ${msg}`]);
         }
       });
@@ -1564,6 +1569,24 @@ export function App() {
     return () => { if (unlisten) unlisten(); };
   }, []);
 
+  // config written by a newer release (app was downgraded): state.json is kept read-only
+  useEffect(() => {
+    let unlisten: null | (() => void) = null;
+    (async () => {
+      unlisten = await listen("config-version-newer", (ev) => {
+        const p = ev.payload as any;
+        if (p?.message) setError(String(p.message));
+      });
+      try {
+        const warning = await invoke<string | null>("get_config_version_warning");
+        if (warning) setError(warning);
+      } catch { /* older backend without this command */ }
+    })();
+    return () => {
+      if (unlisten) unlisten();
+    };
+  }, []);
+
   // tray quit failed: service still running
   useEffect(() => {
     let unlisten: null | (() => void) = null;
@@ -3931,23 +3954,24 @@ export function App() {
   async function doDownloadAndInstall() {
     if (!updateAvailable) return;
     setUpdateProgress({ status: "downloading", percent: 0 });
+    // 下载/安装在 Rust 端进行（走用户配置的代理），通过事件回报进度与阶段
+    const unlisteners = await Promise.all([
+      listen<{ downloaded: number; total: number | null }>("app-update-progress", (ev) => {
+        const { downloaded, total } = ev.payload;
+        const percent = total ? Math.round((downloaded / total) * 100) : 0;
+        setUpdateProgress({ status: "downloading", percent });
+      }),
+      listen<{ phase: string }>("app-update-status", (ev) => {
+        if (ev.payload.phase === "installing") setUpdateProgress({ status: "installing" });
+      }),
+    ]);
     try {
-      let totalBytes = 0;
-      let downloadedBytes = 0;
-      await updateAvailable.downloadAndInstall((event) => {
-        if (event.event === "Started" && event.data.contentLength) {
-          totalBytes = event.data.contentLength;
-        } else if (event.event === "Progress") {
-          downloadedBytes += event.data.chunkLength;
-          const percent = totalBytes > 0 ? Math.round((downloadedBytes / totalBytes) * 100) : 0;
-          setUpdateProgress({ status: "downloading", percent });
-        } else if (event.event === "Finished") {
-          setUpdateProgress({ status: "installing" });
-        }
-      });
+      await invoke<string | null>("download_and_install_update");
       setUpdateProgress({ status: "done" });
     } catch (err) {
       setUpdateProgress({ status: "error", error: String(err) });
+    } finally {
+      unlisteners.forEach((u) => u());
     }
   }
 
@@ -4037,7 +4061,15 @@ export function App() {
         setError(t("topbar.startFail"));
       }
     } catch (e) {
-      setError(String(e));
+      // 启动后立即退出时后端返回结构化错误 JSON：{ class, hint, logTail, logPath }
+      let msg = String(e);
+      try {
+        const se = JSON.parse(msg);
+        if (se && typeof se.hint === "string") {
+          msg = `${se.hint}\n${se.logPath}\n\n--- log tail ---\n${se.logTail}`;
+        }
+      } catch { /* plain error string */ }
+      setError(msg);
     } finally {
       setBusy(null);
     }
@@ -6851,12 +6883,14 @@ export function App() {
       registeredCommands: string[];
       inPath: boolean;
       binDir: string;
+      pathScope: "user" | "system" | null;
     } | null>(null);
     const [cliLoading, setCliLoading] = useState(false);
     const [cliMsg, setCliMsg] = useState("");
     const [cliRegOpenakita, setCliRegOpenakita] = useState(true);
     const [cliRegOa, setCliRegOa] = useState(true);
     const [cliRegPath, setCliRegPath] = useState(true);
+    const [cliPathScope, setCliPathScope] = useState<"user" | "system">("user");
 
     useEffect(() => {
       loadCliStatus();
@@ -6864,11 +6898,12 @@ export function App() {
 
     async function loadCliStatus() {
       try {
-        const status = await invoke<{ registeredCommands: string[]; inPath: boolean; binDir: string }>("get_cli_status");
+        const status = await invoke<{ registeredCommands: string[]; inPath: boolean; binDir: string; pathScope: "user" | "system" | null }>("get_cli_status");
         setCliStatus(status);
         setCliRegOpenakita(status.registeredCommands.includes("openakita"));
         setCliRegOa(status.registeredCommands.includes("oa"));
         setCliRegPath(status.inPath);
+        if (status.pathScope) setCliPathScope(status.pathScope);
       } catch (e) {
         setCliMsg(`查询 CLI 状态失败: ${String(e)}`);
       }
@@ -6885,7 +6920,7 @@ export function App() {
       setCliLoading(true);
       setCliMsg("");
       try {
-        const result = await invoke<string>("register_cli", { commands: cmds, addToPath: cliRegPath });
+        const result = await invoke<string>("register_cli", { commands: cmds, addToPath: cliRegPath, scope: cliPathScope });
         setCliMsg(`✓ ${result}`);
         await loadCliStatus();
       } catch (e) {
@@ -6909,6 +6944,21 @@ export function App() {
       }
     }
 
+    async function doVerify() {
+      const cmd = cliStatus?.registeredCommands[0];
+      if (!cmd) return;
+      setCliLoading(true);
+      setCliMsg("");
+      try {
+        const v = await invoke<{ runs: boolean; resolvesToWrapper: boolean; hint: string | null }>("cli_verify", { command: cmd });
+        setCliMsg(v.hint ? `${v.runs && v.resolvesToWrapper ? "✓" : "✗"} ${v.hint}` : `✓ ${cmd} 可在新终端中直接使用`);
+      } catch (e) {
+        setCliMsg(`✗ 验证失败: ${String(e)}`);
+      } finally {
+        setCliLoading(false);
+      }
+    }
+
     const hasRegistered = cliStatus && cliStatus.registeredCommands.length > 0;
 
     return (
@@ -6941,7 +6991,13 @@ export function App() {
           </label>
           <label style={{ display: "flex", alignItems: "center", gap: 8, cursor: "pointer", fontSize: 13 }}>
             <input type="checkbox" checked={cliRegPath} onChange={() => setCliRegPath(!cliRegPath)} />
-            <span>添加到系统 PATH</span>
+            <span>添加到 PATH</span>
+            {isWin && cliRegPath && (
+              <select value={cliPathScope} onChange={(e) => setCliPathScope(e.target.value as "user" | "system")} style={{ fontSize: 12 }}>
+                <option value="user">当前用户</option>
+                <option value="system">所有用户（需管理员权限）</option>
+              </select>
+            )}
           </label>
         </div>
 
@@ -6954,6 +7010,11 @@ export function App() {
               注销全部
             </button>
           )}
+          {hasRegistered && (
+            <button onClick={doVerify} disabled={cliLoading} style={{ fontSize: 13 }}>
+              验证
+            </button>
+          )}
         </div>
 
         {cliMsg && (
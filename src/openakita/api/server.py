@@ -14,12 +14,15 @@ FastAPI HTTP API server for OpenAkita.
 from __future__ import annotations
 
 import asyncio
+import hmac
 import logging
+import os
 import socket
 import time
 from typing import Any
 
-from fastapi import FastAPI
+from fastapi import FastAPI, Request
+from fastapi.responses import JSONResponse
 from fastapi.middleware.cors import CORSMiddleware
 
 from .routes import chat, chat_models, config, files, health, im, logs, sessions, skills, token_stats, upload
@@ -29,6 +32,19 @@ logger = logging.getLogger(__name__)
 API_HOST = "127.0.0.1"
 API_PORT = 18900
 
+# Setup Center 启动后端时注入的控制令牌；设置后 /api/shutdown 需携带 Authorization: Bearer <token>
+CONTROL_TOKEN_ENV = "OPENAKITA_CONTROL_TOKEN"
+
+
+def _control_token_ok(request: Request) -> bool:
+    """校验控制令牌。未配置令牌（手动 `openakita serve`）时不做限制。"""
+    expected = os.environ.get(CONTROL_TOKEN_ENV, "")
+    if not expected:
+        return True
+    auth = request.headers.get("authorization", "")
+    scheme, _, token = auth.partition(" ")
+    return scheme.lower() == "bearer" and hmac.compare_digest(token.strip(), expected)
+
 
 def is_port_free(host: str, port: int) -> bool:
     """检测端口是否可用（快速单次检测）。"""
@@ -106,12 +122,16 @@ def create_app(
         }
 
     @app.post("/api/shutdown")
-    async def shutdown():
+    async def shutdown(request: Request):
         """Gracefully shut down the OpenAkita service process.
 
         Uses the shared shutdown_event to trigger the same graceful cleanup
         path as SIGINT/SIGTERM (sessions saved, IM adapters stopped, etc.).
+        Requires the control token when OPENAKITA_CONTROL_TOKEN is set.
         """
+        if not _control_token_ok(request):
+            logger.warning("Shutdown request rejected: missing or invalid control token")
+            return JSONResponse(status_code=401, content={"status": "error", "message": "unauthorized"})
         logger.info("Shutdown requested via API")
         if app.state.shutdown_event is not None:
             app.state.shutdown_event.set()
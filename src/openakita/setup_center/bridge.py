@@ -6,6 +6,7 @@ Setup Center Bridge
 - `python -m openakita.setup_center.bridge list-providers`
 - `python -m openakita.setup_center.bridge list-models --api-type ... --base-url ... [--provider-slug ...]`
 - `python -m openakita.setup_center.bridge list-skills --workspace-dir ...`
+- `python -m openakita.setup_center.bridge preflight --workspace-dir ...`
 
 输出均为 JSON（stdout），错误输出到 stderr 并以非 0 退出码返回。
 """
@@ -39,6 +40,69 @@ def _to_dict(obj: Any) -> Any:
     return obj
 
 
+# 引号值中支持的转义（与 Rust 端 parse_env_value 一致）
+_ENV_ESCAPES = {
+    '"': {"n": "\n", "t": "\t", "r": "\r", "\\": "\\", '"': '"'},
+    "'": {"\\": "\\", "'": "'"},
+}
+
+
+def _read_env_file(env_path: Path) -> dict[str, str]:
+    """按 Setup Center 写入 .env 的规则（Rust 端 parse_env_lines）读取键值。
+
+    引号值去掉引号并反转义（可跨多行）；无引号值中空白之后的 ``#`` 起为行内注释，
+    因此 ``KEY=""`` 与 ``KEY= # todo`` 都读作空字符串。文件不存在时返回空字典。
+    """
+    if not env_path.exists():
+        return {}
+    lines = env_path.read_text(encoding="utf-8").splitlines()
+    result: dict[str, str] = {}
+    i = 0
+    while i < len(lines):
+        line = lines[i].strip()
+        i += 1
+        eq = line.find("=")
+        if line.startswith("#") or eq <= 0 or not line[:eq].strip():
+            continue
+        key, rest = line[:eq].strip(), line[eq + 1 :]
+        text = rest.lstrip()
+        quote = text[:1]
+        if quote in ('"', "'"):
+            chars: list[str] = []
+            j, closed = i, False
+            body = text[1:]
+            while True:
+                k = 0
+                while k < len(body):
+                    c = body[k]
+                    if c == quote:
+                        closed = True
+                        break
+                    if c == "\\" and k + 1 < len(body) and body[k + 1] in _ENV_ESCAPES[quote]:
+                        chars.append(_ENV_ESCAPES[quote][body[k + 1]])
+                        k += 2
+                        continue
+                    chars.append(c)
+                    k += 1
+                if closed or j >= len(lines):
+                    break
+                chars.append("\n")
+                body = lines[j]
+                j += 1
+            if closed:
+                result[key] = "".join(chars)
+                i = j
+                continue
+        # 无引号（或引号未闭合）：空白之后的 # 起为行内注释
+        value = rest
+        for k, c in enumerate(rest):
+            if c == "#" and k > 0 and rest[k - 1].isspace():
+                value = rest[:k]
+                break
+        result[key] = value.strip()
+    return result
+
+
 def list_providers() -> None:
     from openakita.llm.registries import list_providers as _list_providers
 
@@ -193,6 +257,78 @@ async def health_check_endpoint(workspace_dir: str, endpoint_name: str | None) -
     _json_print(results)
 
 
+def preflight(workspace_dir: str) -> None:
+    """启动前配置检查，输出 [{severity, key, message}]，severity 为 error / warning。
+
+    只做本地检查（不联网）：配置能否解析、端点必填字段、API Key 是否已配置、
+    模型是否为已知模型。没有端点、API Key 为空时后端仍能启动（可稍后在界面中配置），
+    因此只报 warning。
+    """
+    from openakita.llm.capabilities import MODEL_CAPABILITIES
+    from openakita.llm.config import ConfigurationError, load_endpoints_config
+
+    wd = Path(workspace_dir).expanduser().resolve()
+    config_path = wd / "data" / "llm_endpoints.json"
+    findings: list[dict] = []
+
+    def add(severity: str, key: str, message: str) -> None:
+        findings.append({"severity": severity, "key": key, "message": message})
+
+    if not config_path.exists():
+        add("error", "llm_endpoints.json", f"端点配置文件不存在: {config_path}")
+        _json_print(findings)
+        return
+
+    # 与后端启动时一致：.env 中的值用于解析 api_key_env
+    for key, value in _read_env_file(wd / ".env").items():
+        os.environ.setdefault(key, value)
+
+    try:
+        endpoints, compiler_endpoints, stt_endpoints, _ = load_endpoints_config(config_path)
+    except ConfigurationError as e:
+        add("error", "llm_endpoints.json", str(e))
+        _json_print(findings)
+        return
+
+    if not endpoints:
+        add("warning", "endpoints", "没有可用的 LLM 端点")
+
+    groups = (
+        ("endpoints", endpoints),
+        ("compiler_endpoints", compiler_endpoints),
+        ("stt_endpoints", stt_endpoints),
+    )
+    for label, eps in groups:
+        for ep in eps:
+            key = f"{label}.{ep.name}"
+            if ep.api_type not in ("anthropic", "openai"):
+                add("error", f"{key}.api_type", f"无效的 api_type: {ep.api_type}")
+            if not ep.base_url.startswith(("http://", "https://")):
+                add("error", f"{key}.base_url", f"无效的 base_url: {ep.base_url}")
+            if ep.api_key_env and not os.environ.get(ep.api_key_env) and not ep.api_key:
+                add(
+                    "warning",
+                    f"{key}.api_key_env",
+                    f"未配置 API Key：环境变量 {ep.api_key_env} 为空",
+                )
+            if not ep.model:
+                add("error", f"{key}.model", "未配置模型")
+                continue
+            known = MODEL_CAPABILITIES.get(ep.provider)
+            if known is not None:
+                model_lower = ep.model.lower()
+                if ep.model not in known and not any(
+                    model_lower.startswith(k.lower()) for k in known
+                ):
+                    add(
+                        "warning",
+                        f"{key}.model",
+                        f"服务商 {ep.provider} 下的未知模型: {ep.model}（能力将按名称推断）",
+                    )
+
+    _json_print(findings)
+
+
 async def health_check_im(workspace_dir: str, channel: str | None) -> None:
     """检测 IM 通道连通性"""
     import httpx
@@ -760,6 +896,9 @@ def main(argv: list[str] | None = None) -> None:
     pi.add_argument("--workspace-dir", required=True, help="工作区目录")
     pi.add_argument("--channel", default="", help="可选：仅检测指定通道 ID（为空=全部）")
 
+    p_pre = sub.add_parser("preflight", help="启动前配置检查（JSON）")
+    p_pre.add_argument("--workspace-dir", required=True, help="工作区目录")
+
     p_ecd = sub.add_parser("ensure-channel-deps", help="检查并自动安装已启用 IM 通道的依赖（JSON）")
     p_ecd.add_argument("--workspace-dir", required=True, help="工作区目录")
 
@@ -817,6 +956,10 @@ def main(argv: list[str] | None = None) -> None:
         )
         return
 
+    if args.cmd == "preflight":
+        preflight(workspace_dir=args.workspace_dir)
+        return
+
     if args.cmd == "ensure-channel-deps":
         ensure_channel_deps(workspace_dir=args.workspace_dir)
         return
@@ -120,3 +120,15 @@ class TestShutdownEndpoint:
         data = resp.json()
         assert data["status"] == "shutting_down"
         assert app.state.shutdown_event.is_set()
+
+    async def test_shutdown_requires_control_token(self, client, app, monkeypatch):
+        monkeypatch.setenv("OPENAKITA_CONTROL_TOKEN", "secret-token")
+        resp = await client.post("/api/shutdown")
+        assert resp.status_code == 401
+        resp = await client.post("/api/shutdown", headers={"Authorization": "Bearer wrong"})
+        assert resp.status_code == 401
+        assert not app.state.shutdown_event.is_set()
+
+        resp = await client.post("/api/shutdown", headers={"Authorization": "Bearer secret-token"})
+        assert resp.status_code == 200
+        assert app.state.shutdown_event.is_set()